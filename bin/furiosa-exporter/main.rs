@@ -0,0 +1,100 @@
+//! An exporter which serves metrics of FuriosaAI NPU devices to Prometheus.
+
+use std::net::SocketAddr;
+use std::path::PathBuf;
+
+use clap::Parser;
+use tokio::net::TcpListener;
+use tracing_subscriber::EnvFilter;
+
+use furiosa_device::metrics::{Exporter, METRICS_PATH};
+use furiosa_device::{DeviceLister, DeviceResult};
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "furiosa-exporter",
+    version,
+    about = "Serves metrics of FuriosaAI NPUs in the OpenMetrics format"
+)]
+struct Cli {
+    /// Address to listen on
+    #[arg(long, default_value = "0.0.0.0:9254")]
+    listen: SocketAddr,
+    /// Root directory of devfs
+    #[arg(long, default_value = "/dev")]
+    devfs: PathBuf,
+    /// Root directory of sysfs
+    #[arg(long, default_value = "/sys")]
+    sysfs: PathBuf,
+    /// Root directory of procfs, scanned for processes occupying cores
+    #[arg(long, default_value = "/proc")]
+    procfs: PathBuf,
+    /// Accept regular files under the devfs root as device files
+    #[arg(long, hide = true)]
+    fake_devfs: bool,
+}
+
+impl Cli {
+    fn exporter(self) -> Exporter {
+        Exporter::new(
+            DeviceLister::new()
+                .devfs(self.devfs)
+                .sysfs(self.sysfs)
+                .procfs(self.procfs)
+                .allow_regular_files(self.fake_devfs),
+        )
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .init();
+
+    if let Err(e) = run(Cli::parse()).await {
+        eprintln!("furiosa-exporter: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run(cli: Cli) -> DeviceResult<()> {
+    let listener = TcpListener::bind(cli.listen).await?;
+    tracing::info!(
+        "Serving metrics on http://{}{}",
+        listener.local_addr()?,
+        METRICS_PATH
+    );
+    cli.exporter().serve(listener).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_args() {
+        let cli = Cli::parse_from(["furiosa-exporter"]);
+        assert_eq!(cli.listen, "0.0.0.0:9254".parse().unwrap());
+        assert_eq!(cli.sysfs, PathBuf::from("/sys"));
+
+        let cli = Cli::parse_from(["furiosa-exporter", "--listen", "127.0.0.1:9100"]);
+        assert_eq!(cli.listen, "127.0.0.1:9100".parse().unwrap());
+        assert!(Cli::try_parse_from(["furiosa-exporter", "--listen", "localhost"]).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_render() -> DeviceResult<()> {
+        let cli = Cli::parse_from([
+            "furiosa-exporter",
+            "--devfs",
+            "test_data/test-0/dev",
+            "--sysfs",
+            "test_data/test-0/sys",
+            "--fake-devfs",
+        ]);
+        let text = cli.exporter().render().await?;
+        assert!(text.contains("furiosa_npu_core_occupied{device=\"1\""));
+        Ok(())
+    }
+}
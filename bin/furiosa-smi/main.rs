@@ -0,0 +1,636 @@
+//! A command line tool to list, monitor and control FuriosaAI NPU devices.
+
+use std::io::{self, Write};
+use std::path::PathBuf;
+
+use clap::{Parser, Subcommand, ValueEnum};
+use serde_json::{json, Value};
+use tracing_subscriber::EnvFilter;
+
+use furiosa_device::hwmon::{Amperes, Celsius, Reading, Unit, Volts, Watts};
+use furiosa_device::sysfs::npu_mgmt::{DtmPolicy, PerfLevel, PerfMode, Toggle};
+use furiosa_device::{
+    CoreRange, CoreStatus, Device, DeviceConfig, DeviceError, DeviceFile, DeviceLister,
+    DeviceResult, FindReport, NumaNode,
+};
+
+use crate::output::{Format, Report};
+
+mod output;
+
+#[derive(Debug, Parser)]
+#[command(
+    name = "furiosa-smi",
+    version,
+    about = "Lists, monitors and controls FuriosaAI NPUs"
+)]
+struct Cli {
+    /// Output format
+    #[arg(long, value_enum, default_value_t = Format::Table, global = true)]
+    format: Format,
+    /// Root directory of devfs
+    #[arg(long, default_value = "/dev", global = true)]
+    devfs: PathBuf,
+    /// Root directory of sysfs
+    #[arg(long, default_value = "/sys", global = true)]
+    sysfs: PathBuf,
+    /// Root directory of procfs
+    #[arg(long, default_value = "/proc", global = true)]
+    procfs: PathBuf,
+    /// Accept regular files under the devfs root as device files
+    #[arg(long, hide = true, global = true)]
+    fake_devfs: bool,
+    #[command(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, Subcommand)]
+enum Command {
+    /// List all NPU devices
+    List,
+    /// Show the occupancy of each core
+    Status,
+    /// Show hwmon sensor values
+    Sensors,
+    /// Show the PCI identity and PCIe link of each device, flagging degraded links
+    Pci,
+    /// Find device files matching a config (e.g., "warboy(2)*1", "warboy(1)*2#best-fit", "0:0-1")
+    Find {
+        #[arg(value_parser = parse_config)]
+        config: DeviceConfig,
+        /// Explain why each of the other device files is not found
+        #[arg(long)]
+        explain: bool,
+    },
+    /// Show a device file (e.g., npu0pe0-1)
+    Get { name: String },
+    /// Control a device
+    Ctrl {
+        /// Device name (e.g., npu0)
+        device: String,
+        #[command(subcommand)]
+        action: CtrlAction,
+    },
+}
+
+#[derive(Debug, Subcommand)]
+enum CtrlAction {
+    /// Turn the device LEDs on or off
+    Led {
+        #[arg(value_enum)]
+        led0: Switch,
+        #[arg(value_enum)]
+        led1: Switch,
+        #[arg(value_enum)]
+        led2: Switch,
+    },
+    /// Turn the NE clocks on or off
+    Clock {
+        #[arg(value_enum)]
+        toggle: Switch,
+    },
+    /// Set the Dynamic Thermal Management policy
+    Dtm {
+        #[arg(value_enum)]
+        policy: DtmArg,
+    },
+    /// Set the NE performance mode
+    PerfMode {
+        #[arg(value_enum)]
+        mode: PerfModeArg,
+    },
+    /// Set the NE performance level
+    PerfLevel {
+        #[arg(value_parser = clap::value_parser!(u8).range(0..=15))]
+        level: u8,
+    },
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum Switch {
+    On,
+    Off,
+}
+
+impl From<Switch> for bool {
+    fn from(switch: Switch) -> Self {
+        switch == Switch::On
+    }
+}
+
+impl From<Switch> for Toggle {
+    fn from(switch: Switch) -> Self {
+        match switch {
+            Switch::On => Toggle::Enable,
+            Switch::Off => Toggle::Disable,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum DtmArg {
+    OnDemand,
+    Conservative,
+}
+
+impl From<DtmArg> for DtmPolicy {
+    fn from(policy: DtmArg) -> Self {
+        match policy {
+            DtmArg::OnDemand => DtmPolicy::OnDemand,
+            DtmArg::Conservative => DtmPolicy::Conservative,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq, ValueEnum)]
+enum PerfModeArg {
+    Low,
+    Half,
+    Normal1,
+    Normal2,
+    Full1,
+    Full2,
+}
+
+impl From<PerfModeArg> for PerfMode {
+    fn from(mode: PerfModeArg) -> Self {
+        match mode {
+            PerfModeArg::Low => PerfMode::Low,
+            PerfModeArg::Half => PerfMode::Half,
+            PerfModeArg::Normal1 => PerfMode::Normal1,
+            PerfModeArg::Normal2 => PerfMode::Normal2,
+            PerfModeArg::Full1 => PerfMode::Full1,
+            PerfModeArg::Full2 => PerfMode::Full2,
+        }
+    }
+}
+
+fn parse_config(s: &str) -> Result<DeviceConfig, String> {
+    s.parse()
+        .map_err(|_| format!("invalid device config: {}", s))
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_env_filter(EnvFilter::from_default_env())
+        .with_writer(io::stderr)
+        .init();
+
+    let cli = Cli::parse();
+    if let Err(e) = run(cli, &mut io::stdout().lock()).await {
+        eprintln!("furiosa-smi: {}", e);
+        std::process::exit(1);
+    }
+}
+
+async fn run<W: Write>(cli: Cli, out: &mut W) -> DeviceResult<()> {
+    let lister = DeviceLister::new()
+        .devfs(cli.devfs)
+        .sysfs(cli.sysfs)
+        .procfs(cli.procfs)
+        .allow_regular_files(cli.fake_devfs);
+
+    let report = match cli.command {
+        Command::List => list(&lister).await?,
+        Command::Status => status(&lister).await?,
+        Command::Sensors => sensors(&lister).await?,
+        Command::Pci => pci(&lister).await?,
+        Command::Find {
+            config,
+            explain: true,
+        } => {
+            let report = lister.find_devices_report(&config).await?;
+            explain(&report).render(cli.format, out)?;
+            if !report.is_satisfied() {
+                return Err(DeviceError::DeviceNotFound {
+                    name: config.to_string(),
+                });
+            }
+            return Ok(());
+        }
+        Command::Find {
+            config,
+            explain: false,
+        } => {
+            let report = find(&lister, &config).await?;
+            if report.is_empty() {
+                return Err(DeviceError::DeviceNotFound {
+                    name: config.to_string(),
+                });
+            }
+            report
+        }
+        Command::Get { name } => get(&lister, &name).await?,
+        Command::Ctrl { device, action } => return ctrl(&lister, &device, action).await,
+    };
+
+    report.render(cli.format, out)?;
+    Ok(())
+}
+
+async fn list(lister: &DeviceLister) -> DeviceResult<Report> {
+    let mut report = Report::new(&["npu", "arch", "firmware", "busname", "numa_node", "cores"]);
+    for device in lister.list_devices().await? {
+        report.push(vec![
+            json!(device.name()),
+            json!(device.arch().to_string()),
+            json!(device.firmware_version().ok()),
+            json!(device.busname().ok()),
+            numa_node_value(device.numa_node().ok()),
+            json!(device.cores()),
+        ]);
+    }
+    Ok(report)
+}
+
+async fn pci(lister: &DeviceLister) -> DeviceResult<Report> {
+    let mut report = Report::new(&[
+        "npu",
+        "busname",
+        "id",
+        "link",
+        "max_link",
+        "degraded",
+        "iommu_group",
+        "driver",
+        "error",
+    ]);
+    for device in lister.list_devices().await? {
+        let mut row = vec![json!(device.name()), json!(device.busname().ok())];
+        match device.pci_info() {
+            Ok(info) => row.extend([
+                json!(format!("{:04x}:{:04x}", info.vendor_id(), info.device_id())),
+                json!(info.current_link().map(|link| link.to_string())),
+                json!(info.max_link().map(|link| link.to_string())),
+                json!(info.is_link_degraded()),
+                json!(info.iommu_group()),
+                json!(info.driver()),
+                Value::Null,
+            ]),
+            // The other devices are still reported.
+            Err(e) => {
+                row.resize(row.len() + 6, Value::Null);
+                row.push(json!(e.to_string()));
+            }
+        }
+        report.push(row);
+    }
+    Ok(report)
+}
+
+async fn status(lister: &DeviceLister) -> DeviceResult<Report> {
+    let mut report = Report::new(&["npu", "core", "status", "device_file", "pids"]);
+    for device in lister.list_devices().await? {
+        let statuses = device.get_status_all().await?;
+        for core in device.cores() {
+            let (status, device_file, pids) = match &statuses[core] {
+                CoreStatus::Available => ("available", None, None),
+                CoreStatus::Occupied(device_file) => {
+                    let processes = device.get_core_processes(*core).await?;
+                    (
+                        "occupied",
+                        Some(device_file.clone()),
+                        Some(processes.iter().map(|p| p.pid).collect::<Vec<_>>()),
+                    )
+                }
+                CoreStatus::Unavailable => ("unavailable", None, None),
+            };
+            report.push(vec![
+                json!(device.name()),
+                json!(core),
+                json!(status),
+                json!(device_file),
+                json!(pids),
+            ]);
+        }
+    }
+    Ok(report)
+}
+
+async fn sensors(lister: &DeviceLister) -> DeviceResult<Report> {
+    let mut report = Report::new(&["npu", "type", "label", "value", "unit"]);
+    for device in lister.list_devices().await? {
+        let fetcher = device.get_hwmon_fetcher();
+        push_readings(
+            &mut report,
+            &device,
+            "current",
+            fetcher.read::<Amperes>().await?,
+        );
+        push_readings(
+            &mut report,
+            &device,
+            "voltage",
+            fetcher.read::<Volts>().await?,
+        );
+        push_readings(
+            &mut report,
+            &device,
+            "power",
+            fetcher.read::<Watts>().await?,
+        );
+        push_readings(
+            &mut report,
+            &device,
+            "temperature",
+            fetcher.read::<Celsius>().await?,
+        );
+    }
+    Ok(report)
+}
+
+fn push_readings<U: Unit>(
+    report: &mut Report,
+    device: &Device,
+    sensor_type: &str,
+    readings: Vec<Reading<U>>,
+) {
+    for reading in readings {
+        report.push(vec![
+            json!(device.name()),
+            json!(sensor_type),
+            json!(reading.label),
+            json!(reading.value.value()),
+            json!(U::SYMBOL),
+        ]);
+    }
+}
+
+async fn find(lister: &DeviceLister, config: &DeviceConfig) -> DeviceResult<Report> {
+    let mut report = device_file_report();
+    for device_file in lister.find_devices(config).await? {
+        push_device_file(&mut report, &device_file);
+    }
+    Ok(report)
+}
+
+fn explain(find_report: &FindReport) -> Report {
+    let mut report = Report::new(&["device_file", "npu", "verdict", "reason"]);
+    for device_file in find_report.picked() {
+        report.push(vec![
+            json!(device_file.filename()),
+            json!(format!("npu{}", device_file.device_index())),
+            json!("picked"),
+            Value::Null,
+        ]);
+    }
+    for rejection in find_report.rejected() {
+        report.push(vec![
+            json!(rejection.device_file.filename()),
+            json!(format!("npu{}", rejection.device_file.device_index())),
+            json!("rejected"),
+            json!(rejection.reason.to_string()),
+        ]);
+    }
+    report
+}
+
+async fn get(lister: &DeviceLister, name: &str) -> DeviceResult<Report> {
+    let mut report = device_file_report();
+    push_device_file(&mut report, &lister.get_device(name).await?);
+    Ok(report)
+}
+
+async fn ctrl(lister: &DeviceLister, name: &str, action: CtrlAction) -> DeviceResult<()> {
+    let device = find_device(lister, name).await?;
+    match action {
+        CtrlAction::Led { led0, led1, led2 } => {
+            device.ctrl_device_led((led0.into(), led1.into(), led2.into()))
+        }
+        CtrlAction::Clock { toggle } => device.ctrl_ne_clock(toggle.into()),
+        CtrlAction::Dtm { policy } => device.ctrl_ne_dtm_policy(policy.into()),
+        CtrlAction::PerfMode { mode } => device.ctrl_performance_mode(mode.into()),
+        CtrlAction::PerfLevel { level } => {
+            device.ctrl_performance_level(PerfLevel::try_from(level)?)
+        }
+    }
+}
+
+async fn find_device(lister: &DeviceLister, name: &str) -> DeviceResult<Device> {
+    lister
+        .list_devices()
+        .await?
+        .into_iter()
+        .find(|device| device.name() == name)
+        .ok_or_else(|| DeviceError::DeviceNotFound {
+            name: name.to_string(),
+        })
+}
+
+fn device_file_report() -> Report {
+    Report::new(&["device_file", "npu", "cores", "mode", "path"])
+}
+
+fn push_device_file(report: &mut Report, device_file: &DeviceFile) {
+    let cores = match device_file.core_range() {
+        CoreRange::All => json!("all"),
+        CoreRange::Range((s, e)) => json!((s..=e).collect::<Vec<_>>()),
+    };
+    report.push(vec![
+        json!(device_file.filename()),
+        json!(format!("npu{}", device_file.device_index())),
+        cores,
+        json!(device_file.mode()),
+        json!(device_file.path()),
+    ]);
+}
+
+fn numa_node_value(node: Option<NumaNode>) -> Value {
+    match node {
+        Some(NumaNode::Id(id)) => json!(id),
+        _ => Value::Null,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lister() -> DeviceLister {
+        DeviceLister::new()
+            .devfs("test_data/test-0/dev")
+            .sysfs("test_data/test-0/sys")
+            .allow_regular_files(true)
+    }
+
+    async fn run_json(args: &[&str]) -> DeviceResult<Value> {
+        let mut argv = vec![
+            "furiosa-smi",
+            "--format",
+            "json",
+            "--devfs",
+            "test_data/test-0/dev",
+            "--sysfs",
+            "test_data/test-0/sys",
+            "--fake-devfs",
+        ];
+        argv.extend_from_slice(args);
+        let mut out = Vec::new();
+        run(Cli::try_parse_from(argv).unwrap(), &mut out).await?;
+        Ok(serde_json::from_slice(&out).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_list() -> DeviceResult<()> {
+        let value = run_json(&["list"]).await?;
+        assert_eq!(
+            value,
+            json!([
+                {"npu": "npu0", "arch": "warboy", "firmware": "", "busname": "0000:6d:00.0", "numa_node": 0, "cores": [0, 1]},
+                {"npu": "npu1", "arch": "warboy", "firmware": "", "busname": "0000:ff:00.0", "numa_node": null, "cores": [0, 1]},
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pci() -> DeviceResult<()> {
+        let value = run_json(&["pci"]).await?;
+        assert_eq!(
+            value,
+            json!([
+                {"npu": "npu0", "busname": "0000:6d:00.0", "id": "1ed2:0000", "link": "16.0 GT/s x8", "max_link": "16.0 GT/s x8", "degraded": false, "iommu_group": 27, "driver": "furiosa", "error": null},
+                {"npu": "npu1", "busname": "0000:ff:00.0", "id": "1ed2:0000", "link": "8.0 GT/s x4", "max_link": "16.0 GT/s x8", "degraded": true, "iommu_group": null, "driver": null, "error": null},
+            ])
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pci_error() -> Result<(), Box<dyn std::error::Error>> {
+        // A sysfs whose PCI devices have nothing but hwmon
+        let sysfs = tempfile::tempdir()?;
+        let mut dirs = vec![String::from("class"), String::from("device")];
+        for busname in ["0000:6d:00.0", "0000:ff:00.0"] {
+            let pci = format!("bus/pci/devices/{}", busname);
+            std::fs::create_dir_all(sysfs.path().join(&pci))?;
+            dirs.push(format!("{}/hwmon", pci));
+        }
+        for dir in dirs {
+            std::os::unix::fs::symlink(
+                std::fs::canonicalize(format!("test_data/test-0/sys/{}", dir))?,
+                sysfs.path().join(dir),
+            )?;
+        }
+        let report = pci(&lister().sysfs(sysfs.path())).await?;
+        let mut out = Vec::new();
+        report.render(Format::Json, &mut out)?;
+        let value: Value = serde_json::from_slice(&out)?;
+        let records = value.as_array().unwrap();
+        assert_eq!(records.len(), 2);
+        assert_eq!(records[1]["busname"], json!("0000:ff:00.0"));
+        assert_eq!(records[1]["id"], Value::Null);
+        assert!(records[1]["error"].as_str().unwrap().contains("not found"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_status() -> DeviceResult<()> {
+        let report = status(&lister()).await?;
+        let mut expected = Report::new(&["npu", "core", "status", "device_file", "pids"]);
+        for npu in ["npu0", "npu1"] {
+            for core in 0..2 {
+                expected.push(vec![
+                    json!(npu),
+                    json!(core),
+                    json!("available"),
+                    Value::Null,
+                    Value::Null,
+                ]);
+            }
+        }
+        assert_eq!(report, expected);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_sensors() -> DeviceResult<()> {
+        let value = run_json(&["sensors"]).await?;
+        let records = value.as_array().unwrap();
+        assert_eq!(records.len(), 9);
+        assert_eq!(
+            records[0],
+            json!({"npu": "npu0", "type": "current", "label": "Current1", "value": 1.0, "unit": "A"})
+        );
+        assert_eq!(records[7]["label"], json!("Temp2"));
+        assert_eq!(records[7]["value"], json!(37.0));
+        assert_eq!(records[8]["npu"], json!("npu1"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_and_get() -> DeviceResult<()> {
+        let value = run_json(&["find", "warboy(2)*2"]).await?;
+        let names: Vec<_> = value
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|r| r["device_file"].clone())
+            .collect();
+        assert_eq!(names, vec![json!("npu0pe0-1"), json!("npu1pe0-1")]);
+
+        assert!(matches!(
+            run_json(&["find", "warboy(2)*3"]).await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+
+        let value = run_json(&["get", "npu1pe1"]).await?;
+        assert_eq!(value[0]["npu"], json!("npu1"));
+        assert_eq!(value[0]["cores"], json!([1]));
+        assert_eq!(value[0]["mode"], json!("single"));
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_explain() -> DeviceResult<()> {
+        let value = run_json(&["find", "--explain", "warboy(2)*1"]).await?;
+        let records = value.as_array().unwrap();
+        assert_eq!(records[0]["device_file"], json!("npu0pe0-1"));
+        assert_eq!(records[0]["verdict"], json!("picked"));
+        assert_eq!(records[0]["reason"], Value::Null);
+        let npu0pe0 = records
+            .iter()
+            .find(|r| r["device_file"] == json!("npu0pe0"))
+            .unwrap();
+        assert_eq!(npu0pe0["verdict"], json!("rejected"));
+        assert_eq!(npu0pe0["reason"], json!("mode is single, not fusion"));
+
+        assert!(matches!(
+            run_json(&["find", "--explain", "warboy(2)*3"]).await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_args() {
+        assert!(Cli::try_parse_from(["furiosa-smi", "find", "warboy(2*1"]).is_err());
+        assert!(Cli::try_parse_from(["furiosa-smi", "ctrl", "npu0", "perf-level", "16"]).is_err());
+
+        let cli =
+            Cli::try_parse_from(["furiosa-smi", "ctrl", "npu0", "led", "on", "off", "on"]).unwrap();
+        assert!(matches!(
+            cli.command,
+            Command::Ctrl {
+                action: CtrlAction::Led {
+                    led0: Switch::On,
+                    led1: Switch::Off,
+                    led2: Switch::On
+                },
+                ..
+            }
+        ));
+
+        let cli = Cli::try_parse_from(["furiosa-smi", "--format", "csv", "list"]).unwrap();
+        assert_eq!(cli.format, Format::Csv);
+    }
+
+    #[tokio::test]
+    async fn test_ctrl_unknown_device() {
+        assert!(matches!(
+            ctrl(&lister(), "npu9", CtrlAction::Clock { toggle: Switch::On }).await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+    }
+}
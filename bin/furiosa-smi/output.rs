@@ -0,0 +1,149 @@
+use std::io::{self, Write};
+
+use cli_table::{Cell, Style, Table};
+use serde_json::{Map, Value};
+
+/// Output format of the command line.
+#[derive(clap::ValueEnum, Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Format {
+    Table,
+    Json,
+    Csv,
+}
+
+/// Rows with named columns, which can be rendered in any [`Format`].
+#[derive(Debug, Default, PartialEq)]
+pub struct Report {
+    columns: Vec<&'static str>,
+    rows: Vec<Vec<Value>>,
+}
+
+impl Report {
+    pub fn new(columns: &[&'static str]) -> Self {
+        Self {
+            columns: columns.to_vec(),
+            rows: vec![],
+        }
+    }
+
+    pub fn push(&mut self, row: Vec<Value>) {
+        debug_assert_eq!(row.len(), self.columns.len());
+        self.rows.push(row);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rows.is_empty()
+    }
+
+    pub fn render<W: Write>(&self, format: Format, out: &mut W) -> io::Result<()> {
+        match format {
+            Format::Table => self.render_table(out),
+            Format::Json => self.render_json(out),
+            Format::Csv => self.render_csv(out),
+        }
+    }
+
+    fn render_table<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let rows = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|v| to_text(v).cell()).collect::<Vec<_>>());
+        let title = self.columns.iter().map(|c| c.cell().bold(true));
+        let table = rows.table().title(title);
+        writeln!(out, "{}", table.display()?)
+    }
+
+    fn render_json<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let records: Vec<Value> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let record: Map<String, Value> = self
+                    .columns
+                    .iter()
+                    .map(|c| c.to_string())
+                    .zip(row.iter().cloned())
+                    .collect();
+                Value::Object(record)
+            })
+            .collect();
+        serde_json::to_writer_pretty(&mut *out, &records)?;
+        writeln!(out)
+    }
+
+    fn render_csv<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        let header: Vec<String> = self.columns.iter().map(|c| escape_csv(c)).collect();
+        writeln!(out, "{}", header.join(","))?;
+        for row in &self.rows {
+            let fields: Vec<String> = row.iter().map(|v| escape_csv(&to_text(v))).collect();
+            writeln!(out, "{}", fields.join(","))?;
+        }
+        Ok(())
+    }
+}
+
+fn to_text(value: &Value) -> String {
+    match value {
+        Value::Null => String::from("-"),
+        Value::String(s) => s.clone(),
+        Value::Array(values) => values.iter().map(to_text).collect::<Vec<_>>().join(" "),
+        v => v.to_string(),
+    }
+}
+
+fn escape_csv(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn sample() -> Report {
+        let mut report = Report::new(&["npu", "cores", "numa_node"]);
+        report.push(vec![json!("npu0"), json!([0, 1]), json!(0)]);
+        report.push(vec![json!("npu1"), json!([0, 1]), Value::Null]);
+        report
+    }
+
+    fn render(report: &Report, format: Format) -> String {
+        let mut out = Vec::new();
+        report.render(format, &mut out).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn test_render_csv() {
+        assert_eq!(
+            render(&sample(), Format::Csv),
+            "npu,cores,numa_node\nnpu0,0 1,0\nnpu1,0 1,-\n"
+        );
+        assert_eq!(escape_csv("a,b"), "\"a,b\"");
+        assert_eq!(escape_csv("say \"hi\""), "\"say \"\"hi\"\"\"");
+    }
+
+    #[test]
+    fn test_render_json() {
+        let value: Value = serde_json::from_str(&render(&sample(), Format::Json)).unwrap();
+        assert_eq!(
+            value,
+            json!([
+                {"npu": "npu0", "cores": [0, 1], "numa_node": 0},
+                {"npu": "npu1", "cores": [0, 1], "numa_node": null},
+            ])
+        );
+    }
+
+    #[test]
+    fn test_render_table() {
+        let table = render(&sample(), Format::Table);
+        assert!(table.contains("numa_node"));
+        assert!(table.contains("npu1"));
+        assert!(table.contains("0 1"));
+    }
+}
@@ -0,0 +1,110 @@
+//! CPU affinity of the calling thread or process, with `sched_setaffinity(2)`.
+
+use std::fs;
+use std::io;
+use std::mem;
+use std::path::Path;
+
+/// Pins a thread, or the calling thread if `tid` is 0, to the CPUs.
+pub(crate) fn set_thread_affinity(tid: libc::pid_t, cpus: &[usize]) -> io::Result<()> {
+    // SAFETY: an all-zero `cpu_set_t` is an empty set.
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    for cpu in cpus {
+        if *cpu >= libc::CPU_SETSIZE as usize {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("CPU {} is out of the CPU set", cpu),
+            ));
+        }
+        // SAFETY: `cpu` is within the set.
+        unsafe { libc::CPU_SET(*cpu, &mut set) };
+    }
+
+    // SAFETY: `set` is a valid `cpu_set_t` of the given size.
+    let ret = unsafe { libc::sched_setaffinity(tid, mem::size_of::<libc::cpu_set_t>(), &set) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Returns the CPUs which the calling thread is allowed to run on.
+#[cfg(test)]
+pub(crate) fn thread_affinity() -> io::Result<Vec<usize>> {
+    // SAFETY: an all-zero `cpu_set_t` is an empty set.
+    let mut set: libc::cpu_set_t = unsafe { mem::zeroed() };
+    // SAFETY: `set` is a valid `cpu_set_t` of the given size.
+    let ret = unsafe { libc::sched_getaffinity(0, mem::size_of::<libc::cpu_set_t>(), &mut set) };
+    if ret != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok((0..libc::CPU_SETSIZE as usize)
+        // SAFETY: `cpu` is within the set.
+        .filter(|cpu| unsafe { libc::CPU_ISSET(*cpu, &set) })
+        .collect())
+}
+
+/// Pins all threads of the calling process, listed under `{proc_root}/self/task`, to the CPUs.
+///
+/// Threads spawned afterwards inherit the affinity of their creators. Threads which exit in
+/// the meantime are skipped.
+pub(crate) fn set_process_affinity(proc_root: &Path, cpus: &[usize]) -> io::Result<()> {
+    for entry in fs::read_dir(proc_root.join("self/task"))? {
+        let Some(tid) = entry?.file_name().to_str().and_then(|tid| tid.parse().ok()) else {
+            continue;
+        };
+        match set_thread_affinity(tid, cpus) {
+            Err(e) if e.raw_os_error() == Some(libc::ESRCH) => continue,
+            result => result?,
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_thread_affinity() -> io::Result<()> {
+        // On another thread not to affect other tests
+        std::thread::spawn(|| {
+            let allowed = thread_affinity()?;
+            assert!(!allowed.is_empty());
+
+            set_thread_affinity(0, &allowed[..1])?;
+            assert_eq!(thread_affinity()?, &allowed[..1]);
+            set_thread_affinity(0, &allowed)?;
+            assert_eq!(thread_affinity()?, allowed);
+
+            assert!(set_thread_affinity(0, &[]).is_err());
+            assert!(set_thread_affinity(0, &[usize::MAX]).is_err());
+            Ok(())
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_set_process_affinity() -> io::Result<()> {
+        std::thread::spawn(|| {
+            let allowed = thread_affinity()?;
+
+            // A procfs where the process has only this thread
+            let proc_root = tempfile::tempdir()?;
+            let task = proc_root.path().join("self/task");
+            // SAFETY: gettid(2) always succeeds.
+            fs::create_dir_all(task.join(unsafe { libc::gettid() }.to_string()))?;
+            fs::create_dir_all(task.join("not-a-thread"))?;
+
+            set_process_affinity(proc_root.path(), &allowed[..1])?;
+            assert_eq!(thread_affinity()?, &allowed[..1]);
+            set_thread_affinity(0, &allowed)?;
+
+            assert!(set_process_affinity(&proc_root.path().join("none"), &allowed).is_err());
+            Ok(())
+        })
+        .join()
+        .unwrap()
+    }
+}
@@ -2,6 +2,8 @@ use std::fmt::{Display, Formatter};
 use strum_macros::AsRefStr;
 
 /// Enum for the NPU architecture.
+///
+/// With the `serde` feature, it is serialized as its textual form (e.g., `"warboy-b0"`).
 #[derive(AsRefStr, Clone, Copy, Debug, enum_utils::FromStr, Eq, PartialEq)]
 #[enumeration(case_insensitive)]
 pub enum Arch {
@@ -26,6 +28,25 @@ impl Display for Arch {
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for Arch {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Arch {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        // Only the textual forms are accepted, unlike `FromStr` which parses `device_type`.
+        let s = String::deserialize(deserializer)?;
+        [Arch::Warboy, Arch::WarboyB0, Arch::Renegade, Arch::U250]
+            .into_iter()
+            .find(|arch| arch.to_string() == s)
+            .ok_or_else(|| serde::de::Error::custom(format!("unknown architecture: {}", s)))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -35,4 +56,16 @@ mod tests {
     fn test_archkind() {
         assert!(Arch::from_str("Warboy").is_ok());
     }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_serde_round_trip() -> serde_json::Result<()> {
+        assert_eq!(serde_json::to_string(&Arch::WarboyB0)?, r#""warboy-b0""#);
+        for arch in [Arch::Warboy, Arch::WarboyB0, Arch::Renegade, Arch::U250] {
+            let json = serde_json::to_string(&arch)?;
+            assert_eq!(serde_json::from_str::<Arch>(&json)?, arch);
+        }
+        assert!(serde_json::from_str::<Arch>(r#""unknown""#).is_err());
+        Ok(())
+    }
 }
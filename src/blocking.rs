@@ -1,29 +1,61 @@
 //! A set of synchronous APIs. This requires the optional blocking feature to be enabled.
+//!
+//! Each API behaves identically to its async counterpart in the crate root; both of them
+//! share the same implementation except for the I/O.
 
 use std::collections::HashMap;
-use std::fs::{File, OpenOptions};
-use std::io;
+use std::fs::File;
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
-use crate::devfs::is_character_device;
-use crate::device::{CoreIdx, CoreStatus, DeviceInfo, DeviceMetadata};
+use futures::executor::BlockingStream;
+
+use crate::device::{CachePolicy, CoreIdx, CoreStatus};
 use crate::find::DeviceWithStatus;
-use crate::hwmon;
-use crate::list::{collect_devices, filter_dev_files, DevFile};
-use crate::status::DeviceStatus;
+use crate::find::{find_devices_in, find_devices_or_wait, report_devices_in, FindReport};
+use crate::hwmon::{self, HwmonType, Reading, SensorRecord, SensorValue, Snapshot, Unit};
+use crate::list::{collect_devices, is_furiosa_device_blocking, list_devfs_blocking};
+use crate::procfs::{self, ProcessInfo};
+use crate::queue::{self, Ticket};
+use crate::reserve::{self, Reservation};
+use crate::status::{self, OccupancyProbe};
 use crate::sysfs::npu_mgmt;
-use crate::sysfs::npu_mgmt::PLATFORM_TYPE;
-use crate::{devfs, find_devices_in, Device, DeviceConfig, DeviceError, DeviceFile, DeviceResult};
+use crate::{Device, DeviceConfig, DeviceError, DeviceFile, DeviceResult, DeviceWatcher};
 
 /// List all Furiosa NPU devices in the system.
 pub fn list_devices() -> DeviceResult<Vec<Device>> {
-    list_devices_with("/dev", "/sys")
+    DeviceLister::new().list_devices()
 }
 
 /// Find a set of devices with specific configuration.
 pub fn find_devices(config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>> {
-    let devices = expand_status(list_devices()?)?;
-    find_devices_in(config, &devices)
+    DeviceLister::new().find_devices(config)
+}
+
+/// Find a set of devices with specific configuration, explaining why each of the other device
+/// files is not found.
+pub fn find_devices_report(config: &DeviceConfig) -> DeviceResult<FindReport> {
+    DeviceLister::new().find_devices_report(config)
+}
+
+/// Find a set of devices with specific configuration, waiting up to `timeout` until enough
+/// device files are available.
+pub fn find_devices_wait(
+    config: &DeviceConfig,
+    timeout: Duration,
+) -> DeviceResult<Vec<DeviceFile>> {
+    DeviceLister::new().find_devices_wait(config, timeout)
+}
+
+/// Find a set of devices with specific configuration, waiting up to `timeout` until enough
+/// device files are available, and reserve them exclusively.
+pub fn reserve_devices_wait(config: &DeviceConfig, timeout: Duration) -> DeviceResult<Reservation> {
+    DeviceLister::new().reserve_devices_wait(config, timeout)
+}
+
+/// Find a set of devices with specific configuration, and reserve them exclusively.
+pub fn reserve_devices(config: &DeviceConfig) -> DeviceResult<Reservation> {
+    DeviceLister::new().reserve_devices(config)
 }
 
 /// Return a specific device if it exists.
@@ -33,70 +65,254 @@ pub fn find_devices(config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>> {
 /// * `device_name` - A device name (e.g., npu0, npu0pe0, npu0pe0-1)
 #[inline]
 pub fn get_device<S: AsRef<str>>(device_name: S) -> DeviceResult<DeviceFile> {
-    get_with("/dev", device_name.as_ref())
+    DeviceLister::new().get_device(device_name)
 }
 
-pub(crate) fn get_with(devfs: &str, device_name: &str) -> DeviceResult<DeviceFile> {
-    let path = devfs::path(devfs, device_name);
-    if !path.exists() {
-        return Err(DeviceError::DeviceNotFound {
-            name: device_name.to_string(),
-        });
+/// A blocking counterpart of [`crate::DeviceLister`], which looks up Furiosa NPU devices
+/// under configurable devfs, sysfs and procfs roots.
+///
+/// # Examples
+/// ```rust,ignore
+/// use furiosa_device::blocking::DeviceLister;
+///
+/// let devices = DeviceLister::new()
+///     .devfs("/host/dev")
+///     .sysfs("/host/sys")
+///     .list_devices()?;
+/// ```
+#[derive(Clone, Debug, Default, Eq, PartialEq)]
+pub struct DeviceLister {
+    inner: crate::DeviceLister,
+}
+
+impl DeviceLister {
+    /// Returns a lister with the default roots, `/dev`, `/sys` and `/proc`.
+    pub fn new() -> Self {
+        Self {
+            inner: crate::DeviceLister::new(),
+        }
+    }
+
+    /// Sets the root directory of devfs (default: `/dev`).
+    pub fn devfs<P: Into<PathBuf>>(self, devfs: P) -> Self {
+        self.inner.devfs(devfs).into()
     }
 
-    let file = File::open(&path)?;
-    if !is_character_device(file.metadata()?.file_type()) {
-        return Err(DeviceError::invalid_device_file(path.display()));
+    /// Sets the root directory of sysfs (default: `/sys`).
+    pub fn sysfs<P: Into<PathBuf>>(self, sysfs: P) -> Self {
+        self.inner.sysfs(sysfs).into()
     }
 
-    devfs::parse_indices(path.file_name().expect("not a file").to_string_lossy())?;
+    /// Sets the root directory of procfs (default: `/proc`).
+    pub fn procfs<P: Into<PathBuf>>(self, procfs: P) -> Self {
+        self.inner.procfs(procfs).into()
+    }
 
-    DeviceFile::try_from(&path)
-}
+    /// Sets the cache policy of volatile mgmt attributes for listed devices
+    /// (default: [`CachePolicy::Never`]).
+    pub fn cache_policy(self, cache_policy: CachePolicy) -> Self {
+        self.inner.cache_policy(cache_policy).into()
+    }
+
+    /// Sets the directory of lock files for reservations (default: `/run/furiosa`), which
+    /// only root can create; see [`crate::DeviceLister::runtime_dir`].
+    pub fn runtime_dir<P: Into<PathBuf>>(self, runtime_dir: P) -> Self {
+        self.inner.runtime_dir(runtime_dir).into()
+    }
+
+    /// Sets how to examine whether device files of listed devices are occupied
+    /// (default: [`OccupancyProbe::Open`]).
+    pub fn occupancy_probe(self, probe: OccupancyProbe) -> Self {
+        self.inner.occupancy_probe(probe).into()
+    }
 
-/// Allow to specify arbitrary sysfs, devfs paths for unit testing
-pub(crate) fn list_devices_with(devfs: &str, sysfs: &str) -> DeviceResult<Vec<Device>> {
-    let npu_dev_files = filter_dev_files(list_devfs(devfs)?)?;
+    /// Accepts regular files under the devfs root as device files (default: `false`).
+    ///
+    /// It allows to use a fake devfs, e.g., for testing.
+    pub fn allow_regular_files(self, allow: bool) -> Self {
+        self.inner.allow_regular_files(allow).into()
+    }
+
+    /// Sets the local NUMA node, instead of the node of the CPU which the calling thread runs
+    /// on; see [`crate::DeviceLister::local_node`].
+    pub fn local_node(self, node: usize) -> Self {
+        self.inner.local_node(node).into()
+    }
+
+    /// Returns the root directory of devfs.
+    pub fn devfs_root(&self) -> &Path {
+        self.inner.devfs_root()
+    }
 
-    let mut devices: Vec<Device> = Vec::with_capacity(npu_dev_files.len());
+    /// Returns the root directory of sysfs.
+    pub fn sysfs_root(&self) -> &Path {
+        self.inner.sysfs_root()
+    }
 
-    for (idx, paths) in npu_dev_files {
-        if is_furiosa_device(idx, sysfs) {
-            let mgmt_files = npu_mgmt::read_mgmt_files(sysfs, idx)?;
-            let device_meta = DeviceMetadata::try_from(mgmt_files)?;
-            let device_info =
-                DeviceInfo::new(idx, PathBuf::from(devfs), PathBuf::from(sysfs), device_meta);
-            let busname = device_info.get(npu_mgmt::BUSNAME).unwrap();
-            let hwmon_fetcher = hwmon_fetcher_new(sysfs, idx, &busname)?;
+    /// Returns the root directory of procfs.
+    pub fn procfs_root(&self) -> &Path {
+        self.inner.procfs_root()
+    }
 
-            let device = collect_devices(device_info, hwmon_fetcher, paths)?;
-            devices.push(device);
+    /// Returns the directory of lock files for reservations.
+    pub fn runtime_dir_path(&self) -> &Path {
+        self.inner.runtime_dir_path()
+    }
+
+    /// List all Furiosa NPU devices under the roots.
+    pub fn list_devices(&self) -> DeviceResult<Vec<Device>> {
+        let npu_dev_files = self
+            .inner
+            .npu_dev_files(list_devfs_blocking(self.devfs_root())?)?;
+
+        let mut devices: Vec<Device> = Vec::with_capacity(npu_dev_files.len());
+
+        for (idx, paths) in npu_dev_files {
+            if is_furiosa_device_blocking(idx, self.sysfs_root()) {
+                let device_info = self.inner.device_info(idx)?;
+                // Since busname is a required field, it is guaranteed to exist.
+                let busname = device_info.get(npu_mgmt::BUSNAME).unwrap();
+                let hwmon_fetcher = hwmon::Fetcher::new_blocking(self.sysfs_root(), idx, &busname)?;
+
+                let device = collect_devices(device_info, hwmon_fetcher, paths)?;
+                devices.push(device);
+            }
         }
+
+        devices.sort();
+        Ok(devices)
     }
 
-    devices.sort();
-    Ok(devices)
-}
+    /// Find a set of devices with specific configuration under the roots.
+    pub fn find_devices(&self, config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>> {
+        let devices = expand_status(self.list_devices()?)?;
+        find_devices_in(config, &devices, self.inner.local_numa_node(config))
+    }
 
-fn list_devfs<P: AsRef<Path>>(devfs: P) -> io::Result<Vec<DevFile>> {
-    let mut dev_files = Vec::new();
+    /// Find a set of devices with specific configuration under the roots, explaining why
+    /// each of the other device files is not found.
+    pub fn find_devices_report(&self, config: &DeviceConfig) -> DeviceResult<FindReport> {
+        let devices = expand_status(self.list_devices()?)?;
+        Ok(report_devices_in(
+            config,
+            &devices,
+            self.inner.local_numa_node(config),
+        ))
+    }
 
-    for entry in std::fs::read_dir(devfs)? {
-        let file = entry?;
-        dev_files.push(DevFile {
-            path: file.path(),
-            file_type: file.file_type()?,
-        });
+    /// Find a set of devices with specific configuration under the roots, blocking until
+    /// enough device files are available.
+    ///
+    /// See [`crate::DeviceLister::find_devices_wait`].
+    pub fn find_devices_wait(
+        &self,
+        config: &DeviceConfig,
+        timeout: Duration,
+    ) -> DeviceResult<Vec<DeviceFile>> {
+        let deadline = Instant::now() + timeout;
+        let devfs = std::fs::canonicalize(self.devfs_root())
+            .unwrap_or_else(|_| self.devfs_root().to_path_buf());
+        let ticket = Ticket::take(devfs, config);
+        loop {
+            if ticket.is_turn() {
+                let mut devices = expand_status(self.list_devices()?)?;
+                let local_node = self.inner.local_numa_node(config);
+                if let Some(found) = find_devices_or_wait(config, &mut devices, local_node)? {
+                    return Ok(found);
+                }
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(DeviceError::WaitTimedOut {
+                    config: config.to_string(),
+                });
+            };
+            std::thread::sleep(remaining.min(queue::POLL_INTERVAL));
+        }
+    }
+
+    /// Find a set of devices with specific configuration under the roots, blocking until
+    /// enough device files are available, and reserve them.
+    ///
+    /// See [`crate::DeviceLister::reserve_devices_wait`].
+    pub fn reserve_devices_wait(
+        &self,
+        config: &DeviceConfig,
+        timeout: Duration,
+    ) -> DeviceResult<Reservation> {
+        let deadline = Instant::now() + timeout;
+        let runtime_dir = self.runtime_dir_path();
+        let devfs = std::fs::canonicalize(self.devfs_root())
+            .unwrap_or_else(|_| self.devfs_root().to_path_buf());
+        // The ticket is held until the device files are claimed.
+        let ticket = Ticket::take(devfs, config);
+        loop {
+            if ticket.is_turn() {
+                let mut devices =
+                    reserve::expand_status_blocking(self.list_devices()?, runtime_dir)?;
+                let local_node = self.inner.local_numa_node(config);
+                match find_devices_or_wait(config, &mut devices, local_node)? {
+                    Some(found) if found.is_empty() => {
+                        return Err(DeviceError::DeviceNotFound {
+                            name: config.to_string(),
+                        })
+                    }
+                    Some(found) => {
+                        if let Some(reservation) =
+                            reserve::claim_blocking(runtime_dir, &devices, found)?
+                        {
+                            return Ok(reservation);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(DeviceError::WaitTimedOut {
+                    config: config.to_string(),
+                });
+            };
+            std::thread::sleep(remaining.min(queue::POLL_INTERVAL));
+        }
     }
 
-    Ok(dev_files)
+    /// Find a set of devices with specific configuration under the roots, and reserve them
+    /// exclusively among processes sharing the runtime directory.
+    pub fn reserve_devices(&self, config: &DeviceConfig) -> DeviceResult<Reservation> {
+        let runtime_dir = self.runtime_dir_path();
+        for _ in 0..reserve::CLAIM_ATTEMPTS {
+            let devices = reserve::expand_status_blocking(self.list_devices()?, runtime_dir)?;
+            let found = find_devices_in(config, &devices, self.inner.local_numa_node(config))?;
+            if found.is_empty() {
+                break;
+            }
+            if let Some(reservation) = reserve::claim_blocking(runtime_dir, &devices, found)? {
+                return Ok(reservation);
+            }
+        }
+        Err(DeviceError::DeviceNotFound {
+            name: config.to_string(),
+        })
+    }
+
+    /// Watches devfs for devices being added, removed or changed, as an iterator which blocks
+    /// until the next event.
+    pub fn watch(&self) -> DeviceResult<BlockingStream<DeviceWatcher>> {
+        Ok(futures::executor::block_on_stream(self.inner.watch()?))
+    }
+
+    /// Return a specific device under the devfs root if it exists.
+    pub fn get_device<S: AsRef<str>>(&self, device_name: S) -> DeviceResult<DeviceFile> {
+        let path = self.inner.device_path(device_name.as_ref())?;
+        let file = File::open(&path)?;
+        self.inner
+            .to_device_file(&path, file.metadata()?.file_type())
+    }
 }
 
-fn is_furiosa_device(idx: u8, sysfs: &str) -> bool {
-    std::fs::read_to_string(npu_mgmt::path(sysfs, PLATFORM_TYPE, idx))
-        .ok()
-        .filter(|c| npu_mgmt::is_furiosa_platform(c))
-        .is_some()
+impl From<crate::DeviceLister> for DeviceLister {
+    fn from(inner: crate::DeviceLister) -> Self {
+        Self { inner }
+    }
 }
 
 pub(crate) fn expand_status(devices: Vec<Device>) -> DeviceResult<Vec<DeviceWithStatus>> {
@@ -110,62 +326,150 @@ pub(crate) fn expand_status(devices: Vec<Device>) -> DeviceResult<Vec<DeviceWith
     Ok(new_devices)
 }
 
-fn get_device_status<P>(path: P) -> DeviceResult<DeviceStatus>
-where
-    P: AsRef<Path>,
-{
-    let res = OpenOptions::new().read(true).write(true).open(path);
-
-    match res {
-        Ok(_) => Ok(DeviceStatus::Available),
-        Err(err) => {
-            if err.raw_os_error().unwrap_or(0) == 16 {
-                Ok(DeviceStatus::Occupied)
-            } else {
-                Err(err.into())
-            }
-        }
-    }
+/// Examine a specific core of the device, whether it is available or not.
+pub fn get_status_core(device: &Device, core: CoreIdx) -> DeviceResult<CoreStatus> {
+    let statuses = status::probe_files_blocking(device, status::files_of_core(device, core))?;
+    Ok(status::resolve_statuses(device, statuses)
+        .remove(&core)
+        .unwrap_or(CoreStatus::Available))
 }
 
 /// Examine each core of the device, whether it is available or not.
 pub fn get_status_all(device: &Device) -> DeviceResult<HashMap<CoreIdx, CoreStatus>> {
-    let mut status_map = device.new_status_map();
-
-    for file in &device.dev_files {
-        if get_device_status(&file.path)? == DeviceStatus::Occupied {
-            for core in device
-                .cores()
-                .iter()
-                .filter(|c| file.core_range().contains(*c))
-            {
-                status_map.insert(*core, CoreStatus::Occupied(file.to_string()));
-            }
-        }
+    let statuses = status::probe_files_blocking(device, device.dev_files())?;
+    Ok(status::resolve_statuses(device, statuses))
+}
+
+/// Identifies processes holding any device file containing the core.
+///
+/// See [`Device::get_core_processes`] for details.
+pub fn get_core_processes(device: &Device, core: CoreIdx) -> DeviceResult<Vec<ProcessInfo>> {
+    let paths = status::files_of_core(device, core)
+        .map(|file| file.path.clone())
+        .collect();
+    let processes = procfs::find_processes_blocking(device.device_info().proc_root(), paths);
+    Ok(procfs::merge_processes(processes))
+}
+
+/// Returns a blocking [`Fetcher`] for hwmon metric of the device.
+pub fn get_hwmon_fetcher(device: &Device) -> Fetcher<'_> {
+    Fetcher::from(device.get_hwmon_fetcher())
+}
+
+/// A blocking counterpart of [`hwmon::Fetcher`], which reads the same sensors synchronously.
+#[derive(Debug, Clone, Copy)]
+pub struct Fetcher<'a> {
+    inner: &'a hwmon::Fetcher,
+}
+
+impl<'a> From<&'a hwmon::Fetcher> for Fetcher<'a> {
+    fn from(inner: &'a hwmon::Fetcher) -> Self {
+        Self { inner }
     }
-    Ok(status_map)
 }
 
-fn hwmon_fetcher_new(_: &str, device_index: u8, _: &str) -> DeviceResult<hwmon::Fetcher> {
-    Ok(hwmon::Fetcher {
-        device_index,
-        sensor_container: hwmon::SensorContainer(HashMap::new()),
-    })
+impl Fetcher<'_> {
+    pub fn read_currents(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.inner.read_values_blocking(HwmonType::Current, "input")
+    }
+
+    pub fn read_voltages(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.inner.read_values_blocking(HwmonType::Voltage, "input")
+    }
+
+    pub fn read_powers_average(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.inner.read_values_blocking(HwmonType::Power, "average")
+    }
+
+    pub fn read_temperatures(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.inner
+            .read_values_blocking(HwmonType::Temperature, "input")
+    }
+
+    pub fn read_powers_input(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.inner.read_values_blocking(HwmonType::Power, "input")
+    }
+
+    /// Reads sensors of the type of the unit `U`. See [`hwmon::Fetcher::read`].
+    pub fn read<U: Unit>(&self) -> DeviceResult<Vec<Reading<U>>> {
+        let values = self
+            .inner
+            .read_values_blocking(U::HWMON_TYPE, hwmon::unit_item::<U>())?;
+        Ok(hwmon::to_readings(values))
+    }
+
+    /// Reads all published items of each sensor of the given type.
+    pub fn read_records(&self, t: HwmonType) -> DeviceResult<Vec<SensorRecord>> {
+        self.inner.read_records_blocking(t)
+    }
+
+    /// Reads all published items of every sensor of the device, ordered by type.
+    pub fn read_all_records(&self) -> DeviceResult<Vec<SensorRecord>> {
+        let mut res = vec![];
+        for t in self.inner.types() {
+            res.extend(self.read_records(t)?);
+        }
+        Ok(res)
+    }
+
+    /// Reads all sensors of the device at once, discovering sensors again.
+    /// See [`hwmon::Fetcher::read_snapshot`].
+    pub fn read_snapshot(&self) -> DeviceResult<Snapshot> {
+        self.inner.read_snapshot_blocking()
+    }
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::fake::{lock_device_file, test_lister, FakeSystem};
+
     use super::*;
 
+    #[test]
+    fn test_reserve_devices() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = DeviceLister::from(test_lister()).runtime_dir(runtime_dir.path());
+        let config = DeviceConfig::warboy().fused().count(2);
+
+        let reservation = lister.reserve_devices(&config)?;
+        assert_eq!(reservation.len(), 2);
+        assert!(matches!(
+            lister.reserve_devices(&DeviceConfig::warboy().single().build()),
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+        drop(reservation);
+        assert_eq!(
+            lister.reserve_devices(&config)?.dev_files()[0].filename(),
+            "npu0pe0-1"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_watch() -> Result<(), Box<dyn std::error::Error>> {
+        let devfs = tempfile::tempdir()?;
+        let mut watcher = DeviceLister::new()
+            .devfs(devfs.path())
+            .allow_regular_files(true)
+            .watch()?;
+
+        std::fs::write(devfs.path().join("npu2"), "")?;
+        assert_eq!(
+            watcher.next().unwrap()?,
+            crate::DeviceEvent::Added { device_index: 2 }
+        );
+        Ok(())
+    }
+
     #[test]
     fn test_find_devices() -> DeviceResult<()> {
         // test directory contains 2 warboy NPUs
-        let devices = list_devices_with("test_data/test-0/dev", "test_data/test-0/sys")?;
+        let devices = DeviceLister::from(test_lister()).list_devices()?;
         let devices_with_statuses = expand_status(devices)?;
 
         // try lookup 4 different single cores
         let config = DeviceConfig::warboy().single().count(4);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found.len(), 4);
         assert_eq!(found[0].filename(), "npu0pe0");
         assert_eq!(found[1].filename(), "npu0pe1");
@@ -174,36 +478,141 @@ mod tests {
 
         // looking for 5 different cores should fail
         let config = DeviceConfig::warboy().single().count(5);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found, vec![]);
 
         // try lookup 2 different fused cores
         let config = DeviceConfig::warboy().fused().count(2);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found.len(), 2);
         assert_eq!(found[0].filename(), "npu0pe0-1");
         assert_eq!(found[1].filename(), "npu1pe0-1");
 
         // looking for 3 different fused cores should fail
         let config = DeviceConfig::warboy().fused().count(3);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found, vec![]);
 
         Ok(())
     }
 
+    #[test]
+    fn test_find_devices_wait() -> Result<(), Box<dyn std::error::Error>> {
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        let lock_dir = tempfile::tempdir()?;
+        let lister: DeviceLister = system
+            .lister()
+            .occupancy_probe(OccupancyProbe::LockFile(lock_dir.path().to_path_buf()))
+            .into();
+        let config = DeviceConfig::warboy().single().count(2);
+
+        let lock = lock_device_file(lock_dir.path(), "npu0pe1")?;
+        let err = lister
+            .find_devices_wait(&config, Duration::from_millis(200))
+            .unwrap_err();
+        assert!(matches!(err, DeviceError::WaitTimedOut { .. }));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(lock);
+        });
+        let found = lister.find_devices_wait(&config, Duration::from_secs(5))?;
+        assert_eq!(found.len(), 2);
+        release.join().unwrap();
+        Ok(())
+    }
+
+    #[test]
+    fn test_reserve_devices_wait() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = DeviceLister::from(test_lister()).runtime_dir(runtime_dir.path());
+        let config = DeviceConfig::warboy().fused().count(2);
+
+        let other = lister.reserve_devices(&"1:1".parse().unwrap())?;
+        let err = lister
+            .reserve_devices_wait(&config, Duration::from_millis(200))
+            .unwrap_err();
+        assert!(matches!(err, DeviceError::WaitTimedOut { .. }));
+
+        let release = std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(200));
+            drop(other);
+        });
+        let reservation = lister.reserve_devices_wait(&config, Duration::from_secs(5))?;
+        assert_eq!(reservation.len(), 2);
+        release.join().unwrap();
+        Ok(())
+    }
+
     #[test]
     fn test_get_device() -> DeviceResult<()> {
-        get_with("test_data/test-0/dev", "npu0")?;
-        assert!(get_with("test_data/test-0/dev", "npu0pe0").is_ok());
-        assert!(get_with("test_data/test-0/dev", "npu0pe1").is_ok());
-        assert!(get_with("test_data/test-0/dev", "npu0pe0-1").is_ok());
+        let lister = DeviceLister::from(test_lister());
+        lister.get_device("npu0")?;
+        assert!(lister.get_device("npu0pe0").is_ok());
+        assert!(lister.get_device("npu0pe1").is_ok());
+        assert!(lister.get_device("npu0pe0-1").is_ok());
 
         assert!(matches!(
-            get_with("test_data/test-0/dev", "npu9"),
+            lister.get_device("npu9"),
             Err(DeviceError::DeviceNotFound { .. })
         ));
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn test_hwmon_matches_async() -> DeviceResult<()> {
+        let devices = DeviceLister::from(test_lister()).list_devices()?;
+        let async_devices = test_lister().list_devices().await?;
+        assert_eq!(devices.len(), async_devices.len());
+
+        for (device, async_device) in devices.iter().zip(&async_devices) {
+            let async_fetcher = async_device.get_hwmon_fetcher();
+            assert_eq!(device.get_hwmon_fetcher(), async_fetcher);
+
+            let fetcher = get_hwmon_fetcher(device);
+            assert_eq!(
+                fetcher.read_currents()?,
+                async_fetcher.read_currents().await?
+            );
+            assert_eq!(
+                fetcher.read_voltages()?,
+                async_fetcher.read_voltages().await?
+            );
+            assert_eq!(
+                fetcher.read_powers_average()?,
+                async_fetcher.read_powers_average().await?
+            );
+            assert_eq!(
+                fetcher.read_temperatures()?,
+                async_fetcher.read_temperatures().await?
+            );
+            assert_eq!(
+                fetcher.read::<hwmon::Celsius>()?,
+                async_fetcher.read::<hwmon::Celsius>().await?
+            );
+            assert_eq!(
+                fetcher.read_all_records()?,
+                async_fetcher.read_all_records().await?
+            );
+
+            let snapshot = fetcher.read_snapshot()?;
+            let async_snapshot = async_fetcher.read_snapshot().await?;
+            assert_eq!(
+                Snapshot {
+                    timestamp: async_snapshot.timestamp,
+                    ..snapshot
+                },
+                async_snapshot
+            );
+        }
+
+        let temperatures = get_hwmon_fetcher(&devices[0]).read_temperatures()?;
+        assert_eq!(temperatures.len(), 2);
+        assert_eq!(temperatures[0].label, "Temp1");
+        assert_eq!(temperatures[0].value, 36000);
+
+        Ok(())
+    }
 }
@@ -0,0 +1,160 @@
+//! A conformance test suite which checks that every blocking API behaves identically to its
+//! async counterpart, on the same devfs and sysfs.
+
+use std::fmt::Debug;
+use std::path::Path;
+
+use crate::hwmon::{Celsius, HwmonType};
+use crate::{blocking, DeviceConfig, DeviceLister, DeviceResult, OccupancyProbe};
+
+/// Both results are the same value, or errors with the same message.
+fn assert_same<T: Debug + PartialEq>(blocking: DeviceResult<T>, non_blocking: DeviceResult<T>) {
+    match (blocking, non_blocking) {
+        (Ok(b), Ok(a)) => assert_eq!(b, a),
+        (Err(b), Err(a)) => assert_eq!(b.to_string(), a.to_string()),
+        (b, a) => panic!("blocking: {:?}, async: {:?}", b, a),
+    }
+}
+
+fn listers<P: AsRef<Path>>(devfs: P, sysfs: P) -> (blocking::DeviceLister, DeviceLister) {
+    let lister = DeviceLister::new()
+        .devfs(devfs.as_ref())
+        .sysfs(sysfs.as_ref())
+        .allow_regular_files(true);
+    (lister.clone().into(), lister)
+}
+
+async fn check_conformance(blocking: blocking::DeviceLister, lister: DeviceLister) {
+    assert_same(blocking.list_devices(), lister.list_devices().await);
+
+    if let (Ok(devices), Ok(async_devices)) = (blocking.list_devices(), lister.list_devices().await)
+    {
+        for (device, async_device) in devices.iter().zip(&async_devices) {
+            assert_same(device.numa_node(), async_device.numa_node());
+            assert_same(
+                blocking::get_status_all(device),
+                async_device.get_status_all().await,
+            );
+            // Including a core which does not exist
+            for core in device.cores().iter().copied().chain([u8::MAX]) {
+                assert_same(
+                    blocking::get_status_core(device, core),
+                    async_device.get_status_core(core).await,
+                );
+            }
+
+            let fetcher = blocking::get_hwmon_fetcher(device);
+            let async_fetcher = async_device.get_hwmon_fetcher();
+            assert_same(fetcher.read_currents(), async_fetcher.read_currents().await);
+            assert_same(fetcher.read_voltages(), async_fetcher.read_voltages().await);
+            assert_same(
+                fetcher.read_powers_average(),
+                async_fetcher.read_powers_average().await,
+            );
+            assert_same(
+                fetcher.read_powers_input(),
+                async_fetcher.read_powers_input().await,
+            );
+            assert_same(
+                fetcher.read_temperatures(),
+                async_fetcher.read_temperatures().await,
+            );
+            assert_same(
+                fetcher.read::<Celsius>(),
+                async_fetcher.read::<Celsius>().await,
+            );
+            assert_same(
+                fetcher.read_records(HwmonType::Power),
+                async_fetcher.read_records(HwmonType::Power).await,
+            );
+            assert_same(
+                fetcher.read_all_records(),
+                async_fetcher.read_all_records().await,
+            );
+        }
+    }
+
+    for config in [
+        "warboy*1",
+        "warboy(1)*1",
+        "warboy(2)*2",
+        "warboy(1)*5",
+        "warboy(1)*2@0",
+        "warboy(2)*2@same",
+        "warboy(1)*1@local",
+        "warboy(1)*3#spread",
+        "warboy(1)*2@same#min-fragmentation",
+        "0",
+        "1:0-1",
+        "7:0",
+    ] {
+        let config = config.parse::<DeviceConfig>().unwrap();
+        assert_same(
+            blocking.find_devices(&config),
+            lister.find_devices(&config).await,
+        );
+    }
+
+    for name in [
+        "npu0",
+        "npu0pe1",
+        "npu1pe0-1",
+        "npu9",
+        "npu0pe9",
+        "npu0_mgmt",
+    ] {
+        assert_same(blocking.get_device(name), lister.get_device(name).await);
+    }
+}
+
+#[tokio::test]
+async fn test_conformance() {
+    let (blocking, lister) = listers("test_data/test-0/dev", "test_data/test-0/sys");
+    check_conformance(blocking, lister).await;
+}
+
+#[tokio::test]
+async fn test_conformance_probes() -> std::io::Result<()> {
+    let lock_dir = tempfile::tempdir()?;
+    for probe in [
+        OccupancyProbe::Procfs,
+        OccupancyProbe::Sysfs,
+        OccupancyProbe::LockFile(lock_dir.path().to_path_buf()),
+    ] {
+        let (blocking, lister) = listers("test_data/test-0/dev", "test_data/test-0/sys");
+        check_conformance(
+            blocking.occupancy_probe(probe.clone()),
+            lister.occupancy_probe(probe),
+        )
+        .await;
+    }
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conformance_empty_devfs() -> std::io::Result<()> {
+    let devfs = tempfile::tempdir()?;
+    let (blocking, lister) = listers(devfs.path(), Path::new("test_data/test-0/sys"));
+    check_conformance(blocking, lister).await;
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_conformance_missing_roots() {
+    let (blocking, lister) = listers("test_data/no-dev", "test_data/no-sys");
+    check_conformance(blocking, lister).await;
+}
+
+#[tokio::test]
+async fn test_conformance_missing_mgmt_files() -> std::io::Result<()> {
+    // The device file exists, but sysfs lacks its required mgmt files
+    let sysfs = tempfile::tempdir()?;
+    let mgmt = sysfs.path().join("class/npu_mgmt/npu0_mgmt");
+    std::fs::create_dir_all(&mgmt)?;
+    std::fs::write(mgmt.join("platform_type"), "FuriosaAI\n")?;
+
+    let (blocking, lister) = listers(Path::new("test_data/test-0/dev"), sysfs.path());
+    assert!(lister.list_devices().await.is_err());
+    check_conformance(blocking, lister).await;
+    Ok(())
+}
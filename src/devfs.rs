@@ -17,13 +17,9 @@ pub(crate) fn path<P: AsRef<Path>>(base_path: P, filename: &str) -> PathBuf {
     base_path.as_ref().join(filename)
 }
 
-pub(crate) fn is_character_device(file_type: FileType) -> bool {
-    // allow just a file too for unit testing
-    if cfg!(test) {
-        file_type.is_file()
-    } else {
-        file_type.is_char_device()
-    }
+/// Regular files are allowed as well if requested, which is useful for a fake devfs.
+pub(crate) fn is_device_file(file_type: FileType, allow_regular_files: bool) -> bool {
+    file_type.is_char_device() || (allow_regular_files && file_type.is_file())
 }
 
 pub(crate) fn parse_indices<S: AsRef<str>>(filename: S) -> DeviceResult<(u8, Vec<u8>)> {
@@ -40,7 +36,7 @@ pub(crate) fn parse_indices<S: AsRef<str>>(filename: S) -> DeviceResult<(u8, Vec
         (Some(device_id), None, None) => (device_id?, vec![]),
         (Some(device_id), Some(start_core), None) => (device_id?, vec![start_core?]),
         (Some(device_id), Some(start_core), Some(end_core)) => {
-            (device_id?, (start_core?..=end_core?).into_iter().collect())
+            (device_id?, (start_core?..=end_core?).collect())
         }
         _ => return Err(DeviceError::unrecognized_file(name)),
     };
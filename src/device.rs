@@ -1,15 +1,23 @@
 use std::cmp::Ordering;
 use std::collections::HashMap;
 use std::convert::TryFrom;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 
-use std::cell::RefCell;
 use std::fmt::{self, Display, Formatter};
 use std::str::FromStr;
+use std::sync::atomic::{self, AtomicU64};
+use std::sync::{Arc, OnceLock, PoisonError, RwLock};
+use std::time::{Duration, Instant};
 
+use uuid::Uuid;
+
+use crate::affinity;
 use crate::arch::Arch;
 use crate::hwmon;
-use crate::status::{get_device_status, DeviceStatus};
+use crate::procfs::{self, ProcessInfo, DEFAULT_PROCFS};
+use crate::status::{self, OccupancyProbe};
+use crate::subscription::StatusSubscription;
+use crate::sysfs::npu_mgmt::{ClockFrequency, DeviceState, DriverVersion, PlatformType};
 use crate::{devfs, sysfs, DeviceError, DeviceResult};
 
 #[derive(Debug, Eq, PartialEq)]
@@ -23,11 +31,12 @@ use crate::{devfs, sysfs, DeviceError, DeviceResult};
 /// as following:
 /// * [`Single`][crate::DeviceMode::Single]: A logical device is composed of a single core.
 /// * [`Fusion`][crate::DeviceMode::Fusion]: Multiple cores work together as if
-///     they were one device. This mode is useful when a DNN model requires
-///      much computation power and large memory capacity.
+///   they were one device. This mode is useful when a DNN model requires
+///   much computation power and large memory capacity.
 /// * [`MultiCore`][crate::DeviceMode::MultiCore]: A logical device uses multiple cores,
-///     each of which communicates to one another through interconnect.
-///     In this mode, partitions of a model or multiple models can be pipelined.
+///   each of which communicates to one another through interconnect.
+///   In this mode, partitions of a model or multiple models can be pipelined.
+///
 /// (See [`DeviceConfig`][crate::DeviceConfig] and
 /// [`find_devices`][crate::find_devices]).
 ///
@@ -36,6 +45,20 @@ use crate::{devfs, sysfs, DeviceError, DeviceResult};
 /// method, which returns a list of [`DeviceFile`]s.
 /// Each [`DeviceFile`] again offers [`mode`][DeviceFile::mode] method to
 /// identify its [`DeviceMode`].
+///
+/// With the `serde` feature, a device is serialized (but not deserialized) as a snapshot:
+/// ```json
+/// {
+///   "name": "npu0",
+///   "device_index": 0,
+///   "arch": "warboy",
+///   "busname": "0000:6d:00.0",
+///   "numa_node": 0,
+///   "cores": [0, 1],
+///   "dev_files": [{"device_index": 0, "core_range": "all", "path": "/dev/npu0", "mode": "multicore"}]
+/// }
+/// ```
+/// `numa_node` is `null` if the device has no NUMA affinity or it cannot be read.
 pub struct Device {
     device_info: DeviceInfo,
     hwmon_fetcher: hwmon::Fetcher,
@@ -73,6 +96,19 @@ impl Device {
         &self.device_info
     }
 
+    /// Sets how long the values of volatile mgmt attributes are cached.
+    ///
+    /// See [`CachePolicy`] for details.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.device_info.set_cache_policy(policy)
+    }
+
+    /// Drops the cached values of volatile mgmt attributes, so that the next reads
+    /// retrieve them from sysfs again.
+    pub fn refresh(&self) {
+        self.device_info.refresh()
+    }
+
     /// Returns `Arch` of the device(e.g., `Warboy`).
     pub fn arch(&self) -> Arch {
         self.device_info().arch()
@@ -97,6 +133,11 @@ impl Device {
             .map(sysfs::npu_mgmt::build_atr_error_map)
     }
 
+    /// Returns counters of PCIe AER errors of the device.
+    pub fn aer_counters(&self) -> DeviceResult<sysfs::pci::AerCounters> {
+        self.device_info.get_aer_counters()
+    }
+
     /// Returns PCI bus number of the device.
     pub fn busname(&self) -> DeviceResult<String> {
         self.device_info.get(sysfs::npu_mgmt::BUSNAME)
@@ -112,6 +153,66 @@ impl Device {
         self.device_info.get(sysfs::npu_mgmt::FW_VERSION)
     }
 
+    /// Returns UUID of the device.
+    pub fn device_uuid(&self) -> DeviceResult<Uuid> {
+        self.device_info
+            .get(sysfs::npu_mgmt::DEVICE_UUID)
+            .and_then(|str| {
+                Uuid::from_str(&str)
+                    .map_err(|_| DeviceError::unexpected_value(format!("Bad device uuid: {}", str)))
+            })
+    }
+
+    /// Returns the unique identifier of the SoC.
+    pub fn soc_uid(&self) -> DeviceResult<String> {
+        self.device_info.get(sysfs::npu_mgmt::SOC_UID)
+    }
+
+    /// Returns revision of the SoC.
+    pub fn soc_rev(&self) -> DeviceResult<String> {
+        self.device_info.get(sysfs::npu_mgmt::SOC_REV)
+    }
+
+    /// Returns revision of the evaluation board.
+    pub fn evb_rev(&self) -> DeviceResult<String> {
+        self.device_info.get(sysfs::npu_mgmt::EVB_REV)
+    }
+
+    /// Retrieves version of the kernel driver.
+    pub fn driver_version(&self) -> DeviceResult<DriverVersion> {
+        self.device_info
+            .get(sysfs::npu_mgmt::VERSION)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the platform type of the device.
+    pub fn platform_type(&self) -> DeviceResult<PlatformType> {
+        self.device_info
+            .get(sysfs::npu_mgmt::PLATFORM_TYPE)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the current state of the device.
+    pub fn device_state(&self) -> DeviceResult<DeviceState> {
+        self.device_info
+            .get(sysfs::npu_mgmt::DEVICE_STATE)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the indices of PEs which are currently in use.
+    pub fn cur_pe_ids(&self) -> DeviceResult<Vec<CoreIdx>> {
+        self.device_info
+            .get(sysfs::npu_mgmt::CUR_PE_IDS)
+            .and_then(sysfs::npu_mgmt::parse_pe_ids)
+    }
+
+    /// Returns clock frequencies of the components in the device.
+    pub fn clock_frequency(&self) -> DeviceResult<Vec<ClockFrequency>> {
+        self.device_info
+            .get(sysfs::npu_mgmt::NE_CLK_FREQ_INFO)
+            .and_then(sysfs::npu_mgmt::parse_clock_frequencies)
+    }
+
     /// Returns uptime of the device.
     pub fn heartbeat(&self) -> DeviceResult<u32> {
         self.device_info
@@ -157,11 +258,98 @@ impl Device {
             .ctrl(sysfs::npu_mgmt::PERFORMANCE_MODE, &(mode as u8).to_string())
     }
 
+    /// Returns the current state of NE clocks.
+    pub fn ne_clock(&self) -> DeviceResult<sysfs::npu_mgmt::Toggle> {
+        self.device_info
+            .get(sysfs::npu_mgmt::NE_CLOCK)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the current Dynamic Thermal Management policy.
+    pub fn ne_dtm_policy(&self) -> DeviceResult<sysfs::npu_mgmt::DtmPolicy> {
+        self.device_info
+            .get(sysfs::npu_mgmt::NE_DTM_POLICY)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the current NE performance level.
+    pub fn performance_level(&self) -> DeviceResult<sysfs::npu_mgmt::PerfLevel> {
+        self.device_info
+            .get(sysfs::npu_mgmt::PERFORMANCE_LEVEL)
+            .and_then(|str| str.parse())
+    }
+
+    /// Returns the current NE performance mode.
+    pub fn performance_mode(&self) -> DeviceResult<sysfs::npu_mgmt::PerfMode> {
+        self.device_info
+            .get(sysfs::npu_mgmt::PERFORMANCE_MODE)
+            .and_then(|str| str.parse())
+    }
+
     /// Retrieve NUMA node ID associated with the NPU's PCI lane
     pub fn numa_node(&self) -> DeviceResult<NumaNode> {
         self.device_info.get_numa_node()
     }
 
+    /// Reads identity, link and state of the NPU's PCI device, e.g., to detect a degraded
+    /// PCIe link with [`PciInfo::is_link_degraded`][sysfs::pci::PciInfo::is_link_degraded].
+    pub fn pci_info(&self) -> DeviceResult<sysfs::pci::PciInfo> {
+        self.device_info.get_pci_info()
+    }
+
+    /// Returns the CPUs close to the NPU's PCI lane in ascending order, as listed in
+    /// `local_cpulist` of the PCI device.
+    pub fn local_cpus(&self) -> DeviceResult<Vec<usize>> {
+        self.device_info.get_local_cpus()
+    }
+
+    /// Pins the calling thread to the [local CPUs][Self::local_cpus] of the NPU. Threads
+    /// spawned by it afterwards inherit them.
+    ///
+    /// It is meant for threads dedicated to the NPU, e.g., spawned with [`std::thread::spawn`]
+    /// or inside `tokio::task::spawn_blocking`. Called from an async task, it pins whichever
+    /// worker thread of the runtime happens to run the task, and the task may move to another
+    /// worker afterwards. To pin all workers of a runtime, call it when each worker starts:
+    ///
+    /// ```rust,ignore
+    /// let device = Arc::new(device);
+    /// let runtime = tokio::runtime::Builder::new_multi_thread()
+    ///     .on_thread_start({
+    ///         let device = device.clone();
+    ///         move || {
+    ///             if let Err(e) = device.pin_current_thread() {
+    ///                 eprintln!("Failed to pin a worker thread: {}", e);
+    ///             }
+    ///         }
+    ///     })
+    ///     .build()?;
+    /// ```
+    pub fn pin_current_thread(&self) -> DeviceResult<()> {
+        let cpus = self.local_cpus_to_pin()?;
+        Ok(affinity::set_thread_affinity(0, &cpus)?)
+    }
+
+    /// Pins all threads of the calling process to the [local CPUs][Self::local_cpus] of
+    /// the NPU.
+    pub fn pin_current_process(&self) -> DeviceResult<()> {
+        let cpus = self.local_cpus_to_pin()?;
+        Ok(affinity::set_process_affinity(
+            self.device_info.proc_root(),
+            &cpus,
+        )?)
+    }
+
+    fn local_cpus_to_pin(&self) -> DeviceResult<Vec<usize>> {
+        let cpus = self.local_cpus()?;
+        if cpus.is_empty() {
+            return Err(DeviceError::unexpected_value(format!(
+                "{} has no local CPUs",
+                self.name()
+            )));
+        }
+        Ok(cpus)
+    }
+
     /// Counts the number of cores.
     pub fn core_num(&self) -> u8 {
         u8::try_from(self.cores.len()).unwrap()
@@ -179,25 +367,57 @@ impl Device {
 
     /// Examine a specific core of the device, whether it is available or not.
     pub async fn get_status_core(&self, core: CoreIdx) -> DeviceResult<CoreStatus> {
-        for file in &self.dev_files {
-            if (file.core_range().contains(&core))
-                && get_device_status(&file.path).await? == DeviceStatus::Occupied
-            {
-                return Ok(CoreStatus::Occupied(file.to_string()));
-            }
-        }
-        Ok(CoreStatus::Available)
+        let statuses = status::probe_files(self, status::files_of_core(self, core)).await?;
+        Ok(status::resolve_statuses(self, statuses)
+            .remove(&core)
+            .unwrap_or(CoreStatus::Available))
     }
 
     /// Examine each core of the device, whether it is available or not.
     pub async fn get_status_all(&self) -> DeviceResult<HashMap<CoreIdx, CoreStatus>> {
-        let mut status_map = self.new_status_map();
+        let statuses = status::probe_files(self, &self.dev_files).await?;
+        Ok(status::resolve_statuses(self, statuses))
+    }
 
-        for core in self.cores() {
-            let status = self.get_status_core(*core).await?;
-            status_map.insert(*core, status);
-        }
-        Ok(status_map)
+    /// Identifies processes holding any device file containing the core, by scanning file
+    /// descriptors under procfs (see [`DeviceLister::procfs`][crate::DeviceLister::procfs]).
+    ///
+    /// Processes which are not visible, e.g., in another PID namespace or owned by another
+    /// user without privileges, are missed, so it may be empty even if the core is occupied.
+    pub async fn get_core_processes(&self, core: CoreIdx) -> DeviceResult<Vec<ProcessInfo>> {
+        let paths = status::files_of_core(self, core)
+            .map(|file| file.path.clone())
+            .collect();
+        let processes = procfs::find_processes(self.device_info.proc_root(), paths).await;
+        Ok(procfs::merge_processes(processes))
+    }
+
+    /// Subscribe to transitions of the statuses of the cores, to await an available core
+    /// instead of examining the cores repeatedly.
+    ///
+    /// The subscription holds the device, so that it can be moved to another task.
+    /// See [`StatusSubscription`] for details.
+    ///
+    /// # Examples
+    /// ```rust,ignore
+    /// let device = Arc::new(furiosa_device::list_devices().await?.remove(0));
+    /// let mut subscription = device.subscribe_status();
+    /// tokio::spawn(async move { subscription.wait_available(0).await });
+    /// ```
+    pub fn subscribe_status(self: &Arc<Self>) -> StatusSubscription {
+        StatusSubscription::new(self.clone())
+    }
+
+    /// Returns how to examine whether device files are occupied.
+    pub fn occupancy_probe(&self) -> &OccupancyProbe {
+        self.device_info.occupancy_probe()
+    }
+
+    /// Sets how to examine whether device files are occupied.
+    ///
+    /// See [`OccupancyProbe`] for details.
+    pub fn set_occupancy_probe(&mut self, probe: OccupancyProbe) {
+        self.device_info.set_occupancy_probe(probe)
     }
 
     pub(crate) fn new_status_map(&self) -> HashMap<CoreIdx, CoreStatus> {
@@ -231,21 +451,98 @@ impl PartialOrd for Device {
     }
 }
 
+/// NUMA node associated with a device.
+///
+/// With the `serde` feature, it is serialized as the node id, or `null` if unsupported.
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
 pub enum NumaNode {
     UnSupported,
     Id(usize),
 }
 
-#[derive(Debug, Eq, PartialEq)]
+#[cfg(feature = "serde")]
+impl serde::Serialize for NumaNode {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            NumaNode::UnSupported => serializer.serialize_none(),
+            NumaNode::Id(id) => serializer.serialize_some(id),
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for NumaNode {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        Ok(match Option::<usize>::deserialize(deserializer)? {
+            Some(id) => NumaNode::Id(id),
+            None => NumaNode::UnSupported,
+        })
+    }
+}
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Device {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        use serde::ser::{Error, SerializeStruct};
+
+        let mut state = serializer.serialize_struct("Device", 7)?;
+        state.serialize_field("name", &self.name())?;
+        state.serialize_field("device_index", &self.device_index())?;
+        state.serialize_field("arch", &self.arch())?;
+        state.serialize_field("busname", &self.busname().map_err(S::Error::custom)?)?;
+        // The NUMA node is not essential to the snapshot, which should not fail for its sake.
+        state.serialize_field("numa_node", &self.numa_node().ok())?;
+        state.serialize_field("cores", self.cores())?;
+        state.serialize_field("dev_files", self.dev_files())?;
+        state.end()
+    }
+}
+
+/// Determines how long [`DeviceInfo::get`] caches the values of volatile mgmt attributes
+/// (e.g., `heartbeat`, `alive`, `device_state`).
+///
+/// Static attributes such as `busname`, `dev`, `device_type` and `device_uuid` never change
+/// while the device exists, so they are always cached regardless of the policy.
+#[derive(Debug, Default, Eq, PartialEq, Copy, Clone)]
+pub enum CachePolicy {
+    /// Always read the value from sysfs.
+    #[default]
+    Never,
+    /// Reuse the value read within the given duration.
+    Ttl(Duration),
+    /// Reuse the value read once until [`DeviceInfo::refresh`] or a write through
+    /// [`DeviceInfo::ctrl`].
+    Forever,
+}
+
+#[derive(Debug)]
 pub struct DeviceInfo {
     device_index: u8,
     dev_root: PathBuf,
     sys_root: PathBuf,
     meta: DeviceMetadata,
-    numa_node: RefCell<Option<NumaNode>>,
+    numa_node: OnceLock<NumaNode>,
+    cache_policy: CachePolicy,
+    proc_root: PathBuf,
+    occupancy_probe: OccupancyProbe,
+}
+
+/// Devices listed twice are equal regardless of the values read and cached in between,
+/// including the NUMA node.
+impl PartialEq for DeviceInfo {
+    fn eq(&self, other: &Self) -> bool {
+        self.device_index == other.device_index
+            && self.dev_root == other.dev_root
+            && self.sys_root == other.sys_root
+            && self.meta == other.meta
+            && self.cache_policy == other.cache_policy
+            && self.proc_root == other.proc_root
+            && self.occupancy_probe == other.occupancy_probe
+    }
 }
 
+impl Eq for DeviceInfo {}
+
 impl DeviceInfo {
     pub(crate) fn new(
         device_index: u8,
@@ -258,30 +555,83 @@ impl DeviceInfo {
             dev_root,
             sys_root,
             meta,
-            numa_node: RefCell::new(None),
+            numa_node: OnceLock::new(),
+            cache_policy: CachePolicy::default(),
+            proc_root: PathBuf::from(DEFAULT_PROCFS),
+            occupancy_probe: OccupancyProbe::default(),
         }
     }
 
+    pub(crate) fn dev_root(&self) -> &Path {
+        &self.dev_root
+    }
+
+    pub(crate) fn sys_root(&self) -> &Path {
+        &self.sys_root
+    }
+
+    pub(crate) fn proc_root(&self) -> &Path {
+        &self.proc_root
+    }
+
+    pub(crate) fn set_proc_root(&mut self, proc_root: PathBuf) {
+        self.proc_root = proc_root;
+    }
+
+    pub(crate) fn occupancy_probe(&self) -> &OccupancyProbe {
+        &self.occupancy_probe
+    }
+
+    pub(crate) fn set_occupancy_probe(&mut self, probe: OccupancyProbe) {
+        self.occupancy_probe = probe;
+    }
+
+    /// Returns the cache policy for volatile mgmt attributes.
+    pub fn cache_policy(&self) -> CachePolicy {
+        self.cache_policy
+    }
+
+    /// Sets the cache policy for volatile mgmt attributes.
+    pub fn set_cache_policy(&mut self, policy: CachePolicy) {
+        self.cache_policy = policy;
+    }
+
+    /// Drops the cached values of volatile mgmt attributes.
+    pub fn refresh(&self) {
+        self.meta.retain(sysfs::npu_mgmt::is_static_file);
+    }
+
     pub fn arch(&self) -> Arch {
         self.meta.arch
     }
 
+    /// Reads a mgmt attribute of the device (e.g., [`sysfs::npu_mgmt::VERSION`]).
     pub fn get(&self, key: &str) -> DeviceResult<String> {
         let (key, _) = sysfs::npu_mgmt::MGMT_FILES
             .iter()
             .find(|mgmt_file| mgmt_file.0 == key)
             .ok_or_else(|| DeviceError::unsupported_key(key))?;
 
-        if let Some(value) = self.meta.map.borrow().get(key) {
-            return Ok(value.clone());
+        let policy = if sysfs::npu_mgmt::is_static_file(key) {
+            CachePolicy::Forever
+        } else {
+            self.cache_policy
+        };
+
+        if let Some(value) = self.meta.cached(key, policy) {
+            return Ok(value);
         }
 
+        let generation = self.meta.generation();
         let value = sysfs::npu_mgmt::read_mgmt_file(&self.sys_root, key, self.device_index)?;
 
-        self.meta.map.borrow_mut().insert(key, value.clone());
+        if policy != CachePolicy::Never {
+            self.meta.cache(key, value.clone(), generation);
+        }
         Ok(value)
     }
 
+    /// Writes a control attribute of the device (e.g., [`sysfs::npu_mgmt::DEVICE_LED`]).
     pub fn ctrl(&self, key: &str, contents: &str) -> DeviceResult<()> {
         let key = sysfs::npu_mgmt::CTRL_FILES
             .iter()
@@ -290,19 +640,33 @@ impl DeviceInfo {
 
         sysfs::npu_mgmt::write_ctrl_file(&self.sys_root, key, self.device_index, contents)?;
 
-        if let Some((key, _)) = sysfs::npu_mgmt::MGMT_FILES
-            .iter()
-            .find(|mgmt_file| mgmt_file.0 == *key)
-        {
-            self.meta.map.borrow_mut().remove(key);
-        }
+        // Drop the cached value so that the next `get` reads what was written.
+        self.meta.invalidate(key);
 
         Ok(())
     }
 
+    pub fn get_pci_info(&self) -> DeviceResult<sysfs::pci::PciInfo> {
+        let busname = self.get(sysfs::npu_mgmt::BUSNAME)?;
+        sysfs::pci::read_pci_info(&self.sys_root, &busname)
+    }
+
+    pub fn get_aer_counters(&self) -> DeviceResult<sysfs::pci::AerCounters> {
+        let busname = self.get(sysfs::npu_mgmt::BUSNAME)?;
+        sysfs::pci::read_aer_counters(&self.sys_root, &busname)
+    }
+
+    pub fn get_local_cpus(&self) -> DeviceResult<Vec<usize>> {
+        let busname = self.get(sysfs::npu_mgmt::BUSNAME)?;
+        Ok(sysfs::pci::local_cpus::read_local_cpus(
+            &self.sys_root,
+            &busname,
+        )?)
+    }
+
     pub fn get_numa_node(&self) -> DeviceResult<NumaNode> {
-        if let Some(node) = *self.numa_node.borrow() {
-            return Ok(node);
+        if let Some(node) = self.numa_node.get() {
+            return Ok(*node);
         }
 
         let busname = self.get(sysfs::npu_mgmt::BUSNAME)?;
@@ -321,17 +685,99 @@ impl DeviceInfo {
             )));
         };
 
-        *self.numa_node.borrow_mut() = Some(node);
-        Ok(node)
+        // Another thread may have initialized it in the meantime, but the value is the same.
+        Ok(*self.numa_node.get_or_init(|| node))
     }
 }
 
-#[derive(Debug, Eq, PartialEq)]
+/// Metadata of a device, which caches the values of mgmt files read so far.
+///
+/// The cache is guarded by a `RwLock` so that [`Device`] can be shared across threads.
+#[derive(Debug)]
 pub(crate) struct DeviceMetadata {
     pub(crate) arch: Arch,
-    pub(crate) map: RefCell<HashMap<&'static str, String>>,
+    map: RwLock<HashMap<&'static str, CachedValue>>,
+    /// Incremented whenever cached values are dropped, so that a value read before that is
+    /// not cached afterwards
+    generation: AtomicU64,
 }
 
+#[derive(Debug)]
+struct CachedValue {
+    value: String,
+    read_at: Instant,
+}
+
+impl CachedValue {
+    fn new(value: String) -> Self {
+        Self {
+            value,
+            read_at: Instant::now(),
+        }
+    }
+}
+
+impl DeviceMetadata {
+    pub(crate) fn cached(&self, key: &str, policy: CachePolicy) -> Option<String> {
+        // A poisoned lock is harmless here since the map only holds plain strings.
+        let map = self.map.read().unwrap_or_else(PoisonError::into_inner);
+        let cached = map.get(key)?;
+        match policy {
+            CachePolicy::Never => None,
+            CachePolicy::Ttl(ttl) if cached.read_at.elapsed() >= ttl => None,
+            _ => Some(cached.value.clone()),
+        }
+    }
+
+    /// Returns the generation to pass to [`cache`][Self::cache] a value read from now on.
+    pub(crate) fn generation(&self) -> u64 {
+        self.generation.load(atomic::Ordering::Acquire)
+    }
+
+    /// Caches a value read since `generation`, unless cached values have been dropped in the
+    /// meantime (e.g., by a concurrent write), in which case the value may be stale already.
+    pub(crate) fn cache(&self, key: &'static str, value: String, generation: u64) {
+        let mut map = self.map.write().unwrap_or_else(PoisonError::into_inner);
+        if self.generation.load(atomic::Ordering::Acquire) == generation {
+            map.insert(key, CachedValue::new(value));
+        }
+    }
+
+    pub(crate) fn invalidate(&self, key: &str) {
+        let mut map = self.map.write().unwrap_or_else(PoisonError::into_inner);
+        self.generation.fetch_add(1, atomic::Ordering::AcqRel);
+        map.remove(key);
+    }
+
+    pub(crate) fn retain<F: Fn(&str) -> bool>(&self, f: F) {
+        let mut map = self.map.write().unwrap_or_else(PoisonError::into_inner);
+        self.generation.fetch_add(1, atomic::Ordering::AcqRel);
+        map.retain(|key, _| f(key));
+    }
+}
+
+/// Compares the architecture and the attributes identifying the device, which are read when
+/// the device is listed and never dropped from the cache.
+impl PartialEq for DeviceMetadata {
+    fn eq(&self, other: &Self) -> bool {
+        use sysfs::npu_mgmt::{BUSNAME, DEV};
+
+        if std::ptr::eq(self, other) {
+            return true;
+        }
+
+        let map = self.map.read().unwrap_or_else(PoisonError::into_inner);
+        let other_map = other.map.read().unwrap_or_else(PoisonError::into_inner);
+        self.arch == other.arch
+            && [BUSNAME, DEV].iter().all(|key| {
+                map.get(key).map(|cached| &cached.value)
+                    == other_map.get(key).map(|cached| &cached.value)
+            })
+    }
+}
+
+impl Eq for DeviceMetadata {}
+
 impl TryFrom<HashMap<&'static str, String>> for DeviceMetadata {
     type Error = DeviceError;
 
@@ -345,15 +791,32 @@ impl TryFrom<HashMap<&'static str, String>> for DeviceMetadata {
             arch: device_type.clone(),
         })?;
 
+        let map = map
+            .into_iter()
+            .map(|(key, value)| (key, CachedValue::new(value)))
+            .collect();
+
         Ok(Self {
             arch,
-            map: RefCell::new(map),
+            map: RwLock::new(map),
+            generation: AtomicU64::new(0),
         })
     }
 }
 
 /// Enum for NPU core status.
+///
+/// With the `serde` feature, it is serialized as `{"status": "available"}`,
+/// `{"status": "occupied", "device_file": "npu0pe0"}` or `{"status": "unavailable"}`.
+///
+/// Processes holding the device file of an occupied core are identified by
+/// [`Device::get_core_processes`].
 #[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(
+    feature = "serde",
+    serde(tag = "status", content = "device_file", rename_all = "lowercase")
+)]
 pub enum CoreStatus {
     Available,
     Occupied(String),
@@ -372,7 +835,12 @@ impl Display for CoreStatus {
 
 pub(crate) type CoreIdx = u8;
 
+/// A range of cores which a device file occupies.
+///
+/// With the `serde` feature, it is serialized as `"all"` or `{"range": [0, 1]}`.
 #[derive(Debug, Eq, PartialEq, Clone, Copy)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 pub enum CoreRange {
     All,
     Range((u8, u8)),
@@ -429,7 +897,12 @@ impl TryFrom<(u8, u8)> for CoreRange {
 }
 
 /// An abstraction for a device file and its mode.
+///
+/// With the `serde` feature, it is serialized as
+/// `{"device_index": 0, "core_range": {"range": [0, 1]}, "path": "/dev/npu0pe0-1", "mode": "fusion"}`.
+/// It is deserialized by parsing the path, and the other fields must agree with it.
 #[derive(Debug, Eq, PartialEq, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
 pub struct DeviceFile {
     pub(crate) device_index: u8,
     pub(crate) core_range: CoreRange,
@@ -475,13 +948,40 @@ impl DeviceFile {
     }
 }
 
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceFile {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        use serde::de::Error;
+
+        #[derive(serde::Deserialize)]
+        struct Fields {
+            device_index: u8,
+            core_range: CoreRange,
+            path: PathBuf,
+            mode: DeviceMode,
+        }
+
+        let fields = Fields::deserialize(deserializer)?;
+        let dev_file = DeviceFile::try_from(&fields.path).map_err(D::Error::custom)?;
+        if (fields.device_index, fields.core_range, fields.mode)
+            != (dev_file.device_index, dev_file.core_range, dev_file.mode)
+        {
+            return Err(D::Error::custom(format!(
+                "device file {} does not agree with its path",
+                dev_file
+            )));
+        }
+        Ok(dev_file)
+    }
+}
+
 impl TryFrom<&PathBuf> for DeviceFile {
     type Error = DeviceError;
 
     fn try_from(path: &PathBuf) -> Result<Self, Self::Error> {
         let file_name = path
             .file_name()
-            .expect("not a file")
+            .ok_or_else(|| DeviceError::unrecognized_file(path.to_string_lossy()))?
             .to_string_lossy()
             .to_string();
 
@@ -507,7 +1007,11 @@ impl TryFrom<&PathBuf> for DeviceFile {
 }
 
 /// Enum for NPU's operating mode.
+///
+/// With the `serde` feature, it is serialized as `"single"`, `"fusion"` or `"multicore"`.
 #[derive(Debug, Eq, PartialEq, Copy, Clone, enum_utils::FromStr)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
 #[enumeration(case_insensitive)]
 pub enum DeviceMode {
     Single,
@@ -518,6 +1022,7 @@ pub enum DeviceMode {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::fake::test_lister;
     use crate::sysfs::npu_mgmt::read_mgmt_files;
 
     #[test]
@@ -613,19 +1118,18 @@ mod tests {
     #[test]
     fn test_lazy_read_sysfs() -> DeviceResult<()> {
         let device_meta = DeviceMetadata::try_from(read_mgmt_files("test_data/test-0/sys", 0)?)?;
-        let device_info = DeviceInfo::new(
+        let mut device_info = DeviceInfo::new(
             0,
             PathBuf::from("test_data/test-0/dev"),
             PathBuf::from("test_data/test-0/sys"),
             device_meta,
         );
+        device_info.set_cache_policy(CachePolicy::Forever);
 
         assert_eq!(
             device_info
                 .meta
-                .map
-                .borrow_mut()
-                .get(sysfs::npu_mgmt::PERFORMANCE_MODE),
+                .cached(sysfs::npu_mgmt::PERFORMANCE_MODE, CachePolicy::Forever),
             None
         );
         assert_eq!(
@@ -635,11 +1139,125 @@ mod tests {
         assert_eq!(
             device_info
                 .meta
-                .map
-                .borrow_mut()
-                .get(sysfs::npu_mgmt::PERFORMANCE_MODE),
-            Some(&String::from("4 (FULL 1)"))
+                .cached(sysfs::npu_mgmt::PERFORMANCE_MODE, CachePolicy::Forever),
+            Some(String::from("4 (FULL 1)"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_cache_policy() -> DeviceResult<()> {
+        use sysfs::npu_mgmt::{BUSNAME, PERFORMANCE_MODE};
+
+        let new_device_info = |policy| -> DeviceResult<DeviceInfo> {
+            let device_meta =
+                DeviceMetadata::try_from(read_mgmt_files("test_data/test-0/sys", 0)?)?;
+            let mut device_info = DeviceInfo::new(
+                0,
+                PathBuf::from("test_data/test-0/dev"),
+                PathBuf::from("test_data/test-0/sys"),
+                device_meta,
+            );
+            device_info.set_cache_policy(policy);
+            Ok(device_info)
+        };
+        let is_cached = |device_info: &DeviceInfo, key| {
+            device_info.meta.cached(key, CachePolicy::Forever).is_some()
+        };
+
+        // Volatile attributes are never cached, but static ones are.
+        let device_info = new_device_info(CachePolicy::Never)?;
+        assert_eq!(device_info.get(PERFORMANCE_MODE)?, "4 (FULL 1)");
+        assert!(!is_cached(&device_info, PERFORMANCE_MODE));
+        assert_eq!(device_info.get(BUSNAME)?, "0000:6d:00.0");
+        assert!(is_cached(&device_info, BUSNAME));
+
+        // Expired values are not used
+        let device_info = new_device_info(CachePolicy::Ttl(Duration::ZERO))?;
+        device_info.get(PERFORMANCE_MODE)?;
+        assert!(is_cached(&device_info, PERFORMANCE_MODE));
+        assert_eq!(
+            device_info
+                .meta
+                .cached(PERFORMANCE_MODE, device_info.cache_policy()),
+            None
+        );
+        let device_info = new_device_info(CachePolicy::Ttl(Duration::from_secs(3600)))?;
+        device_info.get(PERFORMANCE_MODE)?;
+        assert!(device_info
+            .meta
+            .cached(PERFORMANCE_MODE, device_info.cache_policy())
+            .is_some());
+
+        // refresh() drops only volatile attributes
+        let device_info = new_device_info(CachePolicy::Forever)?;
+        device_info.get(PERFORMANCE_MODE)?;
+        assert!(is_cached(&device_info, PERFORMANCE_MODE));
+        device_info.refresh();
+        assert!(!is_cached(&device_info, PERFORMANCE_MODE));
+        assert!(is_cached(&device_info, BUSNAME));
+
+        // Values cached so far do not matter to equality.
+        let other = new_device_info(CachePolicy::Forever)?;
+        device_info.get(PERFORMANCE_MODE)?;
+        let _ = device_info.get_numa_node();
+        assert_eq!(device_info, other);
+
+        // A value read before a concurrent write is not cached.
+        let generation = device_info.meta.generation();
+        device_info.meta.invalidate(PERFORMANCE_MODE);
+        device_info
+            .meta
+            .cache(PERFORMANCE_MODE, String::from("stale"), generation);
+        assert!(!is_cached(&device_info, PERFORMANCE_MODE));
+
+        // Volatile attributes are not cached by default.
+        assert_eq!(CachePolicy::default(), CachePolicy::Never);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_mgmt_accessors() -> DeviceResult<()> {
+        let devices = test_lister().list_devices().await?;
+        let device = &devices[0];
+
+        assert_eq!(
+            device.device_uuid()?,
+            Uuid::from_str("a76aad68-6855-40b1-9e86-d080852d1c80").unwrap()
         );
+        assert_eq!(device.soc_uid()?, "0x8D3A2B45C6E7F0910");
+        assert_eq!(device.soc_rev()?, "A0");
+        assert_eq!(device.evb_rev()?, "1.0");
+        assert_eq!(device.driver_version()?.to_string(), "1.2.0, 0e7b5e6");
+        assert_eq!(device.platform_type()?, PlatformType::FuriosaAI);
+        assert_eq!(device.device_state()?, DeviceState::Idle);
+        assert_eq!(device.cur_pe_ids()?, Vec::<CoreIdx>::new());
+
+        let freqs = device.clock_frequency()?;
+        assert_eq!(freqs.len(), 5);
+        assert_eq!(freqs[0].name(), "ne tensor");
+        assert_eq!(freqs[0].value(), 2000);
+        assert_eq!(freqs[4].name(), "pcie");
+
+        // npu1 does not expose the attributes
+        assert!(devices[1].device_uuid().is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_ctrl_readback() -> DeviceResult<()> {
+        use sysfs::npu_mgmt::{DtmPolicy, PerfLevel, PerfMode, Toggle};
+
+        let devices = test_lister().list_devices().await?;
+        let device = &devices[0];
+
+        assert_eq!(device.performance_mode()?, PerfMode::Full1);
+        assert_eq!(device.performance_level()?, PerfLevel::Level15);
+        assert_eq!(device.ne_dtm_policy()?, DtmPolicy::OnDemand);
+        assert_eq!(device.ne_clock()?, Toggle::Enable);
 
         Ok(())
     }
@@ -655,9 +1273,9 @@ mod tests {
             device_meta,
         );
 
-        assert_eq!(*device_info.numa_node.borrow(), None);
+        assert_eq!(device_info.numa_node.get(), None);
         assert_eq!(device_info.get_numa_node()?, NumaNode::Id(0));
-        assert_eq!(*device_info.numa_node.borrow(), Some(NumaNode::Id(0)));
+        assert_eq!(device_info.numa_node.get(), Some(&NumaNode::Id(0)));
 
         // npu1 => numa node unsupported
         let device_meta = DeviceMetadata::try_from(read_mgmt_files("test_data/test-0/sys", 1)?)?;
@@ -668,9 +1286,222 @@ mod tests {
             device_meta,
         );
 
-        assert_eq!(*device_info.numa_node.borrow(), None);
+        assert_eq!(device_info.numa_node.get(), None);
         assert_eq!(device_info.get_numa_node()?, NumaNode::UnSupported);
-        assert_eq!(*device_info.numa_node.borrow(), Some(NumaNode::UnSupported));
+        assert_eq!(device_info.numa_node.get(), Some(&NumaNode::UnSupported));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pci_info() -> DeviceResult<()> {
+        let devices = test_lister().list_devices().await?;
+
+        let info = devices[0].pci_info()?;
+        assert_eq!((info.vendor_id(), info.device_id()), (0x1ed2, 0x0000));
+        assert_eq!(
+            (info.subsystem_vendor_id(), info.subsystem_device_id()),
+            (0x1ed2, 0x0000)
+        );
+        assert_eq!((info.class(), info.revision()), (0x120000, 1));
+        assert_eq!(info.current_link().unwrap().to_string(), "16.0 GT/s x8");
+        assert_eq!(info.current_link(), info.max_link());
+        assert!(!info.is_link_degraded());
+        assert_eq!(info.iommu_group(), Some(27));
+        assert_eq!(info.driver(), Some("furiosa"));
+        assert!(info.is_enabled());
+
+        let info = devices[1].pci_info()?;
+        assert_eq!(info.current_link().unwrap().to_string(), "8.0 GT/s x4");
+        assert_eq!(info.max_link().unwrap().to_string(), "16.0 GT/s x8");
+        assert!(info.is_link_degraded());
+        assert_eq!(info.iommu_group(), None);
+        assert_eq!(info.driver(), None);
+        assert!(!info.is_enabled());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_aer_counters() -> DeviceResult<()> {
+        use sysfs::pci::AerSeverity;
+
+        let devices = test_lister().list_devices().await?;
+
+        let counters = devices[0].aer_counters()?;
+        assert_eq!(counters.get(AerSeverity::Correctable, "RxErr"), Some(3));
+        assert_eq!(counters.total(AerSeverity::Correctable), 4);
+        assert_eq!(counters.total(AerSeverity::NonFatal), 0);
+        assert_eq!(counters.get(AerSeverity::Fatal, "CmpltTO"), Some(0));
+        // Without AER
+        assert!(devices[1].aer_counters().is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_local_cpus() -> DeviceResult<()> {
+        let devices = test_lister().list_devices().await?;
+        assert_eq!(devices[0].local_cpus()?, (0..16).collect::<Vec<_>>());
+        // From the mask without local_cpulist
+        assert_eq!(devices[1].local_cpus()?, (0..48).collect::<Vec<_>>());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_pin_current_thread() -> Result<(), Box<dyn std::error::Error>> {
+        let system = crate::fake::FakeSystem::new()?;
+        system.add_device(0, 0)?;
+        system.add_device(1, 0)?;
+        let allowed = affinity::thread_affinity()?;
+        system.set_local_cpus(0, &allowed[0].to_string())?;
+        system.set_local_cpus(1, "")?;
+        let devices = system.lister().list_devices().await?;
+
+        // On another thread not to affect other tests
+        std::thread::spawn(move || -> DeviceResult<()> {
+            devices[0].pin_current_thread()?;
+            assert_eq!(affinity::thread_affinity()?, &allowed[..1]);
+            assert!(matches!(
+                devices[1].pin_current_thread(),
+                Err(DeviceError::UnexpectedValue { .. })
+            ));
+            affinity::set_thread_affinity(0, &allowed)?;
+            Ok(())
+        })
+        .join()
+        .unwrap()?;
+        Ok(())
+    }
+
+    #[test]
+    fn test_send_sync() {
+        fn assert_send_sync<T: Send + Sync>() {}
+
+        assert_send_sync::<Device>();
+        assert_send_sync::<DeviceInfo>();
+        assert_send_sync::<DeviceFile>();
+    }
+
+    #[test]
+    fn test_concurrent_read() -> DeviceResult<()> {
+        use std::sync::Arc;
+
+        let device_meta = DeviceMetadata::try_from(read_mgmt_files("test_data/test-0/sys", 0)?)?;
+        let device_info = Arc::new(DeviceInfo::new(
+            0,
+            PathBuf::from("test_data/test-0/dev"),
+            PathBuf::from("test_data/test-0/sys"),
+            device_meta,
+        ));
+
+        let handles: Vec<_> = (0..8)
+            .map(|_| {
+                let device_info = Arc::clone(&device_info);
+                std::thread::spawn(move || {
+                    (
+                        device_info.get(sysfs::npu_mgmt::PERFORMANCE_MODE).ok(),
+                        device_info.get_numa_node().ok(),
+                    )
+                })
+            })
+            .collect();
+
+        for handle in handles {
+            assert_eq!(
+                handle.join().unwrap(),
+                (Some(String::from("4 (FULL 1)")), Some(NumaNode::Id(0)))
+            );
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[tokio::test]
+    async fn test_serde_round_trip() -> Result<(), Box<dyn std::error::Error>> {
+        use serde_json::json;
+
+        let dev_file = DeviceFile::try_from(&PathBuf::from("/dev/npu0pe0-1"))?;
+        let value = serde_json::to_value(&dev_file)?;
+        assert_eq!(
+            value,
+            json!({
+                "device_index": 0,
+                "core_range": {"range": [0, 1]},
+                "path": "/dev/npu0pe0-1",
+                "mode": "fusion",
+            })
+        );
+        assert_eq!(serde_json::from_value::<DeviceFile>(value)?, dev_file);
+
+        let dev_file = DeviceFile::try_from(&PathBuf::from("/dev/npu1"))?;
+        let value = serde_json::to_value(&dev_file)?;
+        assert_eq!(value["core_range"], json!("all"));
+        assert_eq!(value["mode"], json!("multicore"));
+        assert_eq!(serde_json::from_value::<DeviceFile>(value)?, dev_file);
+
+        // Fields disagreeing with the path, or an unrecognized path, are rejected.
+        for value in [
+            json!({"device_index": 3, "core_range": "all", "path": "/dev/npu1", "mode": "multicore"}),
+            json!({"device_index": 1, "core_range": "all", "path": "/dev/npu1", "mode": "single"}),
+            json!({"device_index": 1, "core_range": "all", "path": "/dev/sda", "mode": "multicore"}),
+            json!({"device_index": 1, "core_range": "all", "path": "/", "mode": "multicore"}),
+        ] {
+            assert!(serde_json::from_value::<DeviceFile>(value).is_err());
+        }
+
+        for status in [
+            CoreStatus::Available,
+            CoreStatus::Occupied(String::from("npu0pe0")),
+            CoreStatus::Unavailable,
+        ] {
+            let value = serde_json::to_value(&status)?;
+            assert_eq!(serde_json::from_value::<CoreStatus>(value)?, status);
+        }
+        assert_eq!(
+            serde_json::to_value(CoreStatus::Occupied(String::from("npu0pe0")))?,
+            json!({"status": "occupied", "device_file": "npu0pe0"})
+        );
+
+        let process = ProcessInfo {
+            pid: 1234,
+            cmdline: vec![String::from("python"), String::from("serve.py")],
+            uid: 1000,
+            cgroup: Some(String::from("/docker/abc")),
+        };
+        let value = serde_json::to_value(&process)?;
+        assert_eq!(serde_json::from_value::<ProcessInfo>(value)?, process);
+
+        for node in [NumaNode::Id(1), NumaNode::UnSupported] {
+            let value = serde_json::to_value(node)?;
+            assert_eq!(serde_json::from_value::<NumaNode>(value)?, node);
+        }
+        assert_eq!(serde_json::to_value(NumaNode::UnSupported)?, json!(null));
+
+        let devices = test_lister().list_devices().await?;
+        let value = serde_json::to_value(&devices[1])?;
+        assert_eq!(value["name"], json!("npu1"));
+        assert_eq!(value["arch"], json!("warboy"));
+        assert_eq!(value["busname"], json!("0000:ff:00.0"));
+        assert_eq!(value["numa_node"], json!(null));
+        assert_eq!(value["cores"], json!([0, 1]));
+        assert_eq!(value["dev_files"].as_array().map(Vec::len), Some(4));
+
+        // The NUMA node is null as well if it cannot be read.
+        let system = crate::fake::FakeSystem::new()?;
+        system.add_device(0, 0)?;
+        std::fs::remove_file(
+            system
+                .sysfs()
+                .join("bus/pci/devices")
+                .join(crate::fake::FakeSystem::busname(0))
+                .join("numa_node"),
+        )?;
+        let devices = system.lister().list_devices().await?;
+        assert!(devices[0].numa_node().is_err());
+        let value = serde_json::to_value(&devices[0])?;
+        assert_eq!(value["numa_node"], json!(null));
+        assert_eq!(value["name"], json!("npu0"));
 
         Ok(())
     }
@@ -24,6 +24,10 @@ pub enum DeviceError {
     IncompatibleDriver { cause: String },
     #[error("HwmonError: [npu{device_index}] {cause}")]
     HwmonError { device_index: u8, cause: HwmonError },
+    #[error("Devices for {config} not found: {reason}")]
+    NumaUnsatisfied { config: String, reason: String },
+    #[error("Timed out waiting for devices for {config}")]
+    WaitTimedOut { config: String },
     #[error("Unexpected value: {message}")]
     UnexpectedValue { message: String },
 }
@@ -61,11 +65,73 @@ impl DeviceError {
         }
     }
 
+    pub(crate) fn zero_period() -> DeviceError {
+        IoError {
+            cause: io::Error::new(io::ErrorKind::InvalidInput, "period must be non-zero"),
+        }
+    }
+
+    pub(crate) fn runtime_dir_unavailable<P: Display>(
+        runtime_dir: P,
+        cause: io::Error,
+    ) -> DeviceError {
+        io::Error::new(
+            cause.kind(),
+            format!(
+                "cannot use {} for lock files ({}); set DeviceLister::runtime_dir to a writable directory",
+                runtime_dir, cause
+            ),
+        )
+        .into()
+    }
+
     pub(crate) fn unexpected_value<S: ToString>(message: S) -> DeviceError {
         UnexpectedValue {
             message: message.to_string(),
         }
     }
+
+    /// Returns an equivalent error, e.g., to hand one error to many receivers.
+    pub(crate) fn duplicate(&self) -> DeviceError {
+        match self {
+            Self::DeviceNotFound { name } => Self::DeviceNotFound { name: name.clone() },
+            Self::IoError { cause } => Self::IoError {
+                cause: duplicate_io_error(cause),
+            },
+            Self::PermissionDenied { cause } => Self::PermissionDenied {
+                cause: duplicate_io_error(cause),
+            },
+            Self::UnknownArch { arch } => Self::UnknownArch { arch: arch.clone() },
+            Self::IncompatibleDriver { cause } => Self::IncompatibleDriver {
+                cause: cause.clone(),
+            },
+            Self::HwmonError {
+                device_index,
+                cause,
+            } => Self::HwmonError {
+                device_index: *device_index,
+                cause: cause.duplicate(),
+            },
+            Self::NumaUnsatisfied { config, reason } => Self::NumaUnsatisfied {
+                config: config.clone(),
+                reason: reason.clone(),
+            },
+            Self::WaitTimedOut { config } => Self::WaitTimedOut {
+                config: config.clone(),
+            },
+            Self::UnexpectedValue { message } => Self::UnexpectedValue {
+                message: message.clone(),
+            },
+        }
+    }
+}
+
+/// Returns an error of the same OS error code, or of the same kind and message.
+pub(crate) fn duplicate_io_error(e: &io::Error) -> io::Error {
+    match e.raw_os_error() {
+        Some(code) => io::Error::from_raw_os_error(code),
+        None => io::Error::new(e.kind(), e.to_string()),
+    }
 }
 
 impl From<io::Error> for DeviceError {
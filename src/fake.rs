@@ -0,0 +1,179 @@
+//! A fake system with devfs, sysfs and procfs on a temporary directory, whose devices and
+//! NUMA topology are built for each test.
+
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io;
+use std::os::unix::fs::symlink;
+use std::path::{Path, PathBuf};
+
+use tempfile::TempDir;
+
+use crate::device::{DeviceInfo, DeviceMetadata};
+use crate::hwmon::{Fetcher, SensorContainer};
+use crate::list::collect_devices;
+use crate::sysfs::npu_mgmt::DEVICE_TYPE;
+use crate::{lock, Device, DeviceLister};
+
+pub(crate) const CONTAINER_ID: &str =
+    "4f8b9c1e2d3a4b5c6d7e8f90a1b2c3d4e5f60718293a4b5c6d7e8f9a0b1c2d3e";
+
+pub(crate) struct FakeSystem {
+    root: TempDir,
+}
+
+impl FakeSystem {
+    pub(crate) fn new() -> io::Result<Self> {
+        let root = tempfile::tempdir()?;
+        for dir in ["dev", "sys", "proc"] {
+            fs::create_dir(root.path().join(dir))?;
+        }
+        Ok(Self { root })
+    }
+
+    pub(crate) fn devfs(&self) -> PathBuf {
+        self.root.path().join("dev")
+    }
+
+    pub(crate) fn sysfs(&self) -> PathBuf {
+        self.root.path().join("sys")
+    }
+
+    pub(crate) fn procfs(&self) -> PathBuf {
+        self.root.path().join("proc")
+    }
+
+    /// Returns a lister of the fake system, which accepts regular files as device files.
+    pub(crate) fn lister(&self) -> DeviceLister {
+        DeviceLister::new()
+            .devfs(self.devfs())
+            .sysfs(self.sysfs())
+            .procfs(self.procfs())
+            .allow_regular_files(true)
+    }
+
+    pub(crate) fn busname(idx: u8) -> String {
+        format!("0000:{:02x}:00.0", 0x10 + idx)
+    }
+
+    /// Adds a Warboy with 2 cores on the NUMA node (-1 if unsupported).
+    pub(crate) fn add_device(&self, idx: u8, numa_node: i32) -> io::Result<()> {
+        for name in ["", "pe0", "pe1", "pe0-1"] {
+            fs::write(self.devfs().join(format!("npu{}{}", idx, name)), "")?;
+        }
+
+        let busname = Self::busname(idx);
+        let mgmt = self.sysfs().join(format!("class/npu_mgmt/npu{}_mgmt", idx));
+        fs::create_dir_all(&mgmt)?;
+        for (file, contents) in [
+            ("platform_type", "FuriosaAI"),
+            ("device_type", "Warboy"),
+            ("busname", busname.as_str()),
+            ("dev", ""),
+            ("alive", "1"),
+            ("heartbeat", "0"),
+            ("device_state", "0 (IDLE)"),
+            ("cur_pe_ids", ""),
+        ] {
+            fs::write(mgmt.join(file), format!("{}\n", contents))?;
+        }
+
+        let pci = self.sysfs().join(format!("bus/pci/devices/{}", busname));
+        fs::create_dir_all(pci.join("hwmon"))?;
+        fs::write(pci.join("numa_node"), format!("{}\n", numa_node))
+    }
+
+    /// Sets the CPUs close to the device (e.g., `0-3`).
+    pub(crate) fn set_local_cpus(&self, idx: u8, cpulist: &str) -> io::Result<()> {
+        let pci = self
+            .sysfs()
+            .join(format!("bus/pci/devices/{}", Self::busname(idx)));
+        fs::write(pci.join("local_cpulist"), format!("{}\n", cpulist))
+    }
+
+    /// Adds a NUMA node with the CPUs (e.g., `0-3`).
+    pub(crate) fn add_node(&self, node: usize, cpulist: &str) -> io::Result<()> {
+        let dir = self
+            .sysfs()
+            .join(format!("devices/system/node/node{}", node));
+        fs::create_dir_all(&dir)?;
+        fs::write(dir.join("cpulist"), format!("{}\n", cpulist))
+    }
+
+    /// Sets the CPU which the calling thread runs on.
+    pub(crate) fn set_current_cpu(&self, cpu: usize) -> io::Result<()> {
+        let dir = self.procfs().join("thread-self");
+        fs::create_dir_all(&dir)?;
+        let fields = ["0"; 34].join(" ");
+        fs::write(
+            dir.join("stat"),
+            format!("1 (test) R {} 17 {} 0 0\n", fields, cpu),
+        )
+    }
+}
+
+/// Returns a lister of `test_data/test-0`, which has 2 Warboys whose device files are regular
+/// files.
+pub(crate) fn test_lister() -> DeviceLister {
+    DeviceLister::new()
+        .devfs("test_data/test-0/dev")
+        .sysfs("test_data/test-0/sys")
+        .allow_regular_files(true)
+}
+
+/// Builds a procfs where a process holds `targets` open.
+pub(crate) fn fake_process<P: AsRef<Path>>(
+    proc_root: &Path,
+    pid: u32,
+    targets: &[P],
+) -> io::Result<()> {
+    let proc_dir = proc_root.join(pid.to_string());
+    fs::create_dir_all(proc_dir.join("fd"))?;
+    for (fd, target) in targets.iter().enumerate() {
+        symlink(
+            fs::canonicalize(target)?,
+            proc_dir.join("fd").join(fd.to_string()),
+        )?;
+    }
+    fs::write(proc_dir.join("cmdline"), "python\0serve.py\0")?;
+    fs::write(
+        proc_dir.join("status"),
+        "Name:\tpython\nUid:\t1000\t1000\t1000\t1000\nGid:\t1000\t1000\t1000\t1000\n",
+    )?;
+    fs::write(
+        proc_dir.join("cgroup"),
+        format!("0::/system.slice/docker-{}.scope\n", CONTAINER_ID),
+    )?;
+    Ok(())
+}
+
+/// Creates a lock file in `lock_dir` and takes its lock, as a cooperating user of the device
+/// file would, until the returned file is dropped.
+pub(crate) fn lock_device_file(lock_dir: &Path, device_file: &str) -> io::Result<File> {
+    let lock_file = File::create(lock_dir.join(format!("{}.lock", device_file)))?;
+    assert!(
+        lock::try_lock_exclusive(&lock_file)?,
+        "{} is already locked",
+        device_file
+    );
+    Ok(lock_file)
+}
+
+/// Builds a Warboy with the device files named after `npu{idx}` (e.g., `"pe0-1"`), without
+/// files behind it, for tests which only look at the cores and device files.
+pub(crate) fn fake_device(idx: u8, files: &[&str]) -> Device {
+    let meta = DeviceMetadata::try_from(HashMap::from([(DEVICE_TYPE, String::from("Warboy"))]))
+        .expect("valid metadata");
+    let device_info = DeviceInfo::new(idx, PathBuf::from("/dev"), PathBuf::from("/sys"), meta);
+    let fetcher = Fetcher {
+        device_index: idx,
+        sysfs: PathBuf::from("/sys"),
+        busname: FakeSystem::busname(idx),
+        sensor_container: SensorContainer(HashMap::new()),
+    };
+    let paths = files
+        .iter()
+        .map(|name| PathBuf::from(format!("/dev/npu{}{}", idx, name)))
+        .collect();
+    collect_devices(device_info, fetcher, paths).expect("valid device files")
+}
@@ -11,14 +11,14 @@ use nom::sequence::{delimited, preceded, separated_pair};
 use nom::Parser;
 
 use crate::arch::Arch;
-use crate::device::{CoreIdx, CoreRange, CoreStatus, Device, DeviceFile, DeviceMode};
-use crate::error::DeviceResult;
+use crate::device::{CoreIdx, CoreRange, CoreStatus, Device, DeviceFile, DeviceMode, NumaNode};
+use crate::error::{DeviceError, DeviceResult};
 
 /// Describes a required set of devices for [`find_devices`][crate::find_devices].
 ///
 /// # Examples
 /// ```rust
-/// use furiosa_device::DeviceConfig;
+/// use furiosa_device::{AllocationStrategy, DeviceConfig, NumaPolicy};
 ///
 /// // 1 core
 /// DeviceConfig::warboy().build();
@@ -28,8 +28,20 @@ use crate::error::DeviceResult;
 ///
 /// // Fused 2 cores x 2
 /// DeviceConfig::warboy().fused().count(2);
+///
+/// // 1 core x 2, all on the same NUMA node
+/// DeviceConfig::warboy().count(2).numa(NumaPolicy::SameNode);
+///
+/// // 1 core x 2, keeping fused cores available for others
+/// DeviceConfig::warboy()
+///     .single()
+///     .count(2)
+///     .strategy(AllocationStrategy::MinFragmentation);
 /// ```
 ///
+/// With the `serde` feature, it is serialized as its textual form
+/// (e.g., `"warboy(2)*4"`, `"warboy(1)*2@same"`, `"warboy(1)*2#best-fit"` or `"0:0-1"`).
+///
 /// See also [struct `Device`][`Device`].
 #[derive(Copy, Clone, Debug, Eq, PartialEq)]
 pub enum DeviceConfig {
@@ -38,14 +50,92 @@ pub enum DeviceConfig {
         device_id: u8,
         core_range: CoreRange,
     },
+    /// Build it with [`DeviceConfig::warboy`] or parse its textual form, since more fields may
+    /// be added.
+    #[non_exhaustive]
     Unnamed {
         arch: Arch,
         core_num: u8,
         mode: DeviceMode,
         count: u8,
+        numa: NumaPolicy,
+        strategy: AllocationStrategy,
     },
 }
 
+/// NUMA constraint on the devices found by an unnamed [`DeviceConfig`].
+///
+/// Its textual form follows the config after `@` (e.g., `"warboy(1)*2@same"`, `"warboy*1@local"`
+/// or `"warboy(2)*2@1"`).
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum NumaPolicy {
+    /// Devices on any NUMA nodes, in the order of device indices.
+    #[default]
+    Any,
+    /// Devices all on one NUMA node, preferring the local node (see
+    /// [`DeviceLister::local_node`][crate::DeviceLister::local_node]).
+    SameNode,
+    /// Devices on any NUMA nodes, taking those on the local node first.
+    PreferLocal,
+    /// Devices only on the given NUMA node.
+    Node(usize),
+}
+
+impl NumaPolicy {
+    pub(crate) fn uses_local_node(&self) -> bool {
+        matches!(self, Self::SameNode | Self::PreferLocal)
+    }
+}
+
+impl Display for NumaPolicy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Any => write!(f, "any"),
+            Self::SameNode => write!(f, "same"),
+            Self::PreferLocal => write!(f, "local"),
+            Self::Node(node) => write!(f, "{}", node),
+        }
+    }
+}
+
+/// How to choose among available device files for an unnamed [`DeviceConfig`].
+///
+/// Its textual form follows the config after `#` (e.g., `"warboy(1)*2#best-fit"` or
+/// `"warboy(2)*2@same#spread"`). The strategies other than [`FirstFit`][Self::FirstFit]
+/// search all combinations of available device files for the best one by their goals,
+/// preferring the device files in the order of devices among equally good ones. They also find
+/// enough device files whenever there are, which `FirstFit` may miss if fused device files of
+/// a device partially overlap.
+#[derive(Copy, Clone, Debug, Default, Eq, PartialEq)]
+pub enum AllocationStrategy {
+    /// The first available device files in the order of devices, taken one at a time.
+    #[default]
+    FirstFit,
+    /// Device files leaving the fewest available cores on the devices they are on, filling up
+    /// devices in use before taking free ones.
+    BestFit,
+    /// Device files spread over as many devices as possible, and as evenly as possible.
+    Spread,
+    /// Device files packed on as few devices as possible, and then as [`BestFit`][Self::BestFit].
+    Pack,
+    /// Device files which make the fewest device files unavailable, including themselves, to
+    /// keep fused device files available for later configs. Ties are broken as
+    /// [`BestFit`][Self::BestFit].
+    MinFragmentation,
+}
+
+impl Display for AllocationStrategy {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::FirstFit => write!(f, "first-fit"),
+            Self::BestFit => write!(f, "best-fit"),
+            Self::Spread => write!(f, "spread"),
+            Self::Pack => write!(f, "pack"),
+            Self::MinFragmentation => write!(f, "min-fragmentation"),
+        }
+    }
+}
+
 impl DeviceConfig {
     /// Returns a builder associated with Warboy NPUs.
     pub fn warboy() -> DeviceConfigBuilder<Arch, NotDetermined, NotDetermined> {
@@ -56,6 +146,69 @@ impl DeviceConfig {
         }
     }
 
+    /// Sets the NUMA constraint of an unnamed config. A named config, which designates a
+    /// device itself, is returned as is.
+    pub fn numa(self, policy: NumaPolicy) -> Self {
+        match self {
+            Self::Named { .. } => self,
+            Self::Unnamed {
+                arch,
+                core_num,
+                mode,
+                count,
+                numa: _,
+                strategy,
+            } => Self::Unnamed {
+                arch,
+                core_num,
+                mode,
+                count,
+                numa: policy,
+                strategy,
+            },
+        }
+    }
+
+    /// Returns the NUMA constraint, which is always [`NumaPolicy::Any`] for named configs.
+    pub fn numa_policy(&self) -> NumaPolicy {
+        match self {
+            Self::Named { .. } => NumaPolicy::Any,
+            Self::Unnamed { numa, .. } => *numa,
+        }
+    }
+
+    /// Sets how to choose among available device files for an unnamed config. A named config,
+    /// which designates a device file itself, is returned as is.
+    pub fn strategy(self, strategy: AllocationStrategy) -> Self {
+        match self {
+            Self::Named { .. } => self,
+            Self::Unnamed {
+                arch,
+                core_num,
+                mode,
+                count,
+                numa,
+                strategy: _,
+            } => Self::Unnamed {
+                arch,
+                core_num,
+                mode,
+                count,
+                numa,
+                strategy,
+            },
+        }
+    }
+
+    /// Returns the allocation strategy, which is always [`AllocationStrategy::FirstFit`] for
+    /// named configs.
+    pub fn allocation_strategy(&self) -> AllocationStrategy {
+        match self {
+            Self::Named { .. } => AllocationStrategy::FirstFit,
+            Self::Unnamed { strategy, .. } => *strategy,
+        }
+    }
+
     pub(crate) fn fit(&self, arch: Arch, device_file: &DeviceFile) -> bool {
         match self {
             Self::Named {
@@ -69,6 +222,8 @@ impl DeviceConfig {
                 core_num: _,
                 mode,
                 count: _,
+                numa: _,
+                strategy: _,
             } => arch == *config_arch && device_file.mode() == *mode,
         }
     }
@@ -84,6 +239,8 @@ impl DeviceConfig {
                 core_num: _,
                 mode: _,
                 count,
+                numa: _,
+                strategy: _,
             } => *count,
         }
     }
@@ -123,16 +280,37 @@ impl FromStr for DeviceConfig {
                 })
             }
             Err(_) => {
-                // try parsing unnamed configs, from patterns e.g., "warboy*1" or "warboy(1)*2"
-                let (_, ((arch, mode), count)) = all_consuming(separated_pair(
-                    map_res(tag("warboy"), |s: &str| s.parse::<Arch>()).and(opt(delimited(
-                        tag("("),
-                        digit_to_u8(),
-                        tag(")"),
-                    ))),
-                    tag("*"),
-                    digit_to_u8(),
-                ))(s)?;
+                // try parsing unnamed configs, from patterns e.g., "warboy*1", "warboy(1)*2",
+                // "warboy(1)*2@same" or "warboy(1)*2@same#pack"
+                let (_, ((((arch, mode), count), numa), strategy)) =
+                    all_consuming(
+                        separated_pair(
+                            map_res(tag("warboy"), |s: &str| s.parse::<Arch>())
+                                .and(opt(delimited(tag("("), digit_to_u8(), tag(")")))),
+                            tag("*"),
+                            digit_to_u8(),
+                        )
+                        .and(opt(preceded(
+                            tag("@"),
+                            alt((
+                                map(tag("same"), |_| NumaPolicy::SameNode),
+                                map(tag("local"), |_| NumaPolicy::PreferLocal),
+                                map_res(digit1, |s: &str| s.parse().map(NumaPolicy::Node)),
+                            )),
+                        )))
+                        .and(opt(preceded(
+                            tag("#"),
+                            alt((
+                                map(tag("first-fit"), |_| AllocationStrategy::FirstFit),
+                                map(tag("best-fit"), |_| AllocationStrategy::BestFit),
+                                map(tag("spread"), |_| AllocationStrategy::Spread),
+                                map(tag("pack"), |_| AllocationStrategy::Pack),
+                                map(tag("min-fragmentation"), |_| {
+                                    AllocationStrategy::MinFragmentation
+                                }),
+                            )),
+                        ))),
+                    )(s)?;
                 let (core_num, mode) = match mode {
                     None => (0, DeviceMode::MultiCore),
                     Some(1) => (1, DeviceMode::Single),
@@ -145,6 +323,8 @@ impl FromStr for DeviceConfig {
                     core_num,
                     mode,
                     count,
+                    numa: numa.unwrap_or_default(),
+                    strategy: strategy.unwrap_or_default(),
                 })
             }
         }
@@ -174,17 +354,42 @@ impl Display for DeviceConfig {
                 core_num,
                 mode: _mode,
                 count,
+                numa,
+                strategy,
             } => {
                 if *core_num == 0 {
-                    write!(f, "{}*{}", arch, count)
+                    write!(f, "{}*{}", arch, count)?;
                 } else {
-                    write!(f, "{}({})*{}", arch, core_num, count)
+                    write!(f, "{}({})*{}", arch, core_num, count)?;
+                }
+                if *numa != NumaPolicy::Any {
+                    write!(f, "@{}", numa)?;
+                }
+                match strategy {
+                    AllocationStrategy::FirstFit => Ok(()),
+                    strategy => write!(f, "#{}", strategy),
                 }
             }
         }
     }
 }
 
+#[cfg(feature = "serde")]
+impl serde::Serialize for DeviceConfig {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for DeviceConfig {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse()
+            .map_err(|_| serde::de::Error::custom(format!("invalid device config: {}", s)))
+    }
+}
+
 pub struct NotDetermined;
 
 impl From<NotDetermined> for Arch {
@@ -266,10 +471,149 @@ where
             core_num,
             mode,
             count: u8::from(self.count),
+            numa: NumaPolicy::Any,
+            strategy: AllocationStrategy::FirstFit,
+        }
+    }
+}
+
+/// Device files found for a [`DeviceConfig`], along with why the other device files are not,
+/// returned by [`DeviceLister::find_devices_report`][crate::DeviceLister::find_devices_report].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct FindReport {
+    config: DeviceConfig,
+    picked: Vec<DeviceFile>,
+    satisfied: bool,
+    rejected: Vec<Rejection>,
+}
+
+impl FindReport {
+    pub fn config(&self) -> &DeviceConfig {
+        &self.config
+    }
+
+    /// Returns whether enough device files are found for the config.
+    pub fn is_satisfied(&self) -> bool {
+        self.satisfied
+    }
+
+    /// Returns the device files found, which is empty unless the config is satisfied as
+    /// [`find_devices`][crate::find_devices] returns.
+    pub fn found(&self) -> &[DeviceFile] {
+        if self.satisfied {
+            &self.picked
+        } else {
+            &[]
+        }
+    }
+
+    /// Returns the device files picked so far, even if they are not enough.
+    pub fn picked(&self) -> &[DeviceFile] {
+        &self.picked
+    }
+
+    /// Returns the device files which are not picked, with the reasons, in the order of
+    /// devices and device files.
+    pub fn rejected(&self) -> &[Rejection] {
+        &self.rejected
+    }
+}
+
+impl Display for FindReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{} of {} device files found for {}",
+            self.picked.len(),
+            self.config.count(),
+            self.config
+        )?;
+        for file in &self.picked {
+            write!(f, "\n  {}: picked", file.filename())?;
+        }
+        for rejection in &self.rejected {
+            write!(f, "\n  {}", rejection)?;
+        }
+        Ok(())
+    }
+}
+
+/// A device file which is not picked for a [`DeviceConfig`].
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Rejection {
+    pub device_file: DeviceFile,
+    pub reason: RejectReason,
+}
+
+impl Display for Rejection {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {}", self.device_file.filename(), self.reason)
+    }
+}
+
+/// Why a device file is not picked for a [`DeviceConfig`], in the order of precedence.
+#[derive(Clone, Debug, Eq, PartialEq)]
+#[non_exhaustive]
+pub enum RejectReason {
+    ArchMismatch {
+        expected: Arch,
+        actual: Arch,
+    },
+    ModeMismatch {
+        expected: DeviceMode,
+        actual: DeviceMode,
+    },
+    /// The device is on a NUMA node (`None` if unknown) which the NUMA constraint excludes.
+    NumaMismatch {
+        node: Option<usize>,
+    },
+    /// A core of the device file is occupied or unavailable.
+    CoreNotAvailable {
+        core: CoreIdx,
+        status: CoreStatus,
+    },
+    /// A core of the device file is taken by another device file picked earlier.
+    Taken {
+        core: CoreIdx,
+        by: String,
+    },
+    /// The device file fits and is available, but enough device files are picked already.
+    NotNeeded,
+}
+
+impl Display for RejectReason {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::ArchMismatch { expected, actual } => {
+                write!(f, "arch is {}, not {}", actual, expected)
+            }
+            Self::ModeMismatch { expected, actual } => write!(
+                f,
+                "mode is {}, not {}",
+                mode_name(actual),
+                mode_name(expected)
+            ),
+            Self::NumaMismatch { node: Some(node) } => {
+                write!(f, "NUMA node {} is excluded", node)
+            }
+            Self::NumaMismatch { node: None } => write!(f, "NUMA node is unknown"),
+            Self::CoreNotAvailable { core, status } => write!(f, "core {} is {}", core, status),
+            Self::Taken { core, by } => {
+                write!(f, "core {} is taken by {} picked earlier", core, by)
+            }
+            Self::NotNeeded => write!(f, "not needed, enough device files are picked"),
         }
     }
 }
 
+fn mode_name(mode: &DeviceMode) -> &'static str {
+    match mode {
+        DeviceMode::Single => "single",
+        DeviceMode::Fusion => "fusion",
+        DeviceMode::MultiCore => "multicore",
+    }
+}
+
 pub(crate) struct DeviceWithStatus {
     pub device: Device,
     pub statuses: HashMap<CoreIdx, CoreStatus>,
@@ -294,73 +638,520 @@ pub(crate) async fn expand_status(devices: Vec<Device>) -> DeviceResult<Vec<Devi
     Ok(new_devices)
 }
 
+/// Finds device files for the config, or returns an empty list if there are not enough
+/// available device files.
+///
+/// `local_node` is the NUMA node of the calling thread's CPU, if known. If there would be
+/// enough device files but for the NUMA constraint of the config,
+/// [`DeviceError::NumaUnsatisfied`] is returned.
 pub(crate) fn find_devices_in(
     config: &DeviceConfig,
     devices: &[DeviceWithStatus],
+    local_node: Option<usize>,
 ) -> DeviceResult<Vec<DeviceFile>> {
-    let mut allocated: HashMap<u8, HashSet<u8>> = HashMap::with_capacity(devices.len());
+    conclude(config, devices, attempt(config, devices, local_node))
+}
+
+/// Finds device files for the config to stop waiting, or returns `None` if the config has to
+/// wait for device files to be released.
+///
+/// An empty list is returned if the config cannot be satisfied even when all cores are
+/// available, since waiting is then in vain.
+pub(crate) fn find_devices_or_wait(
+    config: &DeviceConfig,
+    devices: &mut [DeviceWithStatus],
+    local_node: Option<usize>,
+) -> DeviceResult<Option<Vec<DeviceFile>>> {
+    match find_devices_in(config, devices, local_node) {
+        Ok(found) if !found.is_empty() => return Ok(Some(found)),
+        Ok(_) | Err(DeviceError::NumaUnsatisfied { .. }) => {}
+        Err(e) => return Err(e),
+    }
 
-    for device in devices {
-        allocated.insert(
-            device.device_index(),
+    for device in devices.iter_mut() {
+        device.statuses = device.new_status_map();
+    }
+    let found = find_devices_in(config, devices, local_node)?;
+    Ok(found.is_empty().then_some(found))
+}
+
+/// Explains which device files are found for the config, and why the others are not.
+pub(crate) fn report_devices_in(
+    config: &DeviceConfig,
+    devices: &[DeviceWithStatus],
+    local_node: Option<usize>,
+) -> FindReport {
+    let attempt = attempt(config, devices, local_node);
+    let rejected = devices
+        .iter()
+        .flat_map(|device| {
             device
-                .statuses
+                .dev_files()
                 .iter()
-                .filter(|(_, status)| **status != CoreStatus::Available)
-                .map(|(core, _)| *core)
-                .collect(),
-        );
+                .filter_map(|file| Some((file, reject(config, device, file, &attempt)?)))
+        })
+        .map(|(file, reason)| Rejection {
+            device_file: file.clone(),
+            reason,
+        })
+        .collect();
+    FindReport {
+        config: *config,
+        satisfied: attempt.picked.len() == usize::from(config.count()),
+        picked: attempt.picked,
+        rejected,
+    }
+}
+
+/// Device files picked for a config, which may be fewer than required.
+struct Attempt {
+    picked: Vec<DeviceFile>,
+    /// Indices of the devices allowed by the NUMA constraint
+    candidates: Vec<u8>,
+    /// Why the NUMA constraint may be to blame if not enough device files are picked
+    numa_reason: Option<String>,
+}
+
+fn attempt(
+    config: &DeviceConfig,
+    devices: &[DeviceWithStatus],
+    local_node: Option<usize>,
+) -> Attempt {
+    let count = usize::from(config.count());
+    let all: Vec<&DeviceWithStatus> = devices.iter().collect();
+    let attempt_on = |candidates: Vec<&DeviceWithStatus>, numa_reason: Option<String>| Attempt {
+        picked: allocate(config, &candidates),
+        candidates: candidates
+            .iter()
+            .map(|device| device.device_index())
+            .collect(),
+        numa_reason,
+    };
+
+    match config.numa_policy() {
+        NumaPolicy::Any => attempt_on(all, None),
+        NumaPolicy::PreferLocal => {
+            let mut ordered = all;
+            if let Some(local) = local_node {
+                // A stable sort keeps the order of device indices on each side.
+                ordered.sort_by_key(|device| numa_node_of(device) != Some(local));
+            }
+            attempt_on(ordered, None)
+        }
+        NumaPolicy::Node(node) => {
+            let mut attempt = attempt_on(on_node(devices, node), None);
+            attempt.numa_reason = Some(format!(
+                "only {} of {} device files are available on NUMA node {}",
+                attempt.picked.len(),
+                count,
+                node
+            ));
+            attempt
+        }
+        NumaPolicy::SameNode => {
+            let mut nodes: Vec<usize> = devices.iter().filter_map(numa_node_of).collect();
+            nodes.sort_unstable();
+            nodes.dedup();
+            if let Some(local) = local_node {
+                nodes.sort_by_key(|node| *node != local);
+            }
+            if nodes.is_empty() {
+                return attempt_on(
+                    vec![],
+                    Some(String::from("NUMA nodes of the devices are unknown")),
+                );
+            }
+
+            let mut best: Option<Attempt> = None;
+            for node in nodes {
+                let attempt = attempt_on(on_node(devices, node), None);
+                if attempt.picked.len() == count {
+                    return attempt;
+                }
+                let better = match &best {
+                    Some(best) => best.picked.len() < attempt.picked.len(),
+                    None => true,
+                };
+                if better {
+                    best = Some(attempt);
+                }
+            }
+            let mut best = best.expect("at least one NUMA node");
+            best.numa_reason = Some(format!(
+                "at most {} of {} device files are available on a single NUMA node",
+                best.picked.len(),
+                count
+            ));
+            best
+        }
+    }
+}
+
+fn conclude(
+    config: &DeviceConfig,
+    devices: &[DeviceWithStatus],
+    attempt: Attempt,
+) -> DeviceResult<Vec<DeviceFile>> {
+    if attempt.picked.len() == usize::from(config.count()) {
+        return Ok(attempt.picked);
+    }
+    match attempt.numa_reason {
+        Some(reason) => unsatisfied(config, &devices.iter().collect::<Vec<_>>(), reason),
+        None => Ok(vec![]),
+    }
+}
+
+/// Tells why the device file is not picked, or `None` if it is or it is not a candidate at
+/// all (e.g., of another device than a named config designates).
+///
+/// Device files which fit and are available are left out only once enough device files are
+/// picked, as every attempt picks as many as it can.
+fn reject(
+    config: &DeviceConfig,
+    device: &DeviceWithStatus,
+    file: &DeviceFile,
+    attempt: &Attempt,
+) -> Option<RejectReason> {
+    match config {
+        DeviceConfig::Named { .. } if !config.fit(device.arch(), file) => return None,
+        DeviceConfig::Named { .. } => {}
+        DeviceConfig::Unnamed { arch, mode, .. } => {
+            if device.arch() != *arch {
+                return Some(RejectReason::ArchMismatch {
+                    expected: *arch,
+                    actual: device.arch(),
+                });
+            }
+            if file.mode() != *mode {
+                return Some(RejectReason::ModeMismatch {
+                    expected: *mode,
+                    actual: file.mode(),
+                });
+            }
+        }
+    }
+    if !attempt.candidates.contains(&device.device_index()) {
+        return Some(RejectReason::NumaMismatch {
+            node: numa_node_of(device),
+        });
+    }
+    if attempt.picked.contains(file) {
+        return None;
     }
 
+    let cores: Vec<CoreIdx> = device
+        .cores()
+        .iter()
+        .copied()
+        .filter(|core| file.core_range().contains(core))
+        .collect();
+    for core in &cores {
+        match device.statuses.get(core) {
+            Some(CoreStatus::Available) | None => {}
+            Some(status) => {
+                return Some(RejectReason::CoreNotAvailable {
+                    core: *core,
+                    status: status.clone(),
+                })
+            }
+        }
+    }
+    attempt
+        .picked
+        .iter()
+        .filter(|picked| picked.device_index() == file.device_index())
+        .find_map(|picked| {
+            let core = cores
+                .iter()
+                .find(|core| picked.core_range().contains(core))?;
+            Some(RejectReason::Taken {
+                core: *core,
+                by: picked.filename().to_string(),
+            })
+        })
+        .or(Some(RejectReason::NotNeeded))
+}
+
+/// Devices whose NUMA nodes are unknown never satisfy NUMA constraints.
+fn numa_node_of(device: &DeviceWithStatus) -> Option<usize> {
+    match device.numa_node() {
+        Ok(NumaNode::Id(node)) => Some(node),
+        Ok(NumaNode::UnSupported) | Err(_) => None,
+    }
+}
+
+fn on_node(devices: &[DeviceWithStatus], node: usize) -> Vec<&DeviceWithStatus> {
+    devices
+        .iter()
+        .filter(|device| numa_node_of(device) == Some(node))
+        .collect()
+}
+
+/// Tells the NUMA constraint apart from a mere lack of available device files.
+fn unsatisfied(
+    config: &DeviceConfig,
+    devices: &[&DeviceWithStatus],
+    reason: String,
+) -> DeviceResult<Vec<DeviceFile>> {
+    if allocate(config, devices).len() < usize::from(config.count()) {
+        return Ok(vec![]);
+    }
+    Err(DeviceError::NumaUnsatisfied {
+        config: config.to_string(),
+        reason,
+    })
+}
+
+/// Allocates device files by the strategy of the config, or as many as there are if they are
+/// not enough.
+fn allocate(config: &DeviceConfig, devices: &[&DeviceWithStatus]) -> Vec<DeviceFile> {
+    match config.allocation_strategy() {
+        AllocationStrategy::FirstFit => first_fit(config, devices),
+        strategy => search(config, strategy, devices),
+    }
+}
+
+/// Takes the first available device file one at a time, stopping at the first one which
+/// cannot be found.
+fn first_fit(config: &DeviceConfig, devices: &[&DeviceWithStatus]) -> Vec<DeviceFile> {
+    let mut used: Vec<HashSet<CoreIdx>> =
+        devices.iter().map(|device| unavailable(device)).collect();
     let config_count = config.count();
     let mut found: Vec<DeviceFile> = Vec::with_capacity(config_count.into());
-    'outer: for _ in 0..config_count {
-        for device in devices {
-            'inner: for dev_file in device.dev_files() {
-                if !config.fit(device.arch(), dev_file) {
-                    continue 'inner;
-                }
+    for _ in 0..config_count {
+        let chosen = devices.iter().enumerate().find_map(|(i, device)| {
+            device
+                .dev_files()
+                .iter()
+                .find(|file| config.fit(device.arch(), file) && is_free(device, file, &used[i]))
+                .map(|file| (i, file))
+        });
+        let Some((i, file)) = chosen else {
+            break;
+        };
 
-                let used = allocated.get_mut(&device.device_index()).unwrap();
+        found.push(file.clone());
+        used[i].extend(cores_of(devices[i], file));
+    }
 
-                for core in used.iter() {
-                    if dev_file.core_range().contains(core) {
-                        continue 'inner;
-                    }
-                }
+    found
+}
+
+/// Costs of a choice of device files, compared lexicographically, where the lower is the
+/// better. Costs of the choices on each device add up.
+type Cost = (usize, usize);
 
-                // this dev_file is suitable
-                found.push(dev_file.clone());
-                used.extend(
-                    device
-                        .cores()
+/// Device files chosen on devices, as pairs of indices of a device and its device file
+type Choice = (Cost, Vec<(usize, usize)>);
+
+/// Searches all combinations of available device files for the ones of the lowest cost by the
+/// strategy, preferring the device files in the order of devices among equally good ones.
+///
+/// Since the cost adds up over devices, the best choice of each number of device files is
+/// built up from the last device to the first, on top of the best choices on the devices
+/// after.
+fn search(
+    config: &DeviceConfig,
+    strategy: AllocationStrategy,
+    devices: &[&DeviceWithStatus],
+) -> Vec<DeviceFile> {
+    let count = usize::from(config.count());
+    // The best choice of each number of device files on the devices examined so far
+    let mut best: Vec<Option<Choice>> = vec![None; count + 1];
+    best[0] = Some(((0, 0), vec![]));
+    for (i, device) in devices.iter().enumerate().rev() {
+        let mut next: Vec<Option<Choice>> = vec![None; count + 1];
+        for (cost, files) in device_choices(config, strategy, device, count) {
+            for (rest, chosen) in best.iter().enumerate() {
+                let Some((rest_cost, rest_files)) = chosen else {
+                    continue;
+                };
+                let Some(slot) = next.get_mut(files.len() + rest) else {
+                    break;
+                };
+                let choice = (
+                    (cost.0 + rest_cost.0, cost.1 + rest_cost.1),
+                    files
                         .iter()
-                        .filter(|idx| dev_file.core_range().contains(idx)),
+                        .map(|file| (i, *file))
+                        .chain(rest_files.iter().copied())
+                        .collect(),
                 );
-                continue 'outer;
+                let better = match slot {
+                    Some(chosen) => choice < *chosen,
+                    None => true,
+                };
+                if better {
+                    *slot = Some(choice);
+                }
             }
         }
-        return Ok(vec![]);
+        best = next;
+    }
+
+    // The most device files there can be, if not as many as required
+    let (_, chosen) = best.into_iter().rev().flatten().next().unwrap_or_default();
+    chosen
+        .into_iter()
+        .map(|(i, file)| devices[i].dev_files()[file].clone())
+        .collect()
+}
+
+/// Finds the best combination of each number of available device files of the device, up to
+/// `count`, which fit the config and do not share cores, with its cost.
+///
+/// The cores of a device file are consecutive, so the chosen device files split the other
+/// cores into gaps, and the device files left available are the ones within the gaps. The
+/// best choices are then built up from the last core to the first, taking the time of the
+/// number of cores times the number of device files times `count`.
+fn device_choices(
+    config: &DeviceConfig,
+    strategy: AllocationStrategy,
+    device: &DeviceWithStatus,
+    count: usize,
+) -> Vec<(Cost, Vec<usize>)> {
+    // Device files chosen within the cores from a position on, with the number of available
+    // device files left within those cores and the number of cores covered.
+    type Partial = (usize, usize, Vec<usize>);
+
+    let cores = device.cores();
+    let used = unavailable(device);
+    // Available device files with the first and last positions of their cores
+    let spans: Vec<(usize, usize, usize)> = device
+        .dev_files()
+        .iter()
+        .enumerate()
+        .filter(|(_, file)| is_free(device, file, &used))
+        .filter_map(|(n, file)| {
+            let first = cores
+                .iter()
+                .position(|core| file.core_range().contains(core))?;
+            let last = cores
+                .iter()
+                .rposition(|core| file.core_range().contains(core))?;
+            Some((n, first, last))
+        })
+        .collect();
+    let within = |start: usize, end: usize| {
+        spans
+            .iter()
+            .filter(|(_, first, last)| start <= *first && *last < end)
+            .count()
+    };
+    let candidates: Vec<(usize, usize, usize)> = spans
+        .iter()
+        .copied()
+        .filter(|(n, _, _)| config.fit(device.arch(), &device.dev_files()[*n]))
+        .collect();
+    let free_files = spans.len();
+    let free_cores = cores.iter().filter(|core| !used.contains(core)).count();
+    // The device files and cores outside the same cores add up the same, so the partial choices
+    // rank as the whole choices they are part of.
+    let cost = |(kept, covered, files): &Partial| {
+        allocation_cost(
+            strategy,
+            free_files - kept,
+            free_cores - covered,
+            files.len(),
+        )
+    };
+
+    // The best choice of each number of device files within the cores from each position on
+    let mut best: Vec<Vec<Option<Partial>>> = vec![vec![]; cores.len() + 1];
+    for start in (0..=cores.len()).rev() {
+        let mut row: Vec<Option<Partial>> = vec![None; count + 1];
+        row[0] = Some((within(start, cores.len()), 0, vec![]));
+        for (n, first, last) in candidates.iter().filter(|(_, first, _)| *first >= start) {
+            let gap = within(start, *first);
+            for (k, rest) in best[last + 1].iter().enumerate().take(count) {
+                let Some((kept, covered, files)) = rest else {
+                    continue;
+                };
+                let mut files = files.clone();
+                files.insert(files.partition_point(|file| file < n), *n);
+                let choice = (gap + kept, covered + last + 1 - first, files);
+                let better = match &row[k + 1] {
+                    Some(chosen) => (cost(&choice), &choice.2) < (cost(chosen), &chosen.2),
+                    None => true,
+                };
+                if better {
+                    row[k + 1] = Some(choice);
+                }
+            }
+        }
+        best[start] = row;
     }
 
-    Ok(found)
+    best.swap_remove(0)
+        .into_iter()
+        .flatten()
+        .map(|choice| (cost(&choice), choice.2))
+        .collect()
+}
+
+/// Cores which are not available
+fn unavailable(device: &DeviceWithStatus) -> HashSet<CoreIdx> {
+    device
+        .statuses
+        .iter()
+        .filter(|(_, status)| **status != CoreStatus::Available)
+        .map(|(core, _)| *core)
+        .collect()
+}
+
+fn cores_of<'a>(device: &'a Device, file: &'a DeviceFile) -> impl Iterator<Item = CoreIdx> + 'a {
+    device
+        .cores()
+        .iter()
+        .copied()
+        .filter(|core| file.core_range().contains(core))
+}
+
+fn is_free(device: &Device, file: &DeviceFile, used: &HashSet<CoreIdx>) -> bool {
+    cores_of(device, file).all(|core| !used.contains(&core))
+}
+
+/// Ranks `picks` device files chosen on a device by the strategy, given the number of device
+/// files they make unavailable, including themselves, and the available cores left.
+fn allocation_cost(
+    strategy: AllocationStrategy,
+    broken: usize,
+    leftover: usize,
+    picks: usize,
+) -> Cost {
+    // Cores left available on the device count only if the device is used
+    let leftover = if picks == 0 { 0 } else { leftover };
+    match strategy {
+        AllocationStrategy::FirstFit => (0, 0),
+        AllocationStrategy::BestFit => (leftover, 0),
+        // Even numbers of device files on the devices, as the sum of squares is the lowest
+        AllocationStrategy::Spread => (usize::from(picks == 0), picks * picks),
+        AllocationStrategy::Pack => (usize::from(picks != 0), leftover),
+        AllocationStrategy::MinFragmentation => (broken, leftover),
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::list::list_devices_with;
+    use std::time::Duration;
+
+    use itertools::Itertools;
+    use proptest::prelude::*;
+
+    use crate::fake::{fake_device, lock_device_file, test_lister, FakeSystem};
+    use crate::OccupancyProbe;
 
     use super::*;
 
     #[tokio::test]
     async fn test_find_devices() -> DeviceResult<()> {
         // test directory contains 2 warboy NPUs
-        let devices = list_devices_with("test_data/test-0/dev", "test_data/test-0/sys").await?;
+        let devices = test_lister().list_devices().await?;
         let devices_with_statuses = expand_status(devices).await?;
 
         // try lookup 4 different single cores
         let config = DeviceConfig::warboy().single().count(4);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found.len(), 4);
         assert_eq!(found[0].filename(), "npu0pe0");
         assert_eq!(found[1].filename(), "npu0pe1");
@@ -369,24 +1160,701 @@ mod tests {
 
         // looking for 5 different cores should fail
         let config = DeviceConfig::warboy().single().count(5);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found, vec![]);
 
         // try lookup 2 different fused cores
         let config = DeviceConfig::warboy().fused().count(2);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found.len(), 2);
         assert_eq!(found[0].filename(), "npu0pe0-1");
         assert_eq!(found[1].filename(), "npu1pe0-1");
 
         // looking for 3 different fused cores should fail
         let config = DeviceConfig::warboy().fused().count(3);
-        let found = find_devices_in(&config, &devices_with_statuses)?;
+        let found = find_devices_in(&config, &devices_with_statuses, None)?;
         assert_eq!(found, vec![]);
 
         Ok(())
     }
 
+    /// npu0 on NUMA node 0, npu1 and npu2 on node 1, and npu3 on an unknown node.
+    fn numa_system() -> std::io::Result<FakeSystem> {
+        let system = FakeSystem::new()?;
+        for (idx, node) in [(0, 0), (1, 1), (2, 1), (3, -1)] {
+            system.add_device(idx, node)?;
+        }
+        system.add_node(0, "0-3")?;
+        system.add_node(1, "4-7")?;
+        Ok(system)
+    }
+
+    async fn find(system: &FakeSystem, config: &str) -> DeviceResult<Vec<String>> {
+        let config = config.parse::<DeviceConfig>().unwrap();
+        let found = system.lister().find_devices(&config).await?;
+        Ok(found
+            .iter()
+            .map(|file| file.filename().to_string())
+            .collect())
+    }
+
+    #[tokio::test]
+    async fn test_find_devices_numa() -> Result<(), Box<dyn std::error::Error>> {
+        let system = numa_system()?;
+
+        // Without constraints, devices are taken in the order of indices
+        assert_eq!(
+            find(&system, "warboy(2)*2").await?,
+            ["npu0pe0-1", "npu1pe0-1"]
+        );
+
+        assert_eq!(
+            find(&system, "warboy(2)*2@1").await?,
+            ["npu1pe0-1", "npu2pe0-1"]
+        );
+        assert_eq!(
+            find(&system, "warboy(1)*3@1").await?,
+            ["npu1pe0", "npu1pe1", "npu2pe0"]
+        );
+        assert_eq!(find(&system, "warboy(2)*1@0").await?, ["npu0pe0-1"]);
+
+        // Node 0 has only one device
+        assert_eq!(
+            find(&system, "warboy(2)*2@same").await?,
+            ["npu1pe0-1", "npu2pe0-1"]
+        );
+        assert_eq!(find(&system, "warboy(2)*1@same").await?, ["npu0pe0-1"]);
+
+        // The local node is unknown without procfs, which is no longer a preference
+        assert_eq!(find(&system, "warboy(1)*1@local").await?, ["npu0pe0"]);
+        system.set_current_cpu(5)?;
+        assert_eq!(find(&system, "warboy(1)*1@local").await?, ["npu1pe0"]);
+        assert_eq!(find(&system, "warboy(2)*1@same").await?, ["npu1pe0-1"]);
+        assert_eq!(
+            find(&system, "warboy(2)*4@local").await?,
+            ["npu1pe0-1", "npu2pe0-1", "npu0pe0-1", "npu3pe0-1"]
+        );
+        system.set_current_cpu(2)?;
+        assert_eq!(
+            find(&system, "warboy(1)*3@local").await?,
+            ["npu0pe0", "npu0pe1", "npu1pe0"]
+        );
+
+        // The local node set explicitly overrides the CPU of the calling thread
+        let config = "warboy(1)*1@local".parse::<DeviceConfig>().unwrap();
+        let found = system.lister().local_node(1).find_devices(&config).await?;
+        assert_eq!(found[0].filename(), "npu1pe0");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_devices_numa_unsatisfied() -> Result<(), Box<dyn std::error::Error>> {
+        let system = numa_system()?;
+
+        let err = find(&system, "warboy(2)*2@0").await.unwrap_err();
+        assert!(matches!(err, DeviceError::NumaUnsatisfied { .. }));
+        assert_eq!(
+            err.to_string(),
+            "Devices for warboy(2)*2@0 not found: only 1 of 2 device files are available on NUMA node 0"
+        );
+        let err = find(&system, "warboy(2)*3@same").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Devices for warboy(2)*3@same not found: at most 2 of 3 device files are available on a single NUMA node"
+        );
+        assert!(matches!(
+            find(&system, "warboy(1)*1@7").await,
+            Err(DeviceError::NumaUnsatisfied { .. })
+        ));
+
+        // Not enough devices regardless of NUMA nodes
+        assert!(find(&system, "warboy(2)*5@same").await?.is_empty());
+        assert!(find(&system, "warboy(2)*5@local").await?.is_empty());
+
+        // Devices on an unknown node do not count
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        let err = find(&system, "warboy(2)*1@same").await.unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Devices for warboy(2)*1@same not found: NUMA nodes of the devices are unknown"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_devices_wait() -> Result<(), Box<dyn std::error::Error>> {
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        let lock_dir = tempfile::tempdir()?;
+        let lister = system
+            .lister()
+            .occupancy_probe(OccupancyProbe::LockFile(lock_dir.path().to_path_buf()));
+        let config = DeviceConfig::warboy().fused().count(1);
+        let timeout = Duration::from_secs(5);
+
+        let lock = lock_device_file(lock_dir.path(), "npu0pe0")?;
+        let err = lister
+            .find_devices_wait(&config, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DeviceError::WaitTimedOut { .. }));
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            drop(lock);
+        });
+        let found = lister.find_devices_wait(&config, timeout).await?;
+        assert_eq!(found[0].filename(), "npu0pe0-1");
+        release.await?;
+
+        // Never satisfied, however long it waits
+        let config = DeviceConfig::warboy().fused().count(2);
+        assert!(lister.find_devices_wait(&config, timeout).await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserve_devices_wait() -> Result<(), Box<dyn std::error::Error>> {
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = system.lister().runtime_dir(runtime_dir.path());
+        let config = DeviceConfig::warboy().fused().count(1);
+        let timeout = Duration::from_secs(5);
+
+        let other = lister.reserve_devices(&"0:0".parse().unwrap()).await?;
+        let err = lister
+            .reserve_devices_wait(&config, Duration::from_millis(200))
+            .await
+            .unwrap_err();
+        assert!(matches!(err, DeviceError::WaitTimedOut { .. }));
+
+        let release = tokio::spawn(async move {
+            tokio::time::sleep(Duration::from_millis(200)).await;
+            drop(other);
+        });
+        let reservation = lister.reserve_devices_wait(&config, timeout).await?;
+        assert_eq!(reservation[0].filename(), "npu0pe0-1");
+        release.await?;
+        drop(reservation);
+
+        // Never satisfied, however long it waits
+        let config = DeviceConfig::warboy().fused().count(2);
+        assert!(matches!(
+            lister.reserve_devices_wait(&config, timeout).await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+        let config = "warboy(2)*1@1".parse::<DeviceConfig>().unwrap();
+        assert!(matches!(
+            lister.reserve_devices_wait(&config, timeout).await,
+            Err(DeviceError::NumaUnsatisfied { .. })
+        ));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserve_devices_wait_in_order() -> Result<(), Box<dyn std::error::Error>> {
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = system.lister().runtime_dir(runtime_dir.path());
+        let core0 = lister.reserve_devices(&"0:0".parse().unwrap()).await?;
+        let core1 = lister.reserve_devices(&"0:1".parse().unwrap()).await?;
+
+        let wait = |config: DeviceConfig| {
+            let lister = lister.clone();
+            tokio::spawn(async move {
+                lister
+                    .reserve_devices_wait(&config, Duration::from_secs(5))
+                    .await
+            })
+        };
+        let fused = wait(DeviceConfig::warboy().fused().count(1));
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        let single = wait(DeviceConfig::warboy().single().count(1));
+
+        // The later waiter for a single core does not take the core released first.
+        drop(core1);
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!fused.is_finished());
+        assert!(!single.is_finished());
+
+        drop(core0);
+        let fused = fused.await??;
+        assert_eq!(fused[0].filename(), "npu0pe0-1");
+        tokio::time::sleep(Duration::from_millis(300)).await;
+        assert!(!single.is_finished());
+        drop(fused);
+        assert_eq!(single.await??.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_find_devices_report() -> Result<(), Box<dyn std::error::Error>> {
+        let system = FakeSystem::new()?;
+        system.add_device(0, -1)?;
+        system.add_device(1, -1)?;
+        // Another fused device file sharing core 1 with npu0pe0-1
+        std::fs::write(system.devfs().join("npu0pe1-2"), "")?;
+        let lock_dir = tempfile::tempdir()?;
+        let lister = system
+            .lister()
+            .occupancy_probe(OccupancyProbe::LockFile(lock_dir.path().to_path_buf()));
+        let _lock = lock_device_file(lock_dir.path(), "npu1pe0")?;
+
+        let config = DeviceConfig::warboy().fused().count(3);
+        let report = lister.find_devices_report(&config).await?;
+        assert!(!report.is_satisfied());
+        assert!(report.found().is_empty());
+        let picked: Vec<&str> = report.picked().iter().map(|f| f.filename()).collect();
+        assert_eq!(picked, ["npu0pe0-1"]);
+        let rejected: HashMap<&str, &RejectReason> = report
+            .rejected()
+            .iter()
+            .map(|rejection| (rejection.device_file.filename(), &rejection.reason))
+            .collect();
+        assert_eq!(
+            rejected["npu0pe0"],
+            &RejectReason::ModeMismatch {
+                expected: DeviceMode::Fusion,
+                actual: DeviceMode::Single,
+            }
+        );
+        assert_eq!(
+            rejected["npu0pe1-2"],
+            &RejectReason::Taken {
+                core: 1,
+                by: String::from("npu0pe0-1"),
+            }
+        );
+        assert_eq!(
+            rejected["npu1pe0-1"],
+            &RejectReason::CoreNotAvailable {
+                core: 0,
+                status: CoreStatus::Occupied(String::from("npu1pe0")),
+            }
+        );
+        assert!(!rejected.contains_key("npu0pe0-1"));
+        assert!(report
+            .to_string()
+            .contains("npu1pe0-1: core 0 is occupied by npu1pe0"));
+        assert_eq!(lister.find_devices(&config).await?, report.found());
+
+        let report = lister
+            .find_devices_report(&DeviceConfig::warboy().fused().count(1))
+            .await?;
+        assert!(report.is_satisfied());
+        assert_eq!(report.found(), report.picked());
+
+        // Available device files left out once enough are picked are reported as well.
+        let report = lister
+            .find_devices_report(&DeviceConfig::warboy().single().count(1))
+            .await?;
+        let rejected: HashMap<&str, &RejectReason> = report
+            .rejected()
+            .iter()
+            .map(|rejection| (rejection.device_file.filename(), &rejection.reason))
+            .collect();
+        assert_eq!(rejected["npu0pe1"], &RejectReason::NotNeeded);
+        assert_eq!(rejected["npu1pe1"], &RejectReason::NotNeeded);
+        assert!(matches!(
+            rejected["npu1pe0"],
+            RejectReason::CoreNotAvailable { core: 0, .. }
+        ));
+        assert!(report
+            .to_string()
+            .contains("npu0pe1: not needed, enough device files are picked"));
+
+        let config = DeviceConfig::Unnamed {
+            arch: Arch::Renegade,
+            core_num: 1,
+            mode: DeviceMode::Single,
+            count: 1,
+            numa: NumaPolicy::Any,
+            strategy: AllocationStrategy::FirstFit,
+        };
+        let report = lister.find_devices_report(&config).await?;
+        assert_eq!(
+            report.rejected()[0].reason,
+            RejectReason::ArchMismatch {
+                expected: Arch::Renegade,
+                actual: Arch::Warboy,
+            }
+        );
+
+        let config = "warboy(1)*1@0".parse::<DeviceConfig>().unwrap();
+        let report = lister.find_devices_report(&config).await?;
+        assert!(report
+            .rejected()
+            .iter()
+            .filter(|rejection| rejection.device_file.mode() == DeviceMode::Single)
+            .all(|rejection| rejection.reason == RejectReason::NumaMismatch { node: None }));
+
+        // Only the designated device file is explained for a named config.
+        let report = lister.find_devices_report(&"1:0".parse()?).await?;
+        assert_eq!(report.rejected().len(), 1);
+        assert_eq!(report.rejected()[0].device_file.filename(), "npu1pe0");
+
+        Ok(())
+    }
+
+    /// Builds a device of the device files, whose cores have the statuses in order.
+    fn device(idx: u8, files: &[&str], statuses: &[CoreStatus]) -> DeviceWithStatus {
+        let device = fake_device(idx, files);
+        let statuses = device
+            .cores()
+            .iter()
+            .copied()
+            .zip(statuses.iter().cloned())
+            .collect();
+        DeviceWithStatus { device, statuses }
+    }
+
+    const WARBOY: [&str; 4] = ["", "pe0", "pe1", "pe0-1"];
+
+    fn occupied() -> CoreStatus {
+        CoreStatus::Occupied(String::from("npu0pe0"))
+    }
+
+    const STRATEGIES: [AllocationStrategy; 5] = [
+        AllocationStrategy::FirstFit,
+        AllocationStrategy::BestFit,
+        AllocationStrategy::Spread,
+        AllocationStrategy::Pack,
+        AllocationStrategy::MinFragmentation,
+    ];
+
+    #[test]
+    fn test_allocation_strategies() -> DeviceResult<()> {
+        use AllocationStrategy::*;
+        use CoreStatus::Available;
+
+        fn find(
+            devices: &[DeviceWithStatus],
+            config: &str,
+            strategy: AllocationStrategy,
+        ) -> DeviceResult<Vec<String>> {
+            let config = config.parse::<DeviceConfig>().unwrap().strategy(strategy);
+            let found = find_devices_in(&config, devices, None)?;
+            Ok(found.iter().map(|f| f.filename().to_string()).collect())
+        }
+
+        // npu1pe1 is occupied.
+        let devices = [
+            device(0, &WARBOY, &[Available, Available]),
+            device(1, &WARBOY, &[Available, occupied()]),
+        ];
+        let find_warboy = |config, strategy| find(&devices, config, strategy);
+
+        // Taking npu0pe0 would leave no fused device files available.
+        assert_eq!(find_warboy("warboy(1)*1", FirstFit)?, ["npu0pe0"]);
+        assert_eq!(find_warboy("warboy(1)*1", BestFit)?, ["npu1pe0"]);
+        assert_eq!(find_warboy("warboy(1)*1", MinFragmentation)?, ["npu1pe0"]);
+
+        assert_eq!(
+            find_warboy("warboy(1)*2", FirstFit)?,
+            ["npu0pe0", "npu0pe1"]
+        );
+        assert_eq!(find_warboy("warboy(1)*2", BestFit)?, ["npu0pe0", "npu0pe1"]);
+        assert_eq!(find_warboy("warboy(1)*2", Spread)?, ["npu0pe0", "npu1pe0"]);
+        assert_eq!(find_warboy("warboy(1)*2", Pack)?, ["npu0pe0", "npu0pe1"]);
+        assert_eq!(
+            find_warboy("warboy(1)*2", MinFragmentation)?,
+            ["npu0pe0", "npu0pe1"]
+        );
+        assert_eq!(
+            find_warboy("warboy(1)*3", Spread)?,
+            ["npu0pe0", "npu0pe1", "npu1pe0"]
+        );
+
+        for strategy in STRATEGIES {
+            assert_eq!(find_warboy("warboy(2)*1", strategy)?, ["npu0pe0-1"]);
+            assert!(find_warboy("warboy(1)*4", strategy)?.is_empty());
+        }
+
+        // Best-fit fills up the cores left on devices in use, even if on more devices.
+        let devices = [
+            device(0, &["", "pe0", "pe1", "pe2", "pe3"], &vec![Available; 4]),
+            device(1, &WARBOY, &[Available, occupied()]),
+            device(2, &WARBOY, &[Available, occupied()]),
+        ];
+        assert_eq!(find(&devices, "warboy(1)*2", Pack)?, ["npu0pe0", "npu0pe1"]);
+        assert_eq!(
+            find(&devices, "warboy(1)*2", BestFit)?,
+            ["npu1pe0", "npu2pe0"]
+        );
+
+        // Taking npu0pe2-3 first leaves no room for another fused device file.
+        let devices = [device(
+            0,
+            &[
+                "pe0", "pe1", "pe2", "pe3", "pe4", "pe5", "pe2-3", "pe0-2", "pe3-5",
+            ],
+            &vec![Available; 6],
+        )];
+        assert!(find(&devices, "warboy(2)*2", FirstFit)?.is_empty());
+        for strategy in [BestFit, Spread, Pack, MinFragmentation] {
+            assert_eq!(
+                find(&devices, "warboy(2)*2", strategy)?,
+                ["npu0pe0-2", "npu0pe3-5"]
+            );
+        }
+
+        // A device with 32 cores, fused in halves down to pairs
+        let names: Vec<String> = [1, 2, 4, 8, 16, 32]
+            .into_iter()
+            .flat_map(|width| {
+                (0..32).step_by(width).map(move |start| match width {
+                    1 => format!("pe{}", start),
+                    _ => format!("pe{}-{}", start, start + width - 1),
+                })
+            })
+            .chain([String::new()])
+            .collect();
+        let files: Vec<&str> = names.iter().map(String::as_str).collect();
+        let singles: Vec<String> = (0..16).map(|core| format!("npu0pe{}", core)).collect();
+        let devices = [device(0, &files, &vec![Available; 32])];
+        assert_eq!(
+            find(&devices, "warboy(1)*4", MinFragmentation)?,
+            ["npu0pe0", "npu0pe1", "npu0pe2", "npu0pe3"]
+        );
+        for strategy in STRATEGIES {
+            assert_eq!(find(&devices, "warboy(1)*16", strategy)?, singles);
+        }
+
+        // npu0pe1 is occupied, so taking npu0pe0 makes no other device file unavailable.
+        let mut statuses = vec![Available; 32];
+        statuses[1] = occupied();
+        let devices = [device(0, &files, &statuses)];
+        assert_eq!(
+            find(&devices, "warboy(1)*2", MinFragmentation)?,
+            ["npu0pe0", "npu0pe2"]
+        );
+        Ok(())
+    }
+
+    fn core_status() -> impl Strategy<Value = CoreStatus> {
+        prop_oneof![
+            3 => Just(CoreStatus::Available),
+            1 => Just(occupied()),
+            1 => Just(CoreStatus::Unavailable),
+        ]
+    }
+
+    /// Device files and core statuses of a device, either a Warboy with 2 cores or a device
+    /// with 4 cores whose cores are fused in different ways.
+    fn device_spec() -> impl Strategy<Value = (Vec<&'static str>, Vec<CoreStatus>)> {
+        const FUSED: [&str; 4] = ["pe0-1", "pe1-2", "pe2-3", "pe0-3"];
+        prop_oneof![
+            prop::collection::vec(core_status(), 2)
+                .prop_map(|statuses| (WARBOY.to_vec(), statuses)),
+            (
+                prop::sample::subsequence(FUSED.to_vec(), 0..=FUSED.len()),
+                prop::collection::vec(core_status(), 4),
+            )
+                .prop_map(|(fused, statuses)| {
+                    let singles = ["", "pe0", "pe1", "pe2", "pe3"];
+                    ([singles.to_vec(), fused].concat(), statuses)
+                }),
+        ]
+    }
+
+    fn device_config() -> impl Strategy<Value = DeviceConfig> {
+        let mode = prop_oneof![
+            Just(DeviceConfig::warboy().single().build()),
+            Just(DeviceConfig::warboy().fused().build()),
+            Just(DeviceConfig::warboy().multicore().build()),
+        ];
+        (mode, 1u8..=6).prop_map(|(config, count)| match config {
+            DeviceConfig::Unnamed {
+                arch,
+                core_num,
+                mode,
+                ..
+            } => DeviceConfig::Unnamed {
+                arch,
+                core_num,
+                mode,
+                count,
+                numa: NumaPolicy::Any,
+                strategy: AllocationStrategy::FirstFit,
+            },
+            named => named,
+        })
+    }
+
+    fn is_available(device: &DeviceWithStatus, file: &DeviceFile) -> bool {
+        cores_of(device, file).all(|core| device.statuses[&core] == CoreStatus::Available)
+    }
+
+    fn overlap(device: &DeviceWithStatus, a: &DeviceFile, b: &DeviceFile) -> bool {
+        a.device_index() == b.device_index()
+            && cores_of(device, a).any(|core| b.core_range().contains(&core))
+    }
+
+    /// Lists all combinations of up to `count` available device files fitting the config,
+    /// which do not share cores.
+    fn combinations(
+        config: &DeviceConfig,
+        devices: &[DeviceWithStatus],
+        count: usize,
+    ) -> Vec<Vec<DeviceFile>> {
+        let candidates: Vec<&DeviceFile> = devices
+            .iter()
+            .flat_map(|device| {
+                device
+                    .dev_files()
+                    .iter()
+                    .filter(|file| config.fit(device.arch(), file) && is_available(device, file))
+            })
+            .collect();
+        let mut combinations = vec![vec![]];
+        let mut n = 0;
+        while n < combinations.len() {
+            let combination: Vec<DeviceFile> = combinations[n].clone();
+            n += 1;
+            if combination.len() == count {
+                continue;
+            }
+            // Extends the combination with the candidates after its last one.
+            let start = combination.last().map_or(0, |last| {
+                candidates.iter().position(|file| *file == last).unwrap() + 1
+            });
+            for file in &candidates[start..] {
+                let device = &devices[usize::from(file.device_index())];
+                if combination
+                    .iter()
+                    .all(|other| !overlap(device, file, other))
+                {
+                    combinations.push([combination.clone(), vec![(*file).clone()]].concat());
+                }
+            }
+        }
+        combinations
+    }
+
+    fn devices_used(found: &[DeviceFile]) -> usize {
+        found.iter().map(|f| f.device_index()).unique().count()
+    }
+
+    /// Counts available cores left on the devices used.
+    fn cores_left(devices: &[DeviceWithStatus], found: &[DeviceFile]) -> usize {
+        found
+            .iter()
+            .map(|f| usize::from(f.device_index()))
+            .unique()
+            .map(|idx| {
+                let device = &devices[idx];
+                device
+                    .cores()
+                    .iter()
+                    .filter(|core| device.statuses[core] == CoreStatus::Available)
+                    .filter(|core| {
+                        !found.iter().any(|f| {
+                            f.device_index() == device.device_index()
+                                && f.core_range().contains(core)
+                        })
+                    })
+                    .count()
+            })
+            .sum()
+    }
+
+    /// Counts device files, of any mode, which are still available besides the ones found.
+    fn files_left(devices: &[DeviceWithStatus], found: &[DeviceFile]) -> usize {
+        devices
+            .iter()
+            .flat_map(|device| device.dev_files().iter().map(move |file| (device, file)))
+            .filter(|(device, file)| {
+                is_available(device, file)
+                    && !found.iter().any(|other| overlap(device, file, other))
+            })
+            .count()
+    }
+
+    proptest! {
+        // Inventories on which the strategies differ are rare among random ones.
+        #![proptest_config(ProptestConfig::with_cases(1024))]
+
+        #[test]
+        fn prop_allocation_strategies(
+            specs in prop::collection::vec(device_spec(), 1..=3),
+            config in device_config(),
+        ) {
+            let devices: Vec<DeviceWithStatus> = specs
+                .iter()
+                .enumerate()
+                .map(|(idx, (files, statuses))| device(idx as u8, files, statuses))
+                .collect();
+            let all: Vec<&DeviceWithStatus> = devices.iter().collect();
+            let count = usize::from(config.count());
+            let combinations = combinations(&config, &devices, count);
+            let most = combinations.iter().map(Vec::len).max().unwrap();
+            let best = |objective: &dyn Fn(&[DeviceFile]) -> usize| {
+                let objectives = combinations
+                    .iter()
+                    .filter(|combination| combination.len() == count)
+                    .map(|combination| objective(combination));
+                (objectives.clone().min(), objectives.max())
+            };
+
+            for strategy in STRATEGIES {
+                let found = allocate(&config.strategy(strategy), &all);
+                for (i, file) in found.iter().enumerate() {
+                    let device = &devices[usize::from(file.device_index())];
+                    prop_assert!(config.fit(device.arch(), file), "{}: {}", strategy, file);
+                    prop_assert!(is_available(device, file), "{}: {}", strategy, file);
+                    for other in &found[..i] {
+                        prop_assert!(!overlap(device, file, other),
+                            "{}: {} overlaps {}", strategy, file, other);
+                    }
+                }
+
+                if strategy == AllocationStrategy::FirstFit {
+                    // Taken one at a time, until no more can be added
+                    prop_assert!(found.len() <= most);
+                    if found.len() < count {
+                        let extensible = combinations.iter().any(|combination| {
+                            combination.len() == found.len() + 1
+                                && found.iter().all(|file| combination.contains(file))
+                        });
+                        prop_assert!(!extensible);
+                    }
+                    continue;
+                }
+                prop_assert_eq!(found.len(), most, "{}", strategy);
+                if found.len() < count {
+                    continue;
+                }
+                match strategy {
+                    AllocationStrategy::BestFit => {
+                        let (least, _) = best(&|found| cores_left(&devices, found));
+                        prop_assert_eq!(Some(cores_left(&devices, &found)), least);
+                    }
+                    AllocationStrategy::Spread => {
+                        let (_, most) = best(&devices_used);
+                        prop_assert_eq!(Some(devices_used(&found)), most);
+                    }
+                    AllocationStrategy::Pack => {
+                        let (least, _) = best(&devices_used);
+                        prop_assert_eq!(Some(devices_used(&found)), least);
+                    }
+                    AllocationStrategy::MinFragmentation => {
+                        let (_, most) = best(&|found| files_left(&devices, found));
+                        prop_assert_eq!(Some(files_left(&devices, &found)), most);
+                    }
+                    AllocationStrategy::FirstFit => unreachable!(),
+                }
+            }
+        }
+    }
+
     #[test]
     fn test_config_from_named_text_repr() -> Result<(), nom::Err<()>> {
         assert!("0:".parse::<DeviceConfig>().is_err());
@@ -453,7 +1921,9 @@ mod tests {
                 arch: Arch::Warboy,
                 core_num: 1,
                 mode: DeviceMode::Single,
-                count: 2
+                count: 2,
+                numa: NumaPolicy::Any,
+                strategy: AllocationStrategy::FirstFit,
             })
         );
         assert_eq!(
@@ -462,7 +1932,9 @@ mod tests {
                 arch: Arch::Warboy,
                 core_num: 2,
                 mode: DeviceMode::Fusion,
-                count: 4
+                count: 4,
+                numa: NumaPolicy::Any,
+                strategy: AllocationStrategy::FirstFit,
             })
         );
         assert_eq!(
@@ -471,9 +1943,46 @@ mod tests {
                 arch: Arch::Warboy,
                 core_num: 0,
                 mode: DeviceMode::MultiCore,
-                count: 12
+                count: 12,
+                numa: NumaPolicy::Any,
+                strategy: AllocationStrategy::FirstFit,
             })
         );
+        assert_eq!(
+            "warboy(1)*2@same".parse::<DeviceConfig>(),
+            Ok(DeviceConfig::warboy()
+                .single()
+                .count(2)
+                .numa(NumaPolicy::SameNode))
+        );
+        assert_eq!(
+            "warboy*1@local".parse::<DeviceConfig>()?.numa_policy(),
+            NumaPolicy::PreferLocal
+        );
+        assert_eq!(
+            "warboy(2)*2@1".parse::<DeviceConfig>()?.numa_policy(),
+            NumaPolicy::Node(1)
+        );
+        assert!("warboy(2)*2@".parse::<DeviceConfig>().is_err());
+        assert!("warboy(2)*2@any".parse::<DeviceConfig>().is_err());
+        assert!("0:0@same".parse::<DeviceConfig>().is_err());
+        assert_eq!(
+            "warboy(1)*2@same#pack".parse::<DeviceConfig>(),
+            Ok(DeviceConfig::warboy()
+                .single()
+                .count(2)
+                .numa(NumaPolicy::SameNode)
+                .strategy(AllocationStrategy::Pack))
+        );
+        assert_eq!(
+            "warboy(2)*2#min-fragmentation"
+                .parse::<DeviceConfig>()?
+                .allocation_strategy(),
+            AllocationStrategy::MinFragmentation
+        );
+        assert!("warboy(2)*2#".parse::<DeviceConfig>().is_err());
+        assert!("warboy(2)*2#worst-fit".parse::<DeviceConfig>().is_err());
+        assert!("warboy(2)*2#pack@same".parse::<DeviceConfig>().is_err());
         // assert!("npu*10".parse::<DeviceConfig>().is_ok());
 
         Ok(())
@@ -497,6 +2006,54 @@ mod tests {
             "warboy(2)*4".parse::<DeviceConfig>()?.to_string(),
             "warboy(2)*4"
         );
+        for text in [
+            "warboy(1)*2@same",
+            "warboy*1@local",
+            "warboy(2)*4@0",
+            "warboy(1)*2#best-fit",
+            "warboy(1)*4@same#spread",
+        ] {
+            assert_eq!(text.parse::<DeviceConfig>()?.to_string(), text);
+        }
+        assert_eq!(
+            DeviceConfig::warboy()
+                .count(1)
+                .numa(NumaPolicy::Any)
+                .to_string(),
+            "warboy(2)*1"
+        );
+        assert_eq!(
+            "warboy(1)*2#first-fit".parse::<DeviceConfig>()?.to_string(),
+            "warboy(1)*2"
+        );
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn test_config_serde_round_trip() -> serde_json::Result<()> {
+        for text in [
+            "0",
+            "0:1",
+            "0:0-1",
+            "warboy*1",
+            "warboy(1)*2",
+            "warboy(2)*4",
+            "warboy(1)*2@same",
+            "warboy(2)*1@1",
+            "warboy(2)*2@same#pack",
+        ] {
+            let config = text.parse::<DeviceConfig>().unwrap();
+            let json = serde_json::to_string(&config)?;
+            assert_eq!(json, format!("\"{}\"", text));
+            assert_eq!(serde_json::from_str::<DeviceConfig>(&json)?, config);
+        }
+        assert_eq!(
+            serde_json::to_string(&DeviceConfig::warboy().fused().count(2))?,
+            r#""warboy(2)*2""#
+        );
+        assert!(serde_json::from_str::<DeviceConfig>(r#""warboy(2*4""#).is_err());
 
         Ok(())
     }
@@ -504,10 +2061,11 @@ mod tests {
     #[tokio::test]
     async fn test_named_config_fit() -> DeviceResult<()> {
         let config = "0:0".parse::<DeviceConfig>().unwrap();
-        let npu0pe0 = crate::get_device_with("test_data/test-0/dev", "npu0pe0").await?;
-        let npu0pe1 = crate::get_device_with("test_data/test-0/dev", "npu0pe1").await?;
-        let npu0pe0_1 = crate::get_device_with("test_data/test-0/dev", "npu0pe0-1").await?;
-        let npu1pe0 = crate::get_device_with("test_data/test-0/dev", "npu0pe1").await?;
+        let lister = test_lister();
+        let npu0pe0 = lister.get_device("npu0pe0").await?;
+        let npu0pe1 = lister.get_device("npu0pe1").await?;
+        let npu0pe0_1 = lister.get_device("npu0pe0-1").await?;
+        let npu1pe0 = lister.get_device("npu0pe1").await?;
 
         assert_eq!(config.count(), 1);
 
@@ -525,9 +2083,10 @@ mod tests {
 
         assert_eq!(config.count(), 2);
 
-        let npu0pe0 = crate::get_device_with("test_data/test-0/dev", "npu0pe0").await?;
-        let npu0pe1 = crate::get_device_with("test_data/test-0/dev", "npu0pe1").await?;
-        let npu0pe0_1 = crate::get_device_with("test_data/test-0/dev", "npu0pe0-1").await?;
+        let lister = test_lister();
+        let npu0pe0 = lister.get_device("npu0pe0").await?;
+        let npu0pe1 = lister.get_device("npu0pe1").await?;
+        let npu0pe0_1 = lister.get_device("npu0pe0-1").await?;
 
         assert!(config.fit(Arch::Warboy, &npu0pe0));
         assert!(config.fit(Arch::Warboy, &npu0pe1));
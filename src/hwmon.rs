@@ -1,13 +1,23 @@
 /* https://www.kernel.org/doc/Documentation/hwmon/sysfs-interface */
 /* The common scheme for files naming is: <type><number>_<item>. */
 
-use std::{collections::HashMap, path::PathBuf, str::FromStr};
+use std::collections::BTreeMap;
+use std::io::{self, ErrorKind};
+use std::time::{Duration, SystemTime};
+use std::{collections::HashMap, path::Path, path::PathBuf, str::FromStr};
 
+use futures::future::try_join_all;
+use futures::stream::{self, Stream};
 use itertools::Itertools;
 use tokio::fs::DirEntry;
+use tokio::time::{Interval, MissedTickBehavior};
 
 use crate::sysfs::pci::hwmon;
-use crate::{DeviceError, DeviceResult};
+use crate::{Device, DeviceError, DeviceResult};
+
+pub use self::units::{Amperes, Celsius, Quantity, Unit, Volts, Watts};
+
+pub mod units;
 
 pub mod error {
     use std::io;
@@ -33,6 +43,30 @@ pub mod error {
         UnexpectedValueFormat { sensor_name: String, value: String },
     }
 
+    impl HwmonError {
+        /// Returns an equivalent error, e.g., to hand one error to many receivers.
+        pub(crate) fn duplicate(&self) -> HwmonError {
+            match self {
+                Self::IoError { cause } => Self::IoError {
+                    cause: crate::error::duplicate_io_error(cause),
+                },
+                Self::UnsupportedType { name } => Self::UnsupportedType { name: name.clone() },
+                Self::InvalidFileName { name } => Self::InvalidFileName { name: name.clone() },
+                Self::ItemNameNotFound {
+                    sensor_name,
+                    item_name,
+                } => Self::ItemNameNotFound {
+                    sensor_name: sensor_name.clone(),
+                    item_name: item_name.clone(),
+                },
+                Self::UnexpectedValueFormat { sensor_name, value } => Self::UnexpectedValueFormat {
+                    sensor_name: sensor_name.clone(),
+                    value: value.clone(),
+                },
+            }
+        }
+    }
+
     impl From<io::Error> for HwmonError {
         fn from(e: io::Error) -> Self {
             Self::IoError { cause: e }
@@ -40,12 +74,23 @@ pub mod error {
     }
 }
 
-#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy)]
+/// A type of hwmon sensors, named after the prefix of their files (e.g., `temp` of
+/// `temp1_input`).
+#[derive(Debug, PartialEq, Eq, Hash, Clone, Copy, PartialOrd, Ord)]
+#[cfg_attr(
+    feature = "serde",
+    derive(serde::Serialize, serde::Deserialize),
+    serde(rename_all = "lowercase")
+)]
 pub enum HwmonType {
     Current,
     Voltage,
     Power,
     Temperature,
+    Fan,
+    Energy,
+    Humidity,
+    Intrusion,
 }
 
 impl FromStr for HwmonType {
@@ -57,6 +102,10 @@ impl FromStr for HwmonType {
             "in" => Ok(HwmonType::Voltage),
             "power" => Ok(HwmonType::Power),
             "temp" => Ok(HwmonType::Temperature),
+            "fan" => Ok(HwmonType::Fan),
+            "energy" => Ok(HwmonType::Energy),
+            "humidity" => Ok(HwmonType::Humidity),
+            "intrusion" => Ok(HwmonType::Intrusion),
             _ => Err(error::HwmonError::UnsupportedType {
                 name: String::from(s),
             }),
@@ -76,7 +125,7 @@ impl TryFrom<&str> for MetricType {
     fn try_from(value: &str) -> Result<Self, Self::Error> {
         let idx_pos =
             value
-                .find(|c: char| c.is_digit(10))
+                .find(|c: char| c.is_ascii_digit())
                 .ok_or(error::HwmonError::InvalidFileName {
                     name: value.to_string(),
                 })?;
@@ -110,19 +159,32 @@ impl TryFrom<DirEntry> for MetricEntry {
     type Error = error::HwmonError;
 
     fn try_from(value: DirEntry) -> Result<Self, Self::Error> {
-        let filename = value.file_name().to_string_lossy().to_string();
+        Self::parse(&value.file_name().to_string_lossy(), value.path())
+    }
+}
+
+#[cfg(feature = "blocking")]
+impl TryFrom<std::fs::DirEntry> for MetricEntry {
+    type Error = error::HwmonError;
+
+    fn try_from(value: std::fs::DirEntry) -> Result<Self, Self::Error> {
+        Self::parse(&value.file_name().to_string_lossy(), value.path())
+    }
+}
 
+impl MetricEntry {
+    fn parse(filename: &str, path: PathBuf) -> error::HwmonResult<Self> {
         let (metric_type_str, metric_item_str) =
             filename
                 .split_once('_')
                 .ok_or_else(|| error::HwmonError::InvalidFileName {
-                    name: filename.clone(),
+                    name: filename.to_string(),
                 })?;
 
         let metric_type = MetricType::try_from(metric_type_str)?;
         let metric_item = MetricItem {
             item_name: metric_item_str.to_string(),
-            path: value.path(),
+            path,
         };
 
         Ok(MetricEntry {
@@ -149,17 +211,167 @@ impl Sensor {
         Self { name, items: map }
     }
 
-    async fn read_item(&self, item_name: &str) -> error::HwmonResult<(String, String)> {
-        if let Some(path) = self.items.get(item_name) {
-            let value = tokio::fs::read_to_string(path).await?;
-
-            Ok((self.name.clone(), value.trim().to_string()))
-        } else {
-            Err(error::HwmonError::ItemNameNotFound {
+    fn item_path(&self, item_name: &str) -> error::HwmonResult<&PathBuf> {
+        self.items
+            .get(item_name)
+            .ok_or_else(|| error::HwmonError::ItemNameNotFound {
                 sensor_name: self.name.clone(),
                 item_name: item_name.to_string(),
             })
+    }
+
+    async fn read_item(&self, item_name: &str) -> error::HwmonResult<(String, String)> {
+        let value = tokio::fs::read_to_string(self.item_path(item_name)?).await?;
+        Ok((self.name.clone(), value.trim().to_string()))
+    }
+
+    #[cfg(feature = "blocking")]
+    fn read_item_blocking(&self, item_name: &str) -> error::HwmonResult<(String, String)> {
+        let value = std::fs::read_to_string(self.item_path(item_name)?)?;
+        Ok((self.name.clone(), value.trim().to_string()))
+    }
+
+    /// Returns items to be read into a [`SensorRecord`].
+    fn record_items(&self) -> Vec<&String> {
+        // Note: Only readable items are read. Others such as `reset_history` may be write-only.
+        self.items
+            .keys()
+            .filter(|name| {
+                name.ends_with("alarm") || SensorRecord::VALUE_ITEMS.contains(&name.as_str())
+            })
+            .sorted()
+            .collect()
+    }
+
+    async fn read_record(&self, hwmon_type: HwmonType) -> error::HwmonResult<SensorRecord> {
+        let mut values = vec![];
+        for item_name in self.record_items() {
+            values.push((item_name, self.read_item(item_name).await?.1));
         }
+        self.build_record(hwmon_type, values)
+    }
+
+    #[cfg(feature = "blocking")]
+    fn read_record_blocking(&self, hwmon_type: HwmonType) -> error::HwmonResult<SensorRecord> {
+        let mut values = vec![];
+        for item_name in self.record_items() {
+            values.push((item_name, self.read_item_blocking(item_name)?.1));
+        }
+        self.build_record(hwmon_type, values)
+    }
+
+    fn build_record(
+        &self,
+        hwmon_type: HwmonType,
+        values: Vec<(&String, String)>,
+    ) -> error::HwmonResult<SensorRecord> {
+        let mut record = SensorRecord::new(hwmon_type, self.name.clone());
+
+        for (item_name, value) in values {
+            let value: i64 =
+                value
+                    .parse()
+                    .map_err(|_| error::HwmonError::UnexpectedValueFormat {
+                        sensor_name: self.name.clone(),
+                        value,
+                    })?;
+
+            if item_name.ends_with("alarm") {
+                record.alarms.insert(item_name.clone(), value != 0);
+                continue;
+            }
+            let field = match item_name.as_str() {
+                "input" => &mut record.input,
+                "average" => &mut record.average,
+                "min" => &mut record.min,
+                "max" => &mut record.max,
+                "lcrit" => &mut record.lcrit,
+                "crit" => &mut record.crit,
+                "emergency" => &mut record.emergency,
+                "lowest" => &mut record.lowest,
+                "highest" => &mut record.highest,
+                "cap" => &mut record.cap,
+                _ => unreachable!("unknown item: {}", item_name),
+            };
+            *field = Some(value);
+        }
+
+        Ok(record)
+    }
+}
+
+/// All readings and thresholds of a hwmon sensor, in the unit of the driver.
+///
+/// Each field is `None` if the driver does not publish the item. `alarms` maps alarm items
+/// (e.g., `alarm`, `crit_alarm`) to whether they are raised.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct SensorRecord {
+    pub hwmon_type: HwmonType,
+    pub label: String,
+    pub input: Option<i64>,
+    pub average: Option<i64>,
+    pub min: Option<i64>,
+    pub max: Option<i64>,
+    pub lcrit: Option<i64>,
+    pub crit: Option<i64>,
+    pub emergency: Option<i64>,
+    pub lowest: Option<i64>,
+    pub highest: Option<i64>,
+    pub cap: Option<i64>,
+    pub alarms: BTreeMap<String, bool>,
+}
+
+impl SensorRecord {
+    const VALUE_ITEMS: &'static [&'static str] = &[
+        "input",
+        "average",
+        "min",
+        "max",
+        "lcrit",
+        "crit",
+        "emergency",
+        "lowest",
+        "highest",
+        "cap",
+    ];
+
+    fn new(hwmon_type: HwmonType, label: String) -> Self {
+        Self {
+            hwmon_type,
+            label,
+            input: None,
+            average: None,
+            min: None,
+            max: None,
+            lcrit: None,
+            crit: None,
+            emergency: None,
+            lowest: None,
+            highest: None,
+            cap: None,
+            alarms: BTreeMap::new(),
+        }
+    }
+
+    /// Returns the current reading: `input`, or `average` if the sensor has no `input`.
+    pub fn value(&self) -> Option<i64> {
+        self.input.or(self.average)
+    }
+
+    /// Returns the current reading as a [`Quantity`], if the sensor type has a unit.
+    pub fn quantity(&self) -> Option<Quantity> {
+        self.value().and_then(|raw| self.to_quantity(raw))
+    }
+
+    /// Converts a raw value of this sensor (e.g., `self.max`) into a [`Quantity`].
+    pub fn to_quantity(&self, raw: i64) -> Option<Quantity> {
+        Quantity::from_raw(self.hwmon_type, raw)
+    }
+
+    /// Returns `true` if any alarm of the sensor is raised.
+    pub fn is_alarmed(&self) -> bool {
+        self.alarms.values().any(|raised| *raised)
     }
 }
 
@@ -167,11 +379,27 @@ impl Sensor {
 pub(crate) struct SensorContainer(pub(crate) HashMap<HwmonType, Vec<Sensor>>);
 
 impl SensorContainer {
-    async fn new(base_dir: &str, busname: &str) -> error::HwmonResult<Self> {
+    async fn new<P: AsRef<Path>>(base_dir: P, busname: &str) -> error::HwmonResult<Self> {
         let path = hwmon::path(base_dir, busname);
         let entries = Self::fetch_entries(path).await?;
         let value_map = Self::build_value_map(entries).await;
 
+        Ok(Self::from_value_map(value_map))
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn new_blocking<P: AsRef<Path>>(
+        base_dir: P,
+        busname: &str,
+    ) -> error::HwmonResult<Self> {
+        let path = hwmon::path(base_dir, busname);
+        let entries = Self::fetch_entries_blocking(path)?;
+        let value_map = Self::build_value_map_blocking(entries);
+
+        Ok(Self::from_value_map(value_map))
+    }
+
+    fn from_value_map(value_map: HashMap<HwmonType, Vec<(String, Vec<MetricItem>)>>) -> Self {
         let sensors: HashMap<HwmonType, Vec<Sensor>> = value_map
             .into_iter()
             .map(|(hwmon_type, v)| {
@@ -184,13 +412,105 @@ impl SensorContainer {
             })
             .collect();
 
-        Ok(SensorContainer(sensors))
+        SensorContainer(sensors)
     }
 
     fn get(&self, t: &HwmonType) -> Option<&Vec<Sensor>> {
         self.0.get(t)
     }
 
+    /// Reads an item of every sensor of the given type. If `skip_missing` is set, sensors
+    /// whose item file has been removed since the container was built are left out.
+    async fn read_values(
+        &self,
+        device_index: u8,
+        t: HwmonType,
+        name: &str,
+        skip_missing: bool,
+    ) -> DeviceResult<Vec<SensorValue>> {
+        let mut res = vec![];
+
+        for sensor in self.get(&t).into_iter().flatten() {
+            let read = sensor.read_item(name).await;
+            res.extend(Self::parse_value(device_index, read, skip_missing)?);
+        }
+
+        Ok(res)
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_values_blocking(
+        &self,
+        device_index: u8,
+        t: HwmonType,
+        name: &str,
+        skip_missing: bool,
+    ) -> DeviceResult<Vec<SensorValue>> {
+        let mut res = vec![];
+
+        for sensor in self.get(&t).into_iter().flatten() {
+            let read = sensor.read_item_blocking(name);
+            res.extend(Self::parse_value(device_index, read, skip_missing)?);
+        }
+
+        Ok(res)
+    }
+
+    /// Parses a value read by [`Sensor::read_item`]. Returns `None` for a missing item if
+    /// `skip_missing` is set.
+    fn parse_value(
+        device_index: u8,
+        read: error::HwmonResult<(String, String)>,
+        skip_missing: bool,
+    ) -> DeviceResult<Option<SensorValue>> {
+        let (label, value) = match read {
+            Ok(v) => v,
+            Err(error::HwmonError::IoError { cause })
+                if skip_missing && cause.kind() == ErrorKind::NotFound =>
+            {
+                return Ok(None)
+            }
+            Err(e) => return Err(DeviceError::hwmon_error(device_index, e)),
+        };
+
+        let value: i32 = value.parse().map_err(|_| {
+            DeviceError::hwmon_error(
+                device_index,
+                error::HwmonError::UnexpectedValueFormat {
+                    sensor_name: label.clone(),
+                    value,
+                },
+            )
+        })?;
+
+        Ok(Some(SensorValue { label, value }))
+    }
+
+    pub(crate) async fn read_records(&self, t: HwmonType) -> error::HwmonResult<Vec<SensorRecord>> {
+        let mut res = vec![];
+        for sensor in self.get(&t).into_iter().flatten() {
+            res.push(sensor.read_record(t).await?);
+        }
+        Ok(res)
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_records_blocking(
+        &self,
+        t: HwmonType,
+    ) -> error::HwmonResult<Vec<SensorRecord>> {
+        self.get(&t)
+            .into_iter()
+            .flatten()
+            .map(|sensor| sensor.read_record_blocking(t))
+            .collect()
+    }
+
+    /// Returns sensor types in the container in order.
+    pub(crate) fn types(&self) -> Vec<HwmonType> {
+        self.0.keys().copied().sorted().collect()
+    }
+
     async fn fetch_entries(mut path: PathBuf) -> error::HwmonResult<Vec<MetricEntry>> {
         let mut vec = vec![];
 
@@ -211,14 +531,54 @@ impl SensorContainer {
         Ok(vec)
     }
 
+    #[cfg(feature = "blocking")]
+    fn fetch_entries_blocking(mut path: PathBuf) -> error::HwmonResult<Vec<MetricEntry>> {
+        let mut vec = vec![];
+
+        if let Some(entry) = std::fs::read_dir(&path)?.next() {
+            // Note: Assume that there is only one 'hwmon' per device
+            path.push(entry?.file_name().to_string_lossy().as_ref());
+
+            for entry in std::fs::read_dir(&path)? {
+                // Note: Unrecognized entries are ignored
+                if let Ok(metric_entry) = MetricEntry::try_from(entry?) {
+                    vec.push(metric_entry);
+                }
+            }
+        }
+
+        Ok(vec)
+    }
+
     async fn build_value_map(
         entries: Vec<MetricEntry>,
     ) -> HashMap<HwmonType, Vec<(String, Vec<MetricItem>)>> {
-        let (labels, metrics): (Vec<_>, Vec<_>) = entries
+        let (labels, metrics) = Self::partition_labels(entries);
+        let labels = Self::read_labels(labels).await;
+
+        Self::group_entries(metrics, Self::label_map(labels))
+    }
+
+    #[cfg(feature = "blocking")]
+    fn build_value_map_blocking(
+        entries: Vec<MetricEntry>,
+    ) -> HashMap<HwmonType, Vec<(String, Vec<MetricItem>)>> {
+        let (labels, metrics) = Self::partition_labels(entries);
+        let labels = Self::read_labels_blocking(labels);
+
+        Self::group_entries(metrics, Self::label_map(labels))
+    }
+
+    fn partition_labels(entries: Vec<MetricEntry>) -> (Vec<MetricEntry>, Vec<MetricEntry>) {
+        entries
             .into_iter()
-            .partition(|entry| entry.metric_item.item_name == "label");
-        let label_map = Self::build_label_map(labels).await;
+            .partition(|entry| entry.metric_item.item_name == "label")
+    }
 
+    fn group_entries(
+        metrics: Vec<MetricEntry>,
+        label_map: HashMap<MetricType, String>,
+    ) -> HashMap<HwmonType, Vec<(String, Vec<MetricItem>)>> {
         let mut map_by_metric_type = HashMap::new();
         for entry in metrics {
             map_by_metric_type
@@ -248,39 +608,161 @@ impl SensorContainer {
         res
     }
 
-    async fn build_label_map(label_entries: Vec<MetricEntry>) -> HashMap<MetricType, String> {
-        let mut map = HashMap::new();
-
+    /// Reads the contents of label files, which are parsed by [`Self::label_map`].
+    async fn read_labels(label_entries: Vec<MetricEntry>) -> Vec<(MetricType, io::Result<String>)> {
+        let mut labels = vec![];
         for entry in label_entries {
-            if let Ok(text) = tokio::fs::read_to_string(&entry.metric_item.path).await {
-                map.insert(entry.metric_type, text.trim().to_string());
-            }
+            let text = tokio::fs::read_to_string(&entry.metric_item.path).await;
+            labels.push((entry.metric_type, text));
         }
+        labels
+    }
 
-        map
+    #[cfg(feature = "blocking")]
+    fn read_labels_blocking(
+        label_entries: Vec<MetricEntry>,
+    ) -> Vec<(MetricType, io::Result<String>)> {
+        label_entries
+            .into_iter()
+            .map(|entry| {
+                let text = std::fs::read_to_string(&entry.metric_item.path);
+                (entry.metric_type, text)
+            })
+            .collect()
+    }
+
+    /// Labels of sensors from the contents of their label files. Unreadable ones are ignored.
+    fn label_map(labels: Vec<(MetricType, io::Result<String>)>) -> HashMap<MetricType, String> {
+        labels
+            .into_iter()
+            .filter_map(|(metric_type, text)| Some((metric_type, text.ok()?.trim().to_string())))
+            .collect()
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
+/// Returns the item read for sensors in the unit `U`: `average` for power and `input` for
+/// the others.
+pub(crate) fn unit_item<U: Unit>() -> &'static str {
+    match U::HWMON_TYPE {
+        HwmonType::Power => "average",
+        _ => "input",
+    }
+}
+
+pub(crate) fn to_readings<U: Unit>(values: Vec<SensorValue>) -> Vec<Reading<U>> {
+    values
+        .into_iter()
+        .map(|v| Reading {
+            value: v.to_unit(),
+            label: v.label,
+        })
+        .collect()
+}
+
+/// A value read from a hwmon sensor, in the unit of the driver.
+///
+/// With the `serde` feature, it is serialized as `{"label": "Temp1", "value": 36000}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct SensorValue {
     pub label: String,
     pub value: i32,
 }
 
+impl SensorValue {
+    /// Converts the raw value into the given unit. The caller is responsible for choosing the
+    /// unit of the sensor type the value was read from; see [`Fetcher::read`] otherwise.
+    pub fn to_unit<U: Unit>(&self) -> U {
+        U::from_raw(i64::from(self.value))
+    }
+}
+
+/// A value read from a hwmon sensor, in the unit `U`.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reading<U> {
+    pub label: String,
+    pub value: U,
+}
+
+/// Values of all sensors of a device, read at the same time.
+///
+/// With the `serde` feature, `timestamp` is serialized as
+/// `{"secs_since_epoch": .., "nanos_since_epoch": ..}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Snapshot {
+    pub device_index: u8,
+    pub timestamp: SystemTime,
+    pub currents: Vec<SensorValue>,
+    pub voltages: Vec<SensorValue>,
+    pub powers_average: Vec<SensorValue>,
+    pub temperatures: Vec<SensorValue>,
+}
+
+impl Snapshot {
+    /// Items read into a snapshot, in the order of its fields.
+    const ITEMS: [(HwmonType, &'static str); 4] = [
+        (HwmonType::Current, "input"),
+        (HwmonType::Voltage, "input"),
+        (HwmonType::Power, "average"),
+        (HwmonType::Temperature, "input"),
+    ];
+
+    /// Builds a snapshot of values read for [`Self::ITEMS`] in order.
+    fn new(device_index: u8, timestamp: SystemTime, values: Vec<Vec<SensorValue>>) -> Self {
+        let mut values = values.into_iter();
+        let mut next = || values.next().unwrap_or_default();
+        Self {
+            device_index,
+            timestamp,
+            currents: next(),
+            voltages: next(),
+            powers_average: next(),
+            temperatures: next(),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Fetcher {
     pub(crate) device_index: u8,
+    pub(crate) sysfs: PathBuf,
+    pub(crate) busname: String,
     pub(crate) sensor_container: SensorContainer,
 }
 
 impl Fetcher {
-    pub(crate) async fn new(base_dir: &str, device_index: u8, busname: &str) -> DeviceResult<Self> {
-        let sensor_container = SensorContainer::new(base_dir, busname)
+    pub(crate) async fn new<P: AsRef<Path>>(
+        base_dir: P,
+        device_index: u8,
+        busname: &str,
+    ) -> DeviceResult<Self> {
+        let sensor_container = SensorContainer::new(&base_dir, busname)
             .await
             .map_err(|e| DeviceError::hwmon_error(device_index, e))?;
 
         Ok(Self {
             device_index,
+            sysfs: base_dir.as_ref().to_path_buf(),
+            busname: busname.to_string(),
+            sensor_container,
+        })
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn new_blocking<P: AsRef<Path>>(
+        base_dir: P,
+        device_index: u8,
+        busname: &str,
+    ) -> DeviceResult<Self> {
+        let sensor_container = SensorContainer::new_blocking(&base_dir, busname)
+            .map_err(|e| DeviceError::hwmon_error(device_index, e))?;
+
+        Ok(Self {
+            device_index,
+            sysfs: base_dir.as_ref().to_path_buf(),
+            busname: busname.to_string(),
             sensor_container,
         })
     }
@@ -301,37 +783,167 @@ impl Fetcher {
         self.read_values(HwmonType::Temperature, "input").await
     }
 
-    async fn read_values(&self, t: HwmonType, name: &str) -> DeviceResult<Vec<SensorValue>> {
+    pub async fn read_powers_input(&self) -> DeviceResult<Vec<SensorValue>> {
+        self.read_values(HwmonType::Power, "input").await
+    }
+
+    /// Reads sensors of the type of the unit `U`, e.g., `fetcher.read::<Celsius>()`.
+    ///
+    /// It reads the same items as the other `read_*` methods: `average` for power and `input`
+    /// for the others.
+    pub async fn read<U: Unit>(&self) -> DeviceResult<Vec<Reading<U>>> {
+        let values = self.read_values(U::HWMON_TYPE, unit_item::<U>()).await?;
+        Ok(to_readings(values))
+    }
+
+    /// Reads all published items of each sensor of the given type.
+    pub async fn read_records(&self, t: HwmonType) -> DeviceResult<Vec<SensorRecord>> {
+        self.sensor_container
+            .read_records(t)
+            .await
+            .map_err(|e| DeviceError::hwmon_error(self.device_index, e))
+    }
+
+    /// Reads all published items of every sensor of the device, ordered by type.
+    pub async fn read_all_records(&self) -> DeviceResult<Vec<SensorRecord>> {
         let mut res = vec![];
+        for t in self.types() {
+            res.extend(self.read_records(t).await?);
+        }
+        Ok(res)
+    }
 
-        if let Some(sensors) = self.sensor_container.get(&t) {
-            for sensor in sensors {
-                let (label, value) = sensor
-                    .read_item(name)
-                    .await
-                    .map_err(|e| DeviceError::hwmon_error(self.device_index, e))?;
-
-                let value: i32 = value.parse().map_err(|_| {
-                    DeviceError::hwmon_error(
-                        self.device_index,
-                        error::HwmonError::UnexpectedValueFormat {
-                            sensor_name: label.clone(),
-                            value,
-                        },
-                    )
-                })?;
+    /// Reads all sensors of the device at once.
+    ///
+    /// Unlike the other `read_*` methods, it discovers sensors again on every call, so
+    /// sensors which appeared or disappeared after the device was listed are reflected.
+    pub async fn read_snapshot(&self) -> DeviceResult<Snapshot> {
+        self.read_snapshot_at(SystemTime::now()).await
+    }
 
-                res.push(SensorValue { label, value });
-            }
+    /// Returns a stream which yields a [`Snapshot`] of the device every `period`.
+    ///
+    /// Sensors are read only when the stream is polled. If the consumer falls behind, missed
+    /// samples are dropped instead of being buffered, and the next sample is taken `period`
+    /// after the late one. A failed read is yielded as an error and sampling continues.
+    ///
+    /// It fails if `period` is zero.
+    pub fn watch(
+        &self,
+        period: Duration,
+    ) -> DeviceResult<impl Stream<Item = DeviceResult<Snapshot>> + '_> {
+        sample(period, move |timestamp| self.read_snapshot_at(timestamp))
+    }
+
+    async fn read_snapshot_at(&self, timestamp: SystemTime) -> DeviceResult<Snapshot> {
+        let container = SensorContainer::new(&self.sysfs, &self.busname)
+            .await
+            .map_err(|e| DeviceError::hwmon_error(self.device_index, e))?;
+        let mut values = vec![];
+        for (t, name) in Snapshot::ITEMS {
+            values.push(
+                container
+                    .read_values(self.device_index, t, name, true)
+                    .await?,
+            );
         }
 
-        Ok(res)
+        Ok(Snapshot::new(self.device_index, timestamp, values))
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_snapshot_blocking(&self) -> DeviceResult<Snapshot> {
+        let timestamp = SystemTime::now();
+        let container = SensorContainer::new_blocking(&self.sysfs, &self.busname)
+            .map_err(|e| DeviceError::hwmon_error(self.device_index, e))?;
+        let values = Snapshot::ITEMS
+            .into_iter()
+            .map(|(t, name)| container.read_values_blocking(self.device_index, t, name, true))
+            .collect::<DeviceResult<_>>()?;
+
+        Ok(Snapshot::new(self.device_index, timestamp, values))
+    }
+
+    async fn read_values(&self, t: HwmonType, name: &str) -> DeviceResult<Vec<SensorValue>> {
+        self.sensor_container
+            .read_values(self.device_index, t, name, false)
+            .await
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_values_blocking(
+        &self,
+        t: HwmonType,
+        name: &str,
+    ) -> DeviceResult<Vec<SensorValue>> {
+        self.sensor_container
+            .read_values_blocking(self.device_index, t, name, false)
+    }
+
+    #[cfg(feature = "blocking")]
+    pub(crate) fn read_records_blocking(&self, t: HwmonType) -> DeviceResult<Vec<SensorRecord>> {
+        self.sensor_container
+            .read_records_blocking(t)
+            .map_err(|e| DeviceError::hwmon_error(self.device_index, e))
     }
+
+    pub(crate) fn types(&self) -> Vec<HwmonType> {
+        self.sensor_container.types()
+    }
+}
+
+/// Returns a stream which yields snapshots of all given devices every `period`.
+///
+/// Snapshots in an item share the same timestamp, in the order of `devices`. Backpressure
+/// and errors are handled in the same way as [`Fetcher::watch`]; an error reading any device
+/// is yielded in place of the item.
+///
+/// It fails if `period` is zero.
+pub fn watch_all(
+    devices: &[Device],
+    period: Duration,
+) -> DeviceResult<impl Stream<Item = DeviceResult<Vec<Snapshot>>> + '_> {
+    sample(period, move |timestamp| {
+        try_join_all(
+            devices
+                .iter()
+                .map(move |device| device.get_hwmon_fetcher().read_snapshot_at(timestamp)),
+        )
+    })
+}
+
+fn sample<'a, T, F, Fut>(period: Duration, read: F) -> DeviceResult<impl Stream<Item = T> + 'a>
+where
+    F: FnMut(SystemTime) -> Fut + 'a,
+    Fut: std::future::Future<Output = T> + 'a,
+{
+    // `tokio::time::interval` panics on a zero period, which is checked here instead.
+    if period.is_zero() {
+        return Err(DeviceError::zero_period());
+    }
+
+    // The interval is created on the first poll since it requires a runtime.
+    Ok(stream::unfold(
+        (None::<Interval>, read),
+        move |(interval, mut read)| async move {
+            let mut interval = interval.unwrap_or_else(|| {
+                let mut interval = tokio::time::interval(period);
+                interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+                interval
+            });
+            interval.tick().await;
+            let item = read(SystemTime::now()).await;
+            Some((item, (Some(interval), read)))
+        },
+    ))
 }
 
 #[cfg(test)]
 mod tests {
+    use futures::StreamExt;
+
     use super::*;
+    use crate::fake::test_lister;
 
     #[tokio::test]
     async fn hwmon_metric_entry_try_from_test() -> error::HwmonResult<()> {
@@ -430,8 +1042,10 @@ mod tests {
 
     #[tokio::test]
     async fn sensor_build_label_map_test() -> error::HwmonResult<()> {
+        let build_label_map =
+            |input| async { SensorContainer::label_map(SensorContainer::read_labels(input).await) };
         let input = vec![];
-        let output = SensorContainer::build_label_map(input).await;
+        let output = build_label_map(input).await;
         assert_eq!(output.len(), 0);
 
         let input = vec![MetricEntry {
@@ -446,7 +1060,7 @@ mod tests {
                 ),
             },
         }];
-        let output = SensorContainer::build_label_map(input).await;
+        let output = build_label_map(input).await;
 
         assert_eq!(output.len(), 1);
         let res = output.get(&MetricType {
@@ -455,18 +1069,14 @@ mod tests {
         });
         assert!(res.is_some());
         assert_eq!(res.unwrap(), "Temp1");
-        assert!(output
-            .get(&MetricType {
-                hwmon_type: HwmonType::Temperature,
-                idx: 2
-            })
-            .is_none());
-        assert!(output
-            .get(&MetricType {
-                hwmon_type: HwmonType::Power,
-                idx: 1
-            })
-            .is_none());
+        assert!(!output.contains_key(&MetricType {
+            hwmon_type: HwmonType::Temperature,
+            idx: 2
+        }));
+        assert!(!output.contains_key(&MetricType {
+            hwmon_type: HwmonType::Power,
+            idx: 1
+        }));
 
         Ok(())
     }
@@ -505,4 +1115,254 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn hwmon_type_from_str_test() {
+        assert_eq!("fan".parse::<HwmonType>().unwrap(), HwmonType::Fan);
+        assert_eq!("energy".parse::<HwmonType>().unwrap(), HwmonType::Energy);
+        assert_eq!(
+            "humidity".parse::<HwmonType>().unwrap(),
+            HwmonType::Humidity
+        );
+        assert_eq!(
+            "intrusion".parse::<HwmonType>().unwrap(),
+            HwmonType::Intrusion
+        );
+        assert!("pwm".parse::<HwmonType>().is_err());
+
+        let metric_type = MetricType::try_from("intrusion0").unwrap();
+        assert_eq!(metric_type.hwmon_type, HwmonType::Intrusion);
+        assert_eq!(metric_type.idx, 0);
+    }
+
+    #[tokio::test]
+    async fn fetcher_read_records_test() -> Result<(), Box<dyn std::error::Error>> {
+        let sysfs = tempfile::tempdir()?;
+        let dir = sysfs
+            .path()
+            .join("bus/pci/devices/0000:01:00.0/hwmon/hwmon0");
+        std::fs::create_dir_all(&dir)?;
+        for (file, contents) in [
+            ("temp1_label", "Temp1"),
+            ("temp1_input", "36000"),
+            ("temp1_max", "80000"),
+            ("temp1_crit", "95000"),
+            ("temp1_highest", "41000"),
+            ("temp1_max_alarm", "0"),
+            ("temp1_crit_alarm", "1"),
+            ("temp1_reset_history", ""),
+            ("power1_input", "25000000"),
+            ("power1_average", "24000000"),
+            ("power1_cap", "40000000"),
+            ("energy1_input", "12345678901"),
+            ("fan1_input", "1200"),
+            ("fan1_min", "600"),
+            ("fan1_alarm", "0"),
+            ("humidity1_input", "45000"),
+            ("intrusion0_alarm", "0"),
+        ] {
+            std::fs::write(dir.join(file), format!("{}\n", contents))?;
+        }
+        let fetcher = Fetcher::new(sysfs.path(), 0, "0000:01:00.0").await?;
+
+        let temperatures = fetcher.read_records(HwmonType::Temperature).await?;
+        assert_eq!(temperatures.len(), 1);
+        let temp = &temperatures[0];
+        assert_eq!(temp.label, "Temp1");
+        assert_eq!(temp.value(), Some(36000));
+        assert_eq!(temp.quantity(), Some(Quantity::Temperature(Celsius(36.0))));
+        assert_eq!(temp.to_quantity(temp.crit.unwrap()).unwrap().value(), 95.0);
+        assert_eq!(temp.max, Some(80000));
+        assert_eq!(temp.crit, Some(95000));
+        assert_eq!(temp.highest, Some(41000));
+        assert_eq!(temp.lcrit, None);
+        assert_eq!(
+            temp.alarms,
+            BTreeMap::from([
+                (String::from("crit_alarm"), true),
+                (String::from("max_alarm"), false),
+            ])
+        );
+        assert!(temp.is_alarmed());
+
+        let powers = fetcher.read_records(HwmonType::Power).await?;
+        assert_eq!(powers[0].input, Some(25000000));
+        assert_eq!(powers[0].average, Some(24000000));
+        assert_eq!(powers[0].cap, Some(40000000));
+        assert_eq!(fetcher.read_powers_input().await?[0].value, 25000000);
+
+        let energies = fetcher.read_records(HwmonType::Energy).await?;
+        assert_eq!(energies[0].input, Some(12345678901));
+
+        let records = fetcher.read_all_records().await?;
+        let types: Vec<_> = records.iter().map(|r| r.hwmon_type).collect();
+        assert_eq!(
+            types,
+            vec![
+                HwmonType::Power,
+                HwmonType::Temperature,
+                HwmonType::Fan,
+                HwmonType::Energy,
+                HwmonType::Humidity,
+                HwmonType::Intrusion
+            ]
+        );
+        assert_eq!(records[2].min, Some(600));
+        assert!(!records[2].is_alarmed());
+        assert_eq!(records[4].input, Some(45000));
+        assert_eq!(records[5].label, "0");
+        assert_eq!(records[5].alarms.get("alarm"), Some(&false));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetcher_read_units_test() -> DeviceResult<()> {
+        let fetcher = Fetcher::new("test_data/test-0/sys", 0, "0000:6d:00.0").await?;
+
+        let currents = fetcher.read::<Amperes>().await?;
+        assert_eq!(currents[1].label, "Current2");
+        assert_eq!(currents[1].value, Amperes(2.0));
+        assert_eq!(fetcher.read::<Volts>().await?[0].value, Volts(1.1));
+        assert_eq!(fetcher.read::<Watts>().await?[1].value, Watts(0.022222));
+
+        let temperatures = fetcher.read::<Celsius>().await?;
+        assert_eq!(temperatures[0].value.to_string(), "36 °C");
+
+        // Raw values are still available.
+        let raw = fetcher.read_temperatures().await?;
+        assert_eq!(raw[0].value, 36000);
+        assert_eq!(raw[0].to_unit::<Celsius>(), temperatures[0].value);
+        assert_eq!(temperatures[0].value.to_raw(), 36000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetcher_watch_test() -> DeviceResult<()> {
+        let fetcher = Fetcher::new("test_data/test-0/sys", 0, "0000:6d:00.0").await?;
+        let snapshots: Vec<_> = fetcher
+            .watch(Duration::from_millis(10))?
+            .take(3)
+            .collect()
+            .await;
+
+        assert_eq!(snapshots.len(), 3);
+        let snapshots = snapshots.into_iter().collect::<DeviceResult<Vec<_>>>()?;
+        for snapshot in &snapshots {
+            assert_eq!(snapshot.device_index, 0);
+            assert_eq!(snapshot.currents, fetcher.read_currents().await?);
+            assert_eq!(snapshot.voltages, fetcher.read_voltages().await?);
+            assert_eq!(
+                snapshot.powers_average,
+                fetcher.read_powers_average().await?
+            );
+            assert_eq!(snapshot.temperatures, fetcher.read_temperatures().await?);
+        }
+        assert!(snapshots[0].timestamp < snapshots[1].timestamp);
+        assert!(snapshots[1].timestamp < snapshots[2].timestamp);
+
+        Ok(())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn fetcher_watch_backpressure_test() -> DeviceResult<()> {
+        let period = Duration::from_millis(20);
+        let fetcher = Fetcher::new("test_data/test-0/sys", 0, "0000:6d:00.0").await?;
+        let mut stream = Box::pin(fetcher.watch(period)?);
+        stream.next().await.unwrap()?;
+
+        // Samples missed while the consumer is busy are not queued up.
+        tokio::time::sleep(period * 5).await;
+        let start = tokio::time::Instant::now();
+        stream.next().await.unwrap()?;
+        assert_eq!(start.elapsed(), Duration::ZERO);
+        stream.next().await.unwrap()?;
+        assert_eq!(start.elapsed(), period);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetcher_watch_zero_period_test() -> DeviceResult<()> {
+        let fetcher = Fetcher::new("test_data/test-0/sys", 0, "0000:6d:00.0").await?;
+        assert!(fetcher.watch(Duration::ZERO).is_err());
+        assert!(watch_all(&[], Duration::ZERO).is_err());
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetcher_watch_sensor_change_test() -> Result<(), Box<dyn std::error::Error>> {
+        let sysfs = tempfile::tempdir()?;
+        let dir = sysfs
+            .path()
+            .join("bus/pci/devices/0000:01:00.0/hwmon/hwmon0");
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("temp1_input"), "36000\n")?;
+        std::fs::write(dir.join("temp1_label"), "Temp1\n")?;
+
+        let fetcher = Fetcher::new(sysfs.path(), 0, "0000:01:00.0").await?;
+        let mut stream = Box::pin(fetcher.watch(Duration::from_millis(10))?);
+        let snapshot = stream.next().await.unwrap()?;
+        assert_eq!(
+            snapshot.temperatures,
+            vec![SensorValue {
+                label: String::from("Temp1"),
+                value: 36000
+            }]
+        );
+        assert!(snapshot.currents.is_empty());
+
+        std::fs::remove_file(dir.join("temp1_input"))?;
+        std::fs::remove_file(dir.join("temp1_label"))?;
+        std::fs::write(dir.join("temp2_input"), "37000\n")?;
+        std::fs::write(dir.join("curr1_input"), "1000\n")?;
+
+        let snapshot = stream.next().await.unwrap()?;
+        assert_eq!(
+            snapshot.temperatures,
+            vec![SensorValue {
+                label: String::from("2"),
+                value: 37000
+            }]
+        );
+        assert_eq!(snapshot.currents.len(), 1);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn watch_all_test() -> DeviceResult<()> {
+        let devices = test_lister().list_devices().await?;
+        let items: Vec<_> = watch_all(&devices, Duration::from_millis(10))?
+            .take(2)
+            .collect()
+            .await;
+
+        assert_eq!(items.len(), 2);
+        for item in items {
+            let snapshots = item?;
+            assert_eq!(snapshots.len(), 2);
+            assert_eq!(snapshots[0].device_index, 0);
+            assert_eq!(snapshots[1].device_index, 1);
+            assert_eq!(snapshots[0].timestamp, snapshots[1].timestamp);
+            assert_eq!(snapshots[0].temperatures.len(), 2);
+            assert_eq!(snapshots[1].currents.len(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn sensor_value_serde_round_trip() -> serde_json::Result<()> {
+        let value = SensorValue {
+            label: String::from("Temp1"),
+            value: 36000,
+        };
+        let json = serde_json::to_string(&value)?;
+        assert_eq!(json, r#"{"label":"Temp1","value":36000}"#);
+        assert_eq!(serde_json::from_str::<SensorValue>(&json)?, value);
+        Ok(())
+    }
 }
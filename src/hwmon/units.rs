@@ -0,0 +1,197 @@
+//! Physical quantities of hwmon sensor values.
+//!
+//! Drivers publish values as integers in fixed units: current in mA, voltage in mV,
+//! power in µW and temperature in m°C. The types in this module convert them into the base
+//! units, e.g., `Celsius::from_raw(36000)` is `36 °C`.
+
+use std::fmt::{self, Display};
+
+use super::HwmonType;
+
+/// A unit of values of a hwmon sensor type.
+pub trait Unit: Copy + Display {
+    /// The sensor type whose values are in this unit.
+    const HWMON_TYPE: HwmonType;
+    /// The symbol used by `Display`.
+    const SYMBOL: &'static str;
+    /// How many raw values, in the unit of the driver, make one of this unit.
+    const RAW_PER_UNIT: i64;
+
+    fn new(value: f64) -> Self;
+
+    /// Returns the value in this unit.
+    fn value(self) -> f64;
+
+    /// Converts a raw value in the unit of the driver.
+    fn from_raw(raw: i64) -> Self {
+        Self::new(raw as f64 / Self::RAW_PER_UNIT as f64)
+    }
+
+    /// Converts back into a raw value in the unit of the driver.
+    fn to_raw(self) -> i64 {
+        (self.value() * Self::RAW_PER_UNIT as f64).round() as i64
+    }
+}
+
+macro_rules! define_unit {
+    ($(#[$meta:meta])* $name:ident, $hwmon_type:expr, $symbol:expr, $raw_per_unit:expr) => {
+        $(#[$meta])*
+        #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, Default)]
+        #[cfg_attr(
+            feature = "serde",
+            derive(serde::Serialize, serde::Deserialize),
+            serde(transparent)
+        )]
+        pub struct $name(pub f64);
+
+        impl Unit for $name {
+            const HWMON_TYPE: HwmonType = $hwmon_type;
+            const SYMBOL: &'static str = $symbol;
+            const RAW_PER_UNIT: i64 = $raw_per_unit;
+
+            fn new(value: f64) -> Self {
+                Self(value)
+            }
+
+            fn value(self) -> f64 {
+                self.0
+            }
+        }
+
+        impl From<$name> for f64 {
+            fn from(v: $name) -> f64 {
+                v.0
+            }
+        }
+
+        /// Honors the precision of the formatter, e.g., `format!("{:.1}", v)`.
+        impl Display for $name {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                Display::fmt(&self.0, f)?;
+                write!(f, " {}", Self::SYMBOL)
+            }
+        }
+    };
+}
+
+define_unit!(
+    /// Current in amperes, published in mA.
+    Amperes,
+    HwmonType::Current,
+    "A",
+    1_000
+);
+define_unit!(
+    /// Voltage in volts, published in mV.
+    Volts,
+    HwmonType::Voltage,
+    "V",
+    1_000
+);
+define_unit!(
+    /// Power in watts, published in µW.
+    Watts,
+    HwmonType::Power,
+    "W",
+    1_000_000
+);
+define_unit!(
+    /// Temperature in degrees Celsius, published in m°C.
+    Celsius,
+    HwmonType::Temperature,
+    "°C",
+    1_000
+);
+
+/// A value of any sensor type that has a [`Unit`].
+#[derive(Debug, Clone, Copy, PartialEq, PartialOrd)]
+pub enum Quantity {
+    Current(Amperes),
+    Voltage(Volts),
+    Power(Watts),
+    Temperature(Celsius),
+}
+
+impl Quantity {
+    /// Converts a raw value of the given sensor type. Returns `None` for sensor types
+    /// without a unit yet (e.g., fan and energy).
+    pub fn from_raw(hwmon_type: HwmonType, raw: i64) -> Option<Self> {
+        match hwmon_type {
+            HwmonType::Current => Some(Self::Current(Amperes::from_raw(raw))),
+            HwmonType::Voltage => Some(Self::Voltage(Volts::from_raw(raw))),
+            HwmonType::Power => Some(Self::Power(Watts::from_raw(raw))),
+            HwmonType::Temperature => Some(Self::Temperature(Celsius::from_raw(raw))),
+            _ => None,
+        }
+    }
+
+    /// Returns the value in the unit of the quantity.
+    pub fn value(self) -> f64 {
+        match self {
+            Self::Current(v) => v.value(),
+            Self::Voltage(v) => v.value(),
+            Self::Power(v) => v.value(),
+            Self::Temperature(v) => v.value(),
+        }
+    }
+
+    /// Returns the symbol of the unit, e.g., `"A"`.
+    pub fn symbol(self) -> &'static str {
+        match self {
+            Self::Current(_) => Amperes::SYMBOL,
+            Self::Voltage(_) => Volts::SYMBOL,
+            Self::Power(_) => Watts::SYMBOL,
+            Self::Temperature(_) => Celsius::SYMBOL,
+        }
+    }
+}
+
+impl Display for Quantity {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Current(v) => v.fmt(f),
+            Self::Voltage(v) => v.fmt(f),
+            Self::Power(v) => v.fmt(f),
+            Self::Temperature(v) => v.fmt(f),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_conversion() {
+        assert_eq!(Amperes::from_raw(1500), Amperes(1.5));
+        assert_eq!(Volts::from_raw(1100), Volts(1.1));
+        assert_eq!(Watts::from_raw(22_222), Watts(0.022222));
+        assert_eq!(Celsius::from_raw(-5000), Celsius(-5.0));
+
+        assert_eq!(Watts(1.5).to_raw(), 1_500_000);
+        assert_eq!(Celsius::from_raw(36123).to_raw(), 36123);
+        assert_eq!(f64::from(Volts(0.8)), 0.8);
+    }
+
+    #[test]
+    fn test_display() {
+        assert_eq!(Amperes(1.0).to_string(), "1 A");
+        assert_eq!(format!("{:.2}", Volts(1.1)), "1.10 V");
+        assert_eq!(Celsius(36.5).to_string(), "36.5 °C");
+        assert_eq!(
+            Quantity::from_raw(HwmonType::Power, 1_111)
+                .unwrap()
+                .to_string(),
+            "0.001111 W"
+        );
+    }
+
+    #[test]
+    fn test_quantity() {
+        let q = Quantity::from_raw(HwmonType::Temperature, 37000).unwrap();
+        assert_eq!(q, Quantity::Temperature(Celsius(37.0)));
+        assert_eq!(q.value(), 37.0);
+        assert_eq!(q.symbol(), "°C");
+        assert_eq!(Quantity::from_raw(HwmonType::Fan, 1200), None);
+    }
+}
@@ -23,7 +23,7 @@
 //! [`list_devices`] and [`find_devices`].
 //!
 //! 1. [`list_devices`] enumerates all Furiosa NPU devices in the system.
-//! One can simply call as below:
+//!    One can simply call as below:
 //! ```rust,ignore
 //! let devices = furiosa_device::list_devices().await?;
 //! ```
@@ -32,8 +32,8 @@
 //! device.
 //!
 //! 2. If you have a desired configuration, call [`find_devices`] with your device configuration
-//! described by a [`DeviceConfig`]. [`find_devices`] will return a list of
-//! [`DeviceFile`]s if there are matched devices.
+//!    described by a [`DeviceConfig`]. [`find_devices`] will return a list of
+//!    [`DeviceFile`]s if there are matched devices.
 //! ```rust,ignore
 //! use furiosa_device::{DeviceConfig, find_devices};
 //!
@@ -42,40 +42,96 @@
 //! let dev_files = find_devices(&config).await?;
 //! ```
 //!
+//!    [`find_devices`] returns just a snapshot, so concurrent callers may get the same device
+//!    files. To claim them exclusively among processes, call [`reserve_devices`] instead,
+//!    which keeps them reserved until the returned [`Reservation`] is dropped.
+//! ```rust,ignore
+//! let reservation = furiosa_device::reserve_devices(&config).await?;
+//! let dev_files = reservation.dev_files();
+//! ```
+//!
+//!    To wait for devices in use to be released instead of getting an empty list, call
+//!    [`find_devices_wait`] with a timeout, or [`reserve_devices_wait`] to reserve them as
+//!    well.
+//! ```rust,ignore
+//! let dev_files = furiosa_device::find_devices_wait(&config, Duration::from_secs(60)).await?;
+//! ```
+//!
+//!    To learn why device files are not found (e.g., occupied cores or a mismatched mode),
+//!    call [`find_devices_report`], which returns a [`FindReport`].
+//!
 //! 3. In case you have prior knowledge on the system and want to pick out a
-//! device with specific name, use [`get_device`].
+//!    device with specific name, use [`get_device`].
 //! ```rust,ignore
 //! let device = furiosa_device::get_device("npu0pe0").await?;
 //! ```
+//!
+//! [`list_devices`] scans devfs once. To learn that devices are added, removed or recreated
+//! afterwards (e.g., by a reload of the driver), use [`DeviceLister::watch`].
+//! Likewise, to await a core to become available, use [`Device::subscribe_status`].
+//!
+//! ## Using other devfs and sysfs roots
+//!
+//! All of the above APIs look up `/dev` and `/sys`. To enumerate devices under other roots
+//! (e.g., bind-mounted `/host/sys` in a container), use a [`DeviceLister`].
+//! ```rust,ignore
+//! use furiosa_device::DeviceLister;
+//!
+//! let lister = DeviceLister::new().devfs("/host/dev").sysfs("/host/sys");
+//! let devices = lister.list_devices().await?;
+//! ```
 
 // Allows displaying feature flags in the documentation.
 #![cfg_attr(docsrs, feature(doc_cfg))]
 
 pub use crate::arch::Arch;
-pub use crate::device::{CoreStatus, Device, DeviceFile, DeviceMode};
+pub use crate::device::{
+    CachePolicy, CoreRange, CoreStatus, Device, DeviceFile, DeviceInfo, DeviceMode, NumaNode,
+};
 pub use crate::error::{DeviceError, DeviceResult};
-use crate::find::{expand_status, find_devices_in};
-pub use crate::find::{DeviceConfig, DeviceConfigBuilder};
-use crate::list::list_devices_with;
+pub use crate::find::{
+    AllocationStrategy, DeviceConfig, DeviceConfigBuilder, FindReport, NumaPolicy, RejectReason,
+    Rejection,
+};
+pub use crate::list::DeviceLister;
+pub use crate::procfs::ProcessInfo;
+pub use crate::reserve::Reservation;
+pub use crate::status::OccupancyProbe;
+pub use crate::subscription::{CoreStatusEvent, StatusSubscription};
+pub use crate::watch::{DeviceEvent, DeviceWatcher};
 
+mod affinity;
 mod arch;
 #[cfg(feature = "blocking")]
 #[cfg_attr(docsrs, doc(cfg(feature = "blocking")))]
 pub mod blocking;
+#[cfg(all(test, feature = "blocking"))]
+mod conformance;
 mod devfs;
 mod device;
 mod error;
+#[cfg(test)]
+mod fake;
 mod find;
 pub mod hwmon;
 mod list;
+mod lock;
+#[cfg(feature = "metrics")]
+#[cfg_attr(docsrs, doc(cfg(feature = "metrics")))]
+pub mod metrics;
+mod procfs;
+mod queue;
+mod reserve;
 mod status;
-mod sysfs;
+mod subscription;
+pub mod sysfs;
+mod watch;
 
 /// List all Furiosa NPU devices in the system.
 ///
 /// See the [crate-level documentation](crate).
 pub async fn list_devices() -> DeviceResult<Vec<Device>> {
-    list_devices_with("/dev", "/sys").await
+    DeviceLister::new().list_devices().await
 }
 
 /// Find a set of devices with specific configuration.
@@ -86,8 +142,47 @@ pub async fn list_devices() -> DeviceResult<Vec<Device>> {
 ///
 /// See the [crate-level documentation](crate).
 pub async fn find_devices(config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>> {
-    let devices = expand_status(list_devices().await?).await?;
-    find_devices_in(config, &devices)
+    DeviceLister::new().find_devices(config).await
+}
+
+/// Find a set of devices with specific configuration, waiting up to `timeout` until enough
+/// device files are available.
+///
+/// See [`DeviceLister::find_devices_wait`].
+pub async fn find_devices_wait(
+    config: &DeviceConfig,
+    timeout: std::time::Duration,
+) -> DeviceResult<Vec<DeviceFile>> {
+    DeviceLister::new().find_devices_wait(config, timeout).await
+}
+
+/// Find a set of devices with specific configuration, explaining why each of the other device
+/// files is not found.
+///
+/// See [`DeviceLister::find_devices_report`].
+pub async fn find_devices_report(config: &DeviceConfig) -> DeviceResult<FindReport> {
+    DeviceLister::new().find_devices_report(config).await
+}
+
+/// Find a set of devices with specific configuration, and reserve them exclusively among
+/// processes until the returned [`Reservation`] is dropped.
+///
+/// See [`DeviceLister::reserve_devices`].
+pub async fn reserve_devices(config: &DeviceConfig) -> DeviceResult<Reservation> {
+    DeviceLister::new().reserve_devices(config).await
+}
+
+/// Find a set of devices with specific configuration, waiting up to `timeout` until enough
+/// device files are available, and reserve them exclusively among processes.
+///
+/// See [`DeviceLister::reserve_devices_wait`].
+pub async fn reserve_devices_wait(
+    config: &DeviceConfig,
+    timeout: std::time::Duration,
+) -> DeviceResult<Reservation> {
+    DeviceLister::new()
+        .reserve_devices_wait(config, timeout)
+        .await
 }
 
 /// Return a specific device if it exists.
@@ -98,23 +193,5 @@ pub async fn find_devices(config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>
 ///
 /// See the [crate-level documentation](crate).
 pub async fn get_device<S: AsRef<str>>(device_name: S) -> DeviceResult<DeviceFile> {
-    get_device_with("/dev", device_name.as_ref()).await
-}
-
-pub(crate) async fn get_device_with(devfs: &str, device_name: &str) -> DeviceResult<DeviceFile> {
-    let path = devfs::path(devfs, device_name);
-    if !path.exists() {
-        return Err(DeviceError::DeviceNotFound {
-            name: device_name.to_string(),
-        });
-    }
-
-    let file = tokio::fs::File::open(&path).await?;
-    if !devfs::is_character_device(file.metadata().await?.file_type()) {
-        return Err(DeviceError::invalid_device_file(path.display()));
-    }
-
-    devfs::parse_indices(path.file_name().expect("not a file").to_string_lossy())?;
-
-    DeviceFile::try_from(&path)
+    DeviceLister::new().get_device(device_name).await
 }
@@ -3,41 +3,423 @@ use std::fs::FileType;
 use std::io;
 
 use std::path::{Path, PathBuf};
+use std::time::{Duration, Instant};
 
 use crate::devfs;
-use crate::devfs::is_character_device;
+use crate::devfs::is_device_file;
 use tokio::fs;
 
-use crate::device::{Device, DeviceFile, DeviceInfo, DeviceMetadata};
+use crate::device::{CachePolicy, Device, DeviceFile, DeviceInfo, DeviceMetadata};
 
 use crate::error::DeviceResult;
+use crate::find::{
+    expand_status, find_devices_in, find_devices_or_wait, report_devices_in, DeviceConfig,
+    FindReport,
+};
 use crate::hwmon;
+use crate::procfs::{self, DEFAULT_PROCFS};
+use crate::queue::{self, Ticket};
+use crate::reserve::{self, Reservation, DEFAULT_RUNTIME_DIR};
+use crate::status::OccupancyProbe;
+use crate::sysfs::node;
 use crate::sysfs::npu_mgmt::{self, read_mgmt_files, *};
+use crate::watch::{self, DeviceWatcher};
+use crate::DeviceError;
 
-/// Allow to specify arbitrary sysfs, devfs paths for unit testing
-pub(crate) async fn list_devices_with(devfs: &str, sysfs: &str) -> DeviceResult<Vec<Device>> {
-    let npu_dev_files = filter_dev_files(list_devfs(devfs).await?)?;
+pub(crate) static DEFAULT_DEVFS: &str = "/dev";
+pub(crate) static DEFAULT_SYSFS: &str = "/sys";
 
-    let mut devices: Vec<Device> = Vec::with_capacity(npu_dev_files.keys().len());
+/// A builder-style lister which looks up Furiosa NPU devices under configurable
+/// devfs, sysfs and procfs roots.
+///
+/// [`list_devices`][crate::list_devices], [`find_devices`][crate::find_devices] and
+/// [`get_device`][crate::get_device] are shorthands for the default lister, which uses
+/// `/dev` and `/sys`. A lister with other roots is useful for containers with bind-mounted
+/// host directories (e.g., `/host/sys`), chroot environments, or tests with a fake sysfs.
+///
+/// # Examples
+/// ```rust,ignore
+/// use furiosa_device::DeviceLister;
+///
+/// let devices = DeviceLister::new()
+///     .devfs("/host/dev")
+///     .sysfs("/host/sys")
+///     .list_devices()
+///     .await?;
+/// ```
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DeviceLister {
+    devfs: PathBuf,
+    sysfs: PathBuf,
+    procfs: PathBuf,
+    runtime_dir: PathBuf,
+    cache_policy: CachePolicy,
+    occupancy_probe: OccupancyProbe,
+    allow_regular_files: bool,
+    local_node: Option<usize>,
+}
+
+impl DeviceLister {
+    /// Returns a lister with the default roots, `/dev`, `/sys` and `/proc`.
+    pub fn new() -> Self {
+        Self {
+            devfs: PathBuf::from(DEFAULT_DEVFS),
+            sysfs: PathBuf::from(DEFAULT_SYSFS),
+            procfs: PathBuf::from(DEFAULT_PROCFS),
+            runtime_dir: PathBuf::from(DEFAULT_RUNTIME_DIR),
+            cache_policy: CachePolicy::default(),
+            occupancy_probe: OccupancyProbe::default(),
+            allow_regular_files: false,
+            local_node: None,
+        }
+    }
+
+    /// Sets the root directory of devfs (default: `/dev`).
+    pub fn devfs<P: Into<PathBuf>>(mut self, devfs: P) -> Self {
+        self.devfs = devfs.into();
+        self
+    }
+
+    /// Sets the root directory of sysfs (default: `/sys`).
+    pub fn sysfs<P: Into<PathBuf>>(mut self, sysfs: P) -> Self {
+        self.sysfs = sysfs.into();
+        self
+    }
+
+    /// Sets the root directory of procfs (default: `/proc`), which is scanned to identify
+    /// processes occupying cores of listed devices.
+    pub fn procfs<P: Into<PathBuf>>(mut self, procfs: P) -> Self {
+        self.procfs = procfs.into();
+        self
+    }
+
+    /// Sets the directory of lock files for [`reserve_devices`][Self::reserve_devices]
+    /// (default: `/run/furiosa`). Processes reserving devices from each other must share it.
+    ///
+    /// The directory is created if missing. Only root can create the default one, so
+    /// unprivileged processes should set a directory writable by all of them.
+    pub fn runtime_dir<P: Into<PathBuf>>(mut self, runtime_dir: P) -> Self {
+        self.runtime_dir = runtime_dir.into();
+        self
+    }
+
+    /// Sets the cache policy of volatile mgmt attributes for listed devices
+    /// (default: [`CachePolicy::Never`]).
+    pub fn cache_policy(mut self, cache_policy: CachePolicy) -> Self {
+        self.cache_policy = cache_policy;
+        self
+    }
+
+    /// Sets how to examine whether device files of listed devices are occupied
+    /// (default: [`OccupancyProbe::Open`]).
+    pub fn occupancy_probe(mut self, probe: OccupancyProbe) -> Self {
+        self.occupancy_probe = probe;
+        self
+    }
 
-    for (idx, paths) in npu_dev_files {
-        if is_furiosa_device(idx, sysfs).await {
-            let mgmt_files = read_mgmt_files(sysfs, idx)?;
-            let device_meta = DeviceMetadata::try_from(mgmt_files)?;
-            let device_info =
-                DeviceInfo::new(idx, PathBuf::from(devfs), PathBuf::from(sysfs), device_meta);
+    /// Replaces [`OccupancyProbe::Open`], which takes available device files briefly, with
+    /// [`OccupancyProbe::Procfs`] for callers examining cores over and over.
+    #[cfg(feature = "metrics")]
+    pub(crate) fn non_intrusive(self) -> Self {
+        let probe = self.occupancy_probe.non_intrusive();
+        self.occupancy_probe(probe)
+    }
 
-            // Since busname is a required field, it is guaranteed to exist.
-            let busname = device_info.get(npu_mgmt::BUSNAME).unwrap();
-            let hwmon_fetcher = crate::hwmon::Fetcher::new(sysfs, idx, &busname).await?;
+    /// Accepts regular files under the devfs root as device files (default: `false`).
+    ///
+    /// It allows to use a fake devfs, e.g., for testing.
+    pub fn allow_regular_files(mut self, allow: bool) -> Self {
+        self.allow_regular_files = allow;
+        self
+    }
+
+    /// Sets the local NUMA node for [`NumaPolicy::SameNode`][crate::NumaPolicy::SameNode] and
+    /// [`NumaPolicy::PreferLocal`][crate::NumaPolicy::PreferLocal].
+    ///
+    /// By default, it is the node of the CPU which the calling thread runs on, read from
+    /// procfs and sysfs. An async task may run on any worker thread of a multi-threaded
+    /// runtime, so callers that will run their work elsewhere, e.g., on a thread pinned with
+    /// [`Device::pin_current_thread`], should set the node explicitly.
+    pub fn local_node(mut self, node: usize) -> Self {
+        self.local_node = Some(node);
+        self
+    }
+
+    /// Returns the root directory of devfs.
+    pub fn devfs_root(&self) -> &Path {
+        &self.devfs
+    }
+
+    /// Returns the root directory of sysfs.
+    pub fn sysfs_root(&self) -> &Path {
+        &self.sysfs
+    }
+
+    /// Returns the root directory of procfs.
+    pub fn procfs_root(&self) -> &Path {
+        &self.procfs
+    }
 
-            let device = collect_devices(device_info, hwmon_fetcher, paths)?;
-            devices.push(device);
+    /// Returns the directory of lock files for reservations.
+    pub fn runtime_dir_path(&self) -> &Path {
+        &self.runtime_dir
+    }
+
+    /// List all Furiosa NPU devices under the roots.
+    pub async fn list_devices(&self) -> DeviceResult<Vec<Device>> {
+        let npu_dev_files = self.npu_dev_files(list_devfs(&self.devfs).await?)?;
+
+        let mut devices: Vec<Device> = Vec::with_capacity(npu_dev_files.keys().len());
+
+        for (idx, paths) in npu_dev_files {
+            if is_furiosa_device(idx, &self.sysfs).await {
+                let device_info = self.device_info(idx)?;
+                // Since busname is a required field, it is guaranteed to exist.
+                let busname = device_info.get(npu_mgmt::BUSNAME).unwrap();
+                let hwmon_fetcher = hwmon::Fetcher::new(&self.sysfs, idx, &busname).await?;
+
+                let device = collect_devices(device_info, hwmon_fetcher, paths)?;
+                devices.push(device);
+            }
         }
+
+        devices.sort();
+        Ok(devices)
     }
 
-    devices.sort();
-    Ok(devices)
+    /// Find a set of devices with specific configuration under the roots.
+    pub async fn find_devices(&self, config: &DeviceConfig) -> DeviceResult<Vec<DeviceFile>> {
+        let devices = expand_status(self.list_devices().await?).await?;
+        find_devices_in(config, &devices, self.local_numa_node(config))
+    }
+
+    /// Find a set of devices with specific configuration under the roots, explaining why
+    /// each of the other device files is not found.
+    ///
+    /// # Examples
+    /// ```rust,ignore
+    /// let report = DeviceLister::new().find_devices_report(&config).await?;
+    /// if !report.is_satisfied() {
+    ///     // e.g., "npu0pe0-1: core 0 is occupied by npu0pe0 (pid 1234)"
+    ///     eprintln!("{}", report);
+    /// }
+    /// ```
+    pub async fn find_devices_report(&self, config: &DeviceConfig) -> DeviceResult<FindReport> {
+        let devices = expand_status(self.list_devices().await?).await?;
+        Ok(report_devices_in(
+            config,
+            &devices,
+            self.local_numa_node(config),
+        ))
+    }
+
+    /// Find a set of devices with specific configuration under the roots, waiting until
+    /// enough device files are available.
+    ///
+    /// Waiters in this process are served in the order of their calls, so a waiter for many
+    /// cores is not overtaken by later waiters for fewer cores which may share them. The order
+    /// is not kept among processes. The found device files are a snapshot as with
+    /// [`find_devices`][Self::find_devices]; use
+    /// [`reserve_devices_wait`][Self::reserve_devices_wait] to keep others from taking them.
+    ///
+    /// An empty list is returned at once if the config cannot be satisfied even when all
+    /// cores are available. [`DeviceError::WaitTimedOut`] is returned if enough device files
+    /// are not available within the timeout.
+    pub async fn find_devices_wait(
+        &self,
+        config: &DeviceConfig,
+        timeout: Duration,
+    ) -> DeviceResult<Vec<DeviceFile>> {
+        let deadline = Instant::now() + timeout;
+        let devfs = fs::canonicalize(&self.devfs)
+            .await
+            .unwrap_or_else(|_| self.devfs.clone());
+        let ticket = Ticket::take(devfs, config);
+        loop {
+            if ticket.is_turn() {
+                let mut devices = expand_status(self.list_devices().await?).await?;
+                let local_node = self.local_numa_node(config);
+                if let Some(found) = find_devices_or_wait(config, &mut devices, local_node)? {
+                    return Ok(found);
+                }
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(DeviceError::WaitTimedOut {
+                    config: config.to_string(),
+                });
+            };
+            tokio::time::sleep(remaining.min(queue::POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Find a set of devices with specific configuration under the roots, waiting until
+    /// enough device files are available, and reserve them as
+    /// [`reserve_devices`][Self::reserve_devices] does.
+    ///
+    /// Waiters in this process are served in the order of their calls, so a waiter for many
+    /// cores is not overtaken by later waiters for fewer cores which may share them. The order
+    /// is not kept among processes; a waiter in another process may take device files first.
+    ///
+    /// [`DeviceError::DeviceNotFound`] is returned at once if the config cannot be satisfied
+    /// even when all cores are available. [`DeviceError::WaitTimedOut`] is returned if enough
+    /// device files are not available within the timeout.
+    pub async fn reserve_devices_wait(
+        &self,
+        config: &DeviceConfig,
+        timeout: Duration,
+    ) -> DeviceResult<Reservation> {
+        let deadline = Instant::now() + timeout;
+        let devfs = fs::canonicalize(&self.devfs)
+            .await
+            .unwrap_or_else(|_| self.devfs.clone());
+        // The ticket is held until the device files are claimed.
+        let ticket = Ticket::take(devfs, config);
+        loop {
+            if ticket.is_turn() {
+                let mut devices =
+                    reserve::expand_status(self.list_devices().await?, &self.runtime_dir).await?;
+                let local_node = self.local_numa_node(config);
+                match find_devices_or_wait(config, &mut devices, local_node)? {
+                    Some(found) if found.is_empty() => {
+                        return Err(DeviceError::DeviceNotFound {
+                            name: config.to_string(),
+                        })
+                    }
+                    Some(found) => {
+                        let claimed = reserve::claim(&self.runtime_dir, &devices, found).await?;
+                        if let Some(reservation) = claimed {
+                            return Ok(reservation);
+                        }
+                    }
+                    None => {}
+                }
+            }
+            let Some(remaining) = deadline.checked_duration_since(Instant::now()) else {
+                return Err(DeviceError::WaitTimedOut {
+                    config: config.to_string(),
+                });
+            };
+            tokio::time::sleep(remaining.min(queue::POLL_INTERVAL)).await;
+        }
+    }
+
+    /// Find a set of devices with specific configuration under the roots, and reserve them
+    /// exclusively among processes sharing the [`runtime_dir`][Self::runtime_dir].
+    ///
+    /// Device files reserved by others are regarded as occupied. If no devices match,
+    /// [`DeviceError::DeviceNotFound`] is returned.
+    pub async fn reserve_devices(&self, config: &DeviceConfig) -> DeviceResult<Reservation> {
+        for _ in 0..reserve::CLAIM_ATTEMPTS {
+            let devices =
+                reserve::expand_status(self.list_devices().await?, &self.runtime_dir).await?;
+            let found = find_devices_in(config, &devices, self.local_numa_node(config))?;
+            if found.is_empty() {
+                break;
+            }
+            if let Some(reservation) = reserve::claim(&self.runtime_dir, &devices, found).await? {
+                return Ok(reservation);
+            }
+        }
+        Err(DeviceError::DeviceNotFound {
+            name: config.to_string(),
+        })
+    }
+
+    /// Watches devfs for devices being added, removed or changed, relative to the devices
+    /// present now.
+    ///
+    /// # Examples
+    /// ```rust,ignore
+    /// use futures::StreamExt;
+    ///
+    /// let mut watcher = DeviceLister::new().watch()?;
+    /// while let Some(event) = watcher.next().await {
+    ///     println!("{:?}", event?);
+    /// }
+    /// ```
+    pub fn watch(&self) -> DeviceResult<DeviceWatcher> {
+        watch::watch(self)
+    }
+
+    /// Return a specific device under the devfs root if it exists.
+    pub async fn get_device<S: AsRef<str>>(&self, device_name: S) -> DeviceResult<DeviceFile> {
+        let path = self.device_path(device_name.as_ref())?;
+        let file = tokio::fs::File::open(&path).await?;
+        self.to_device_file(&path, file.metadata().await?.file_type())
+    }
+
+    /// Groups device files in devfs by device index. It is shared by the async and blocking
+    /// listers, as are the other `pub(crate)` methods below.
+    pub(crate) fn npu_dev_files(
+        &self,
+        dev_files: Vec<DevFile>,
+    ) -> DeviceResult<HashMap<u8, Vec<PathBuf>>> {
+        filter_dev_files(dev_files, self.allow_regular_files)
+    }
+
+    pub(crate) fn device_info(&self, idx: u8) -> DeviceResult<DeviceInfo> {
+        let mgmt_files = read_mgmt_files(&self.sysfs, idx)?;
+        let device_meta = DeviceMetadata::try_from(mgmt_files)?;
+        let mut device_info =
+            DeviceInfo::new(idx, self.devfs.clone(), self.sysfs.clone(), device_meta);
+        device_info.set_cache_policy(self.cache_policy);
+        device_info.set_proc_root(self.procfs.clone());
+        device_info.set_occupancy_probe(self.occupancy_probe.clone());
+        Ok(device_info)
+    }
+
+    /// Returns the local NUMA node set by [`local_node`][Self::local_node], or the node of the
+    /// CPU which the calling thread runs on, if the config refers to it.
+    ///
+    /// procfs and sysfs are read in place even by the async lister, since another thread may
+    /// run on another CPU. They are a couple of small files in memory, which never block long.
+    pub(crate) fn local_numa_node(&self, config: &DeviceConfig) -> Option<usize> {
+        if !config.numa_policy().uses_local_node() {
+            return None;
+        }
+        if self.local_node.is_some() {
+            return self.local_node;
+        }
+        let node =
+            procfs::current_cpu(&self.procfs).and_then(|cpu| node::node_of_cpu(&self.sysfs, cpu));
+        match node {
+            Ok(node) => node,
+            Err(e) => {
+                tracing::debug!("Failed to find the NUMA node of the current CPU: {}", e);
+                None
+            }
+        }
+    }
+
+    pub(crate) fn device_path(&self, device_name: &str) -> DeviceResult<PathBuf> {
+        let path = devfs::path(&self.devfs, device_name);
+        if !path.exists() {
+            return Err(DeviceError::DeviceNotFound {
+                name: device_name.to_string(),
+            });
+        }
+        Ok(path)
+    }
+
+    pub(crate) fn to_device_file(
+        &self,
+        path: &Path,
+        file_type: FileType,
+    ) -> DeviceResult<DeviceFile> {
+        if !devfs::is_device_file(file_type, self.allow_regular_files) {
+            return Err(DeviceError::invalid_device_file(path.display()));
+        }
+
+        devfs::parse_indices(path.file_name().expect("not a file").to_string_lossy())?;
+
+        DeviceFile::try_from(&path.to_path_buf())
+    }
+}
+
+impl Default for DeviceLister {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 pub(crate) fn collect_devices(
@@ -81,11 +463,28 @@ async fn list_devfs<P: AsRef<Path>>(devfs: P) -> io::Result<Vec<DevFile>> {
     Ok(dev_files)
 }
 
-pub(crate) fn filter_dev_files(dev_files: Vec<DevFile>) -> DeviceResult<HashMap<u8, Vec<PathBuf>>> {
+pub(crate) fn list_devfs_blocking<P: AsRef<Path>>(devfs: P) -> io::Result<Vec<DevFile>> {
+    let mut dev_files = Vec::new();
+
+    for entry in std::fs::read_dir(devfs)? {
+        let file = entry?;
+        dev_files.push(DevFile {
+            path: file.path(),
+            file_type: file.file_type()?,
+        });
+    }
+
+    Ok(dev_files)
+}
+
+fn filter_dev_files(
+    dev_files: Vec<DevFile>,
+    allow_regular_files: bool,
+) -> DeviceResult<HashMap<u8, Vec<PathBuf>>> {
     let mut npu_dev_files: HashMap<u8, Vec<PathBuf>> = HashMap::new();
 
     for dev_file in dev_files {
-        if is_character_device(dev_file.file_type) {
+        if is_device_file(dev_file.file_type, allow_regular_files) {
             let path = &dev_file.path;
             let filename = path
                 .file_name()
@@ -96,7 +495,7 @@ pub(crate) fn filter_dev_files(dev_files: Vec<DevFile>) -> DeviceResult<HashMap<
             if let Ok((device_id, _)) = devfs::parse_indices(&filename) {
                 npu_dev_files
                     .entry(device_id)
-                    .or_insert_with(Vec::new)
+                    .or_default()
                     .push(path.canonicalize()?); // make an absolute path
             }
         }
@@ -105,23 +504,32 @@ pub(crate) fn filter_dev_files(dev_files: Vec<DevFile>) -> DeviceResult<HashMap<
     Ok(npu_dev_files)
 }
 
-async fn is_furiosa_device(idx: u8, sysfs: &str) -> bool {
-    fs::read_to_string(npu_mgmt::path(&sysfs, PLATFORM_TYPE, idx))
+async fn is_furiosa_device<P: AsRef<Path>>(idx: u8, sysfs: P) -> bool {
+    fs::read_to_string(npu_mgmt::path(sysfs, PLATFORM_TYPE, idx))
         .await
         .ok()
         .filter(|c| npu_mgmt::is_furiosa_platform(c))
         .is_some()
 }
 
+#[cfg(feature = "blocking")]
+pub(crate) fn is_furiosa_device_blocking<P: AsRef<Path>>(idx: u8, sysfs: P) -> bool {
+    std::fs::read_to_string(npu_mgmt::path(sysfs, PLATFORM_TYPE, idx))
+        .ok()
+        .filter(|c| npu_mgmt::is_furiosa_platform(c))
+        .is_some()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
     use crate::arch::Arch;
+    use crate::fake::test_lister;
     use itertools::Itertools;
 
     #[tokio::test]
     async fn test_find_dev_files() -> DeviceResult<()> {
-        let dev_files = filter_dev_files(list_devfs("test_data/test-0/dev").await?)?;
+        let dev_files = filter_dev_files(list_devfs("test_data/test-0/dev").await?, true)?;
         assert_eq!(
             dev_files.keys().copied().sorted().collect::<Vec<u8>>(),
             vec![0, 1]
@@ -143,6 +551,45 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn test_device_lister() -> DeviceResult<()> {
+        let lister = test_lister();
+
+        let devices = lister.list_devices().await?;
+        assert_eq!(
+            devices.iter().map(|d| d.name()).collect::<Vec<_>>(),
+            vec!["npu0", "npu1"]
+        );
+        assert_eq!(
+            devices[0]
+                .dev_files()
+                .iter()
+                .map(|f| f.filename())
+                .collect::<Vec<_>>(),
+            vec!["npu0", "npu0pe0", "npu0pe1", "npu0pe0-1"]
+        );
+
+        let dev_file = lister.get_device("npu1pe0-1").await?;
+        assert_eq!(dev_file.device_index(), 1);
+        assert!(matches!(
+            lister.get_device("npu9").await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+
+        let found = lister
+            .find_devices(&DeviceConfig::warboy().fused().count(2))
+            .await?;
+        assert_eq!(found.len(), 2);
+
+        // No devices can be found under an empty devfs root
+        let empty = DeviceLister::new()
+            .devfs("test_data/test-0/sys")
+            .sysfs("test_data/test-0/sys");
+        assert!(empty.list_devices().await?.is_empty());
+
+        Ok(())
+    }
+
     #[test]
     fn test_identify_arch() -> DeviceResult<()> {
         assert_eq!(
@@ -0,0 +1,22 @@
+//! Advisory locks (`flock(2)`) of lock files, shared by the lock file probe and reservations.
+
+use std::fs::File;
+use std::io;
+use std::os::unix::io::AsRawFd;
+
+/// Takes the exclusive lock of the file without blocking, returning whether it is taken.
+///
+/// The lock is released when the file is closed.
+pub(crate) fn try_lock_exclusive(file: &File) -> io::Result<bool> {
+    // SAFETY: the file descriptor is valid while `file` is borrowed.
+    let ret = unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX | libc::LOCK_NB) };
+    if ret == 0 {
+        return Ok(true);
+    }
+    let err = io::Error::last_os_error();
+    if err.raw_os_error() == Some(libc::EWOULDBLOCK) {
+        Ok(false)
+    } else {
+        Err(err)
+    }
+}
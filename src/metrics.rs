@@ -0,0 +1,369 @@
+//! An exporter that serves health and hwmon metrics of NPU devices in the
+//! [OpenMetrics](https://openmetrics.io) text format.
+//!
+//! Every scrape lists devices again through a [`DeviceLister`], so devices added or removed
+//! while the exporter is running are reflected in the next scrape.
+//!
+//! ```rust,ignore
+//! use furiosa_device::metrics::Exporter;
+//! use furiosa_device::DeviceLister;
+//!
+//! let listener = tokio::net::TcpListener::bind("127.0.0.1:9254").await?;
+//! Exporter::new(DeviceLister::new()).serve(listener).await?;
+//! ```
+
+use std::collections::BTreeMap;
+use std::convert::Infallible;
+use std::fmt::Write;
+use std::sync::Arc;
+use std::time::Duration;
+
+use hyper::header::CONTENT_TYPE;
+use hyper::server::conn::Http;
+use hyper::service::service_fn;
+use hyper::{Body, Method, Request, Response, StatusCode};
+use tokio::net::TcpListener;
+
+use crate::hwmon::{Amperes, Celsius, Reading, Unit, Volts, Watts};
+use crate::{CoreStatus, Device, DeviceLister, DeviceResult};
+
+/// How long to wait before accepting connections again after failing to, at first and at most.
+const ACCEPT_BACKOFF: (Duration, Duration) = (Duration::from_millis(10), Duration::from_secs(1));
+
+/// The content type of the OpenMetrics text format.
+pub const CONTENT_TYPE_OPENMETRICS: &str =
+    "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
+/// The path where metrics are served.
+pub const METRICS_PATH: &str = "/metrics";
+
+/// Serves metrics of the devices found by a [`DeviceLister`].
+#[derive(Debug, Clone)]
+pub struct Exporter {
+    lister: DeviceLister,
+}
+
+impl Default for Exporter {
+    fn default() -> Self {
+        Self::new(DeviceLister::new())
+    }
+}
+
+impl Exporter {
+    /// Creates an exporter of the devices found by `lister`.
+    ///
+    /// Since every scrape examines whether cores are occupied,
+    /// [`OccupancyProbe::Open`][crate::OccupancyProbe::Open] of the lister, which takes
+    /// available devices briefly, is replaced with
+    /// [`OccupancyProbe::Procfs`][crate::OccupancyProbe::Procfs].
+    pub fn new(lister: DeviceLister) -> Self {
+        Self {
+            lister: lister.non_intrusive(),
+        }
+    }
+
+    /// Lists devices and renders their metrics in the OpenMetrics text format.
+    pub async fn render(&self) -> DeviceResult<String> {
+        let devices = self.lister.list_devices().await?;
+        Ok(render(&devices).await)
+    }
+
+    /// Accepts connections from `listener` and serves [`METRICS_PATH`] until dropped.
+    ///
+    /// A failure to accept a connection (e.g., running out of file descriptors) is logged,
+    /// and connections are accepted again after a backoff.
+    pub async fn serve(self, listener: TcpListener) -> DeviceResult<()> {
+        let exporter = Arc::new(self);
+        let mut backoff = ACCEPT_BACKOFF.0;
+        loop {
+            let (stream, peer) = match listener.accept().await {
+                Ok(accepted) => {
+                    backoff = ACCEPT_BACKOFF.0;
+                    accepted
+                }
+                Err(e) => {
+                    tracing::warn!("Failed to accept a connection: {}", e);
+                    tokio::time::sleep(backoff).await;
+                    backoff = (backoff * 2).min(ACCEPT_BACKOFF.1);
+                    continue;
+                }
+            };
+            let exporter = exporter.clone();
+            tokio::spawn(async move {
+                let service = service_fn(move |req| {
+                    let exporter = exporter.clone();
+                    async move { Ok::<_, Infallible>(exporter.handle(req).await) }
+                });
+                if let Err(e) = Http::new()
+                    .http1_only(true)
+                    .serve_connection(stream, service)
+                    .await
+                {
+                    tracing::debug!("Connection from {} closed: {}", peer, e);
+                }
+            });
+        }
+    }
+
+    async fn handle(&self, req: Request<Body>) -> Response<Body> {
+        if req.uri().path() != METRICS_PATH {
+            return text_response(StatusCode::NOT_FOUND, "Not Found\n".to_string());
+        }
+        if req.method() != Method::GET {
+            return text_response(
+                StatusCode::METHOD_NOT_ALLOWED,
+                "Method Not Allowed\n".to_string(),
+            );
+        }
+
+        match self.render().await {
+            Ok(body) => Response::builder()
+                .header(CONTENT_TYPE, CONTENT_TYPE_OPENMETRICS)
+                .body(Body::from(body))
+                .unwrap(),
+            Err(e) => {
+                tracing::error!("Failed to list devices: {}", e);
+                text_response(StatusCode::INTERNAL_SERVER_ERROR, format!("{}\n", e))
+            }
+        }
+    }
+}
+
+fn text_response(status: StatusCode, body: String) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "text/plain; charset=utf-8")
+        .body(Body::from(body))
+        .unwrap()
+}
+
+/// Renders metrics of the given devices in the OpenMetrics text format.
+///
+/// A value that cannot be read from a device is left out and logged, rather than failing
+/// the whole scrape.
+pub async fn render(devices: &[Device]) -> String {
+    let mut alive = Family::new(
+        "furiosa_npu_alive",
+        "Whether the device is alive (1) or not (0).",
+    );
+    let mut heartbeat = Family::new("furiosa_npu_heartbeat", "Heartbeat counter of the device.");
+    let mut currents = Family::new("furiosa_npu_hwmon_current_amperes", "Current in amperes.");
+    let mut voltages = Family::new("furiosa_npu_hwmon_voltage_volts", "Voltage in volts.");
+    let mut powers = Family::new(
+        "furiosa_npu_hwmon_power_average_watts",
+        "Average power in watts.",
+    );
+    let mut temperatures = Family::new(
+        "furiosa_npu_hwmon_temperature_celsius",
+        "Temperature in degrees Celsius.",
+    );
+    let mut occupied = Family::new(
+        "furiosa_npu_core_occupied",
+        "Whether the core is occupied (1) or not (0).",
+    );
+
+    for device in devices {
+        let labels = device_labels(device);
+
+        match device.alive() {
+            Ok(value) => alive.push(&labels, &[], f64::from(u8::from(value))),
+            Err(e) => tracing::warn!("Failed to read alive of {}: {}", device, e),
+        }
+        match device.heartbeat() {
+            Ok(value) => heartbeat.push(&labels, &[], f64::from(value)),
+            Err(e) => tracing::warn!("Failed to read heartbeat of {}: {}", device, e),
+        }
+
+        let fetcher = device.get_hwmon_fetcher();
+        push_sensors(&mut currents, &labels, fetcher.read::<Amperes>().await);
+        push_sensors(&mut voltages, &labels, fetcher.read::<Volts>().await);
+        push_sensors(&mut powers, &labels, fetcher.read::<Watts>().await);
+        push_sensors(&mut temperatures, &labels, fetcher.read::<Celsius>().await);
+
+        match device.get_status_all().await {
+            Ok(statuses) => {
+                for (core, status) in statuses.into_iter().collect::<BTreeMap<_, _>>() {
+                    let value = matches!(status, CoreStatus::Occupied(_));
+                    occupied.push(
+                        &labels,
+                        &[("core", core.to_string())],
+                        f64::from(u8::from(value)),
+                    );
+                }
+            }
+            Err(e) => tracing::warn!("Failed to read core status of {}: {}", device, e),
+        }
+    }
+
+    let mut out = String::new();
+    for family in [
+        alive,
+        heartbeat,
+        currents,
+        voltages,
+        powers,
+        temperatures,
+        occupied,
+    ] {
+        family.encode(&mut out);
+    }
+    out.push_str("# EOF\n");
+    out
+}
+
+fn device_labels(device: &Device) -> Vec<(&'static str, String)> {
+    vec![
+        ("device", device.device_index().to_string()),
+        ("arch", device.arch().to_string()),
+        ("busname", device.busname().unwrap_or_default()),
+    ]
+}
+
+fn push_sensors<U: Unit>(
+    family: &mut Family,
+    labels: &[(&'static str, String)],
+    readings: DeviceResult<Vec<Reading<U>>>,
+) {
+    match readings {
+        Ok(readings) => {
+            for Reading { label, value } in readings {
+                family.push(labels, &[("label", label)], value.value());
+            }
+        }
+        Err(e) => tracing::warn!("Failed to read {}: {}", family.name, e),
+    }
+}
+
+/// A gauge metric family and its samples.
+struct Family {
+    name: &'static str,
+    help: &'static str,
+    samples: Vec<(Vec<(&'static str, String)>, f64)>,
+}
+
+impl Family {
+    fn new(name: &'static str, help: &'static str) -> Self {
+        Self {
+            name,
+            help,
+            samples: vec![],
+        }
+    }
+
+    fn push(
+        &mut self,
+        labels: &[(&'static str, String)],
+        extra: &[(&'static str, String)],
+        value: f64,
+    ) {
+        let labels = labels.iter().chain(extra).cloned().collect();
+        self.samples.push((labels, value));
+    }
+
+    fn encode(&self, out: &mut String) {
+        let _ = writeln!(out, "# TYPE {} gauge", self.name);
+        let _ = writeln!(out, "# HELP {} {}", self.name, self.help);
+        for (labels, value) in &self.samples {
+            let labels = labels
+                .iter()
+                .map(|(k, v)| format!("{}=\"{}\"", k, escape_label_value(v)))
+                .collect::<Vec<_>>()
+                .join(",");
+            let _ = writeln!(out, "{}{{{}}} {}", self.name, labels, value);
+        }
+    }
+}
+
+fn escape_label_value(value: &str) -> String {
+    value
+        .replace('\\', r"\\")
+        .replace('"', "\\\"")
+        .replace('\n', r"\n")
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpStream;
+
+    use super::*;
+    use crate::fake::test_lister;
+
+    fn exporter() -> Exporter {
+        Exporter::new(test_lister())
+    }
+
+    async fn scrape(addr: std::net::SocketAddr, path: &str) -> std::io::Result<String> {
+        let mut stream = TcpStream::connect(addr).await?;
+        let request = format!(
+            "GET {} HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n",
+            path
+        );
+        stream.write_all(request.as_bytes()).await?;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await?;
+        Ok(response)
+    }
+
+    #[tokio::test]
+    async fn test_render() -> DeviceResult<()> {
+        let text = exporter().render().await?;
+        let lines: Vec<_> = text.lines().collect();
+
+        assert!(lines.contains(
+            &"furiosa_npu_alive{device=\"0\",arch=\"warboy\",busname=\"0000:6d:00.0\"} 1"
+        ));
+        assert!(lines.contains(
+            &"furiosa_npu_heartbeat{device=\"0\",arch=\"warboy\",busname=\"0000:6d:00.0\"} 3600"
+        ));
+        assert!(!text.contains("furiosa_npu_alive{device=\"1\""));
+        assert!(lines.contains(&"furiosa_npu_hwmon_temperature_celsius{device=\"0\",arch=\"warboy\",busname=\"0000:6d:00.0\",label=\"Temp1\"} 36"));
+        assert!(lines.contains(&"furiosa_npu_hwmon_power_average_watts{device=\"0\",arch=\"warboy\",busname=\"0000:6d:00.0\",label=\"Power1\"} 0.001111"));
+        assert!(lines.contains(&"furiosa_npu_core_occupied{device=\"1\",arch=\"warboy\",busname=\"0000:ff:00.0\",core=\"1\"} 0"));
+        assert_eq!(
+            lines
+                .iter()
+                .filter(|l| l.starts_with("furiosa_npu_core_occupied{"))
+                .count(),
+            4
+        );
+        assert_eq!(lines.last(), Some(&"# EOF"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_occupancy_probe() {
+        use crate::OccupancyProbe;
+
+        assert_eq!(
+            Exporter::default().lister,
+            DeviceLister::new().occupancy_probe(OccupancyProbe::Procfs)
+        );
+        let lister = DeviceLister::new().occupancy_probe(OccupancyProbe::Sysfs);
+        assert_eq!(Exporter::new(lister.clone()).lister, lister);
+    }
+
+    #[test]
+    fn test_escape_label_value() {
+        assert_eq!(escape_label_value("a\"b\\c\nd"), r#"a\"b\\c\nd"#);
+    }
+
+    #[tokio::test]
+    async fn test_serve() -> Result<(), Box<dyn std::error::Error>> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        tokio::spawn(exporter().serve(listener));
+
+        let response = scrape(addr, METRICS_PATH).await?;
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.contains(CONTENT_TYPE_OPENMETRICS));
+        assert!(response.contains("# TYPE furiosa_npu_heartbeat gauge"));
+        assert!(response.trim_end().ends_with("# EOF"));
+
+        let response = scrape(addr, "/").await?;
+        assert!(response.starts_with("HTTP/1.1 404 Not Found"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,298 @@
+//! Identifies processes holding device files, by scanning file descriptors under procfs.
+
+use std::collections::HashMap;
+use std::fmt::{self, Display, Formatter};
+use std::fs::{self, Metadata};
+use std::io;
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+use lazy_static::lazy_static;
+use regex::Regex;
+
+pub(crate) static DEFAULT_PROCFS: &str = "/proc";
+
+lazy_static! {
+    // Container runtimes name cgroups after 64 hex digits of container IDs, e.g.,
+    // `/docker/<id>`, `/system.slice/docker-<id>.scope` or `/kubepods/.../cri-containerd-<id>.scope`.
+    static ref CONTAINER_ID_PATTERN: Regex = Regex::new(r"[0-9a-f]{64}").unwrap();
+}
+
+/// A process which holds a device file open.
+///
+/// With the `serde` feature, it is serialized as
+/// `{"pid": 1234, "cmdline": ["python", "serve.py"], "uid": 1000, "cgroup": "/docker/..."}`.
+#[derive(Debug, Clone, Eq, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct ProcessInfo {
+    pub pid: u32,
+    /// Arguments of the command line, which is empty for zombie processes.
+    pub cmdline: Vec<String>,
+    /// Real user ID of the process.
+    pub uid: u32,
+    /// Path of the cgroup of the process, from the cgroup v2 hierarchy if available.
+    pub cgroup: Option<String>,
+}
+
+impl ProcessInfo {
+    /// Returns the ID of the container in which the process runs, if its cgroup is named
+    /// after it as Docker, containerd and CRI-O do.
+    pub fn container_id(&self) -> Option<&str> {
+        let cgroup = self.cgroup.as_deref()?;
+        CONTAINER_ID_PATTERN
+            .find_iter(cgroup)
+            .last()
+            .map(|m| m.as_str())
+    }
+}
+
+impl Display for ProcessInfo {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "pid {}", self.pid)
+    }
+}
+
+/// Returns the CPU which the calling thread ran on most recently.
+pub(crate) fn current_cpu(proc_root: &Path) -> io::Result<usize> {
+    let stat = fs::read_to_string(proc_root.join("thread-self/stat"))?;
+    parse_processor(&stat)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidData, "no processor in the stat"))
+}
+
+/// Parses `processor`, the 39th field of `/proc/[pid]/stat`. The 2nd field, the command name
+/// in parentheses, may contain spaces.
+fn parse_processor(stat: &str) -> Option<usize> {
+    let (_, fields) = stat.rsplit_once(')')?;
+    fields.split_whitespace().nth(36)?.parse().ok()
+}
+
+/// Identity of a file, which is the same for all paths and file descriptors to the file.
+#[derive(Debug, Clone, Copy, Eq, PartialEq, Hash)]
+enum FileKey {
+    /// Device number of a character device, even if its node lives in another devfs
+    /// (e.g., of a container).
+    Device(u64),
+    Inode(u64, u64),
+}
+
+impl From<&Metadata> for FileKey {
+    fn from(meta: &Metadata) -> Self {
+        if meta.file_type().is_char_device() {
+            Self::Device(meta.rdev())
+        } else {
+            Self::Inode(meta.dev(), meta.ino())
+        }
+    }
+}
+
+/// Finds processes holding each of the given device files.
+///
+/// Processes which exit during the scan or whose file descriptors are not accessible
+/// (e.g., of other users without privileges) are skipped.
+pub(crate) async fn find_processes(
+    proc_root: &Path,
+    paths: Vec<PathBuf>,
+) -> HashMap<PathBuf, Vec<ProcessInfo>> {
+    if paths.is_empty() {
+        return HashMap::new();
+    }
+    let proc_root = proc_root.to_path_buf();
+    tokio::task::spawn_blocking(move || find_processes_blocking(&proc_root, paths))
+        .await
+        .unwrap_or_default()
+}
+
+/// Merges processes holding any of the device files, in the order of their PIDs.
+pub(crate) fn merge_processes(processes: HashMap<PathBuf, Vec<ProcessInfo>>) -> Vec<ProcessInfo> {
+    let mut merged: Vec<ProcessInfo> = processes.into_values().flatten().collect();
+    merged.sort_by_key(|process| process.pid);
+    merged.dedup_by_key(|process| process.pid);
+    merged
+}
+
+pub(crate) fn find_processes_blocking(
+    proc_root: &Path,
+    paths: Vec<PathBuf>,
+) -> HashMap<PathBuf, Vec<ProcessInfo>> {
+    if paths.is_empty() {
+        return HashMap::new();
+    }
+    let keys: HashMap<FileKey, PathBuf> = paths
+        .into_iter()
+        .filter_map(|path| Some((FileKey::from(&fs::metadata(&path).ok()?), path)))
+        .collect();
+
+    let mut processes: HashMap<PathBuf, Vec<ProcessInfo>> = HashMap::new();
+    let pids = match list_pids(proc_root) {
+        Ok(pids) => pids,
+        Err(e) => {
+            tracing::warn!("Failed to scan {}: {}", proc_root.display(), e);
+            return processes;
+        }
+    };
+    for pid in pids {
+        let proc_dir = proc_root.join(pid.to_string());
+        let Ok(held) = held_files(&proc_dir, &keys) else {
+            continue;
+        };
+        if held.is_empty() {
+            continue;
+        }
+        let Ok(info) = read_process_info(&proc_dir, pid) else {
+            continue;
+        };
+        for path in held {
+            processes
+                .entry(path.clone())
+                .or_default()
+                .push(info.clone());
+        }
+    }
+    processes
+}
+
+fn list_pids(proc_root: &Path) -> io::Result<Vec<u32>> {
+    let mut pids: Vec<u32> = fs::read_dir(proc_root)?
+        .filter_map(|entry| entry.ok()?.file_name().to_str()?.parse().ok())
+        .collect();
+    pids.sort_unstable();
+    Ok(pids)
+}
+
+/// Returns the given files which the process holds, at most once each.
+fn held_files<'a>(
+    proc_dir: &Path,
+    keys: &'a HashMap<FileKey, PathBuf>,
+) -> io::Result<Vec<&'a PathBuf>> {
+    let mut held = vec![];
+    for entry in fs::read_dir(proc_dir.join("fd"))? {
+        // A file descriptor may be closed in the meantime.
+        let Ok(meta) = fs::metadata(entry?.path()) else {
+            continue;
+        };
+        if let Some(path) = keys.get(&FileKey::from(&meta)) {
+            if !held.contains(&path) {
+                held.push(path);
+            }
+        }
+    }
+    Ok(held)
+}
+
+fn read_process_info(proc_dir: &Path, pid: u32) -> io::Result<ProcessInfo> {
+    let cmdline = fs::read(proc_dir.join("cmdline"))?;
+    let uid = parse_uid(&fs::read_to_string(proc_dir.join("status"))?).ok_or_else(|| {
+        io::Error::new(io::ErrorKind::InvalidData, "no Uid in the process status")
+    })?;
+    let cgroup = fs::read_to_string(proc_dir.join("cgroup"))
+        .ok()
+        .and_then(|s| parse_cgroup(&s));
+
+    Ok(ProcessInfo {
+        pid,
+        cmdline: parse_cmdline(&cmdline),
+        uid,
+        cgroup,
+    })
+}
+
+fn parse_cmdline(cmdline: &[u8]) -> Vec<String> {
+    cmdline
+        .split(|b| *b == 0)
+        .filter(|arg| !arg.is_empty())
+        .map(|arg| String::from_utf8_lossy(arg).into_owned())
+        .collect()
+}
+
+/// Parses the real UID from a line like `Uid:\t1000\t1000\t1000\t1000`.
+fn parse_uid(status: &str) -> Option<u32> {
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Uid:"))?
+        .split_whitespace()
+        .next()?
+        .parse()
+        .ok()
+}
+
+/// Parses lines like `0::/docker/<id>` (v2) or `4:memory:/docker/<id>` (v1), preferring v2.
+fn parse_cgroup(cgroup: &str) -> Option<String> {
+    let paths: Vec<(&str, &str)> = cgroup
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.splitn(3, ':');
+            let (id, path) = (fields.next()?, fields.nth(1)?);
+            Some((id, path))
+        })
+        .collect();
+    paths
+        .iter()
+        .find(|(id, _)| *id == "0")
+        .or_else(|| paths.iter().find(|(_, path)| *path != "/"))
+        .or_else(|| paths.first())
+        .map(|(_, path)| path.to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::{fake_process, CONTAINER_ID};
+
+    #[tokio::test]
+    async fn test_find_processes() -> io::Result<()> {
+        let proc_root = tempfile::tempdir()?;
+        let npu0pe0 = PathBuf::from("test_data/test-0/dev/npu0pe0");
+        let npu0pe1 = PathBuf::from("test_data/test-0/dev/npu0pe1");
+        fake_process(proc_root.path(), 1234, &[&npu0pe0, &npu0pe0])?;
+        fake_process(proc_root.path(), 42, &["test_data/test-0/dev/npu1"])?;
+        // Not a process
+        fs::create_dir(proc_root.path().join("sys"))?;
+
+        let processes =
+            find_processes(proc_root.path(), vec![npu0pe0.clone(), npu0pe1.clone()]).await;
+        assert_eq!(processes.len(), 1);
+        assert_eq!(
+            processes[&npu0pe0],
+            vec![ProcessInfo {
+                pid: 1234,
+                cmdline: vec![String::from("python"), String::from("serve.py")],
+                uid: 1000,
+                cgroup: Some(format!("/system.slice/docker-{}.scope", CONTAINER_ID)),
+            }]
+        );
+        assert_eq!(processes[&npu0pe0][0].container_id(), Some(CONTAINER_ID));
+
+        // A missing procfs is not an error
+        assert!(
+            find_processes(Path::new("test_data/no-proc"), vec![npu0pe0])
+                .await
+                .is_empty()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_processor() {
+        let stat = "1234 (my (cmd)) S 1 1234 1234 0 -1 4194560 1 0 0 0 0 0 0 0 20 0 1 0 \
+                    10 1000 100 18446744073709551615 1 1 0 0 0 0 0 0 0 0 0 0 17 5 0 0";
+        assert_eq!(parse_processor(stat), Some(5));
+        assert_eq!(parse_processor("1234 (cmd) S 1"), None);
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(parse_cmdline(b"a\0b c\0"), vec!["a", "b c"]);
+        assert!(parse_cmdline(b"").is_empty());
+        assert_eq!(parse_uid("Name:\tx\nUid:\t0\t1\t1\t1\n"), Some(0));
+        assert_eq!(parse_uid("Name:\tx\n"), None);
+        assert_eq!(
+            parse_cgroup("12:pids:/\n4:memory:/docker/abc\n1:name=systemd:/init.scope\n"),
+            Some(String::from("/docker/abc"))
+        );
+        assert_eq!(
+            parse_cgroup("1:name=systemd:/x\n0::/user.slice\n"),
+            Some(String::from("/user.slice"))
+        );
+        assert_eq!(parse_cgroup(""), None);
+    }
+}
@@ -0,0 +1,174 @@
+//! First-come, first-served order among waiters for device files in a process.
+//!
+//! Waiters for devices under the same devfs queue up with [`Ticket`]s, and a waiter looks for
+//! available device files only when no earlier waiter may compete for the same cores. A waiter
+//! for many cores is therefore never overtaken by later waiters for fewer cores, which would
+//! otherwise take the cores one by one as they are released, while waiters for unrelated cores
+//! do not wait for each other.
+//!
+//! The order is kept only within a process. Waiters in other processes take device files
+//! whenever they find them available.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Mutex, PoisonError};
+use std::time::Duration;
+
+use lazy_static::lazy_static;
+
+use crate::device::CoreRange;
+use crate::DeviceConfig;
+
+/// How often waiters examine whether it is their turn and device files are available.
+pub(crate) const POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+static NEXT_TICKET: AtomicU64 = AtomicU64::new(0);
+
+lazy_static! {
+    static ref QUEUES: Mutex<HashMap<PathBuf, VecDeque<(u64, DeviceConfig)>>> =
+        Mutex::new(HashMap::new());
+}
+
+/// A place in the queue of a devfs, which is left when it is dropped.
+#[derive(Debug)]
+pub(crate) struct Ticket {
+    devfs: PathBuf,
+    id: u64,
+}
+
+impl Ticket {
+    /// Queues up for the config. `devfs` has to be canonical, so that the same devfs under
+    /// different paths shares a queue.
+    pub(crate) fn take(devfs: PathBuf, config: &DeviceConfig) -> Self {
+        let id = NEXT_TICKET.fetch_add(1, Ordering::Relaxed);
+        let mut queues = QUEUES.lock().unwrap_or_else(PoisonError::into_inner);
+        queues
+            .entry(devfs.clone())
+            .or_default()
+            .push_back((id, *config));
+        Self { devfs, id }
+    }
+
+    /// Returns whether all waiters queued earlier for cores which may overlap have left.
+    pub(crate) fn is_turn(&self) -> bool {
+        let queues = QUEUES.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(queue) = queues.get(&self.devfs) else {
+            return false;
+        };
+        let Some(position) = queue.iter().position(|(id, _)| *id == self.id) else {
+            return false;
+        };
+        let (_, config) = &queue[position];
+        !queue
+            .iter()
+            .take(position)
+            .any(|(_, earlier)| may_overlap(earlier, config))
+    }
+}
+
+impl Drop for Ticket {
+    fn drop(&mut self) {
+        let mut queues = QUEUES.lock().unwrap_or_else(PoisonError::into_inner);
+        if let Some(queue) = queues.get_mut(&self.devfs) {
+            queue.retain(|(id, _)| *id != self.id);
+            if queue.is_empty() {
+                queues.remove(&self.devfs);
+            }
+        }
+    }
+}
+
+/// Returns whether device files found for the configs may share cores. Unnamed configs may
+/// take any device files of their architecture, so they are assumed to overlap with those.
+fn may_overlap(a: &DeviceConfig, b: &DeviceConfig) -> bool {
+    match (a, b) {
+        (
+            DeviceConfig::Named {
+                device_id: a_id,
+                core_range: a_range,
+            },
+            DeviceConfig::Named {
+                device_id: b_id,
+                core_range: b_range,
+            },
+        ) => a_id == b_id && ranges_overlap(a_range, b_range),
+        (
+            DeviceConfig::Unnamed { arch: a_arch, .. },
+            DeviceConfig::Unnamed { arch: b_arch, .. },
+        ) => a_arch == b_arch,
+        _ => true,
+    }
+}
+
+fn ranges_overlap(a: &CoreRange, b: &CoreRange) -> bool {
+    match (a, b) {
+        (CoreRange::Range((a_start, a_end)), CoreRange::Range((b_start, b_end))) => {
+            a_start <= b_end && b_start <= a_end
+        }
+        _ => true,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn named(config: &str) -> DeviceConfig {
+        config.parse().unwrap()
+    }
+
+    #[test]
+    fn test_ticket() {
+        let devfs = tempfile::tempdir().unwrap();
+        let devfs = devfs.path().to_path_buf();
+        let config = DeviceConfig::warboy().fused().count(2);
+        let first = Ticket::take(devfs.clone(), &config);
+        let second = Ticket::take(devfs.clone(), &config);
+        let third = Ticket::take(devfs.clone(), &config);
+        assert!(first.is_turn());
+        assert!(!second.is_turn());
+
+        // Waiters may give up in any order.
+        drop(second);
+        assert!(!third.is_turn());
+        drop(first);
+        assert!(third.is_turn());
+
+        // Queues of other devfs are independent.
+        let other = tempfile::tempdir().unwrap();
+        assert!(Ticket::take(other.path().to_path_buf(), &config).is_turn());
+        drop(third);
+        assert!(!QUEUES.lock().unwrap().contains_key(&devfs));
+    }
+
+    #[test]
+    fn test_unrelated_waiters() {
+        let devfs = tempfile::tempdir().unwrap();
+        let devfs = devfs.path().to_path_buf();
+        let first = Ticket::take(devfs.clone(), &named("0:0-1"));
+        assert!(Ticket::take(devfs.clone(), &named("1")).is_turn());
+        assert!(!Ticket::take(devfs.clone(), &named("0:1")).is_turn());
+        assert!(!Ticket::take(devfs.clone(), &named("0")).is_turn());
+
+        // Unnamed configs may take any device files.
+        let unnamed = Ticket::take(devfs.clone(), &DeviceConfig::warboy().count(1));
+        assert!(!unnamed.is_turn());
+        drop(first);
+        assert!(unnamed.is_turn());
+        assert!(!Ticket::take(devfs, &named("1:0")).is_turn());
+    }
+
+    #[test]
+    fn test_may_overlap() {
+        assert!(may_overlap(&named("0:0-1"), &named("0:1")));
+        assert!(may_overlap(&named("0"), &named("0:1")));
+        assert!(!may_overlap(&named("0:0"), &named("0:1")));
+        assert!(!may_overlap(&named("0:0"), &named("1:0")));
+        assert!(may_overlap(
+            &DeviceConfig::warboy().count(1),
+            &DeviceConfig::warboy().fused().count(2)
+        ));
+        assert!(may_overlap(&DeviceConfig::warboy().count(1), &named("1:0")));
+    }
+}
@@ -0,0 +1,287 @@
+//! Exclusive reservation of device files among cooperating processes, with advisory locks
+//! (`flock(2)`) of lock files under a runtime directory.
+//!
+//! A reservation of a device file locks `{device file}.lock`, which
+//! [`OccupancyProbe::LockFile`][crate::OccupancyProbe::LockFile] examines, and
+//! `npu{N}.core{M}.lock` of each of its cores, so that device files sharing cores
+//! (e.g., npu0pe0 and npu0pe0-1) are never reserved at the same time.
+
+use std::fs::{self, File, OpenOptions};
+use std::ops::Deref;
+use std::path::Path;
+
+use crate::device::{Device, DeviceFile};
+use crate::error::DeviceResult;
+use crate::find::DeviceWithStatus;
+use crate::{lock, status, DeviceError};
+
+/// The default runtime directory, which only root can create and write to by default.
+pub(crate) static DEFAULT_RUNTIME_DIR: &str = "/run/furiosa";
+
+/// How many times to find devices again, if other processes claim some of them in the
+/// meantime.
+pub(crate) const CLAIM_ATTEMPTS: usize = 3;
+
+/// Device files reserved exclusively by
+/// [`DeviceLister::reserve_devices`][crate::DeviceLister::reserve_devices].
+///
+/// They are released when it is dropped, including when the process exits.
+#[must_use = "the device files are released when the reservation is dropped"]
+#[derive(Debug)]
+pub struct Reservation {
+    dev_files: Vec<DeviceFile>,
+    // Held only to keep the locks
+    _locks: Vec<File>,
+}
+
+impl Reservation {
+    /// Returns the reserved device files.
+    pub fn dev_files(&self) -> &[DeviceFile] {
+        &self.dev_files
+    }
+
+    /// Releases the device files, which is the same as dropping it.
+    pub fn release(self) {}
+}
+
+impl Deref for Reservation {
+    type Target = [DeviceFile];
+
+    fn deref(&self) -> &Self::Target {
+        &self.dev_files
+    }
+}
+
+/// Lists statuses of devices, where device files reserved by others are also occupied.
+pub(crate) async fn expand_status(
+    devices: Vec<Device>,
+    runtime_dir: &Path,
+) -> DeviceResult<Vec<DeviceWithStatus>> {
+    let mut new_devices = Vec::with_capacity(devices.len());
+    for device in devices {
+        let statuses = status::probe_files(&device, device.dev_files()).await?;
+        let statuses = status::with_lock_files(runtime_dir, statuses)
+            .await
+            .map_err(|e| in_runtime_dir(runtime_dir, e))?;
+        let statuses = status::resolve_statuses(&device, statuses);
+        new_devices.push(DeviceWithStatus { statuses, device });
+    }
+    Ok(new_devices)
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn expand_status_blocking(
+    devices: Vec<Device>,
+    runtime_dir: &Path,
+) -> DeviceResult<Vec<DeviceWithStatus>> {
+    let mut new_devices = Vec::with_capacity(devices.len());
+    for device in devices {
+        let statuses = status::probe_files_blocking(&device, device.dev_files())?;
+        let statuses = status::with_lock_files_blocking(runtime_dir, statuses)
+            .map_err(|e| in_runtime_dir(runtime_dir, e))?;
+        let statuses = status::resolve_statuses(&device, statuses);
+        new_devices.push(DeviceWithStatus { statuses, device });
+    }
+    Ok(new_devices)
+}
+
+/// Explains I/O errors on lock files with the runtime directory, which is likely not writable.
+fn in_runtime_dir(runtime_dir: &Path, e: DeviceError) -> DeviceError {
+    match e {
+        DeviceError::IoError { cause } | DeviceError::PermissionDenied { cause } => {
+            DeviceError::runtime_dir_unavailable(runtime_dir.display(), cause)
+        }
+        e => e,
+    }
+}
+
+/// Locks all of the found device files, or returns `None` if any of them has been claimed by
+/// others since their statuses were examined.
+pub(crate) async fn claim(
+    runtime_dir: &Path,
+    devices: &[DeviceWithStatus],
+    found: Vec<DeviceFile>,
+) -> DeviceResult<Option<Reservation>> {
+    let runtime_dir = runtime_dir.to_path_buf();
+    let lock_names = lock_names(devices, &found);
+    status::run_blocking(move || take_locks(&runtime_dir, found, &lock_names)).await
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn claim_blocking(
+    runtime_dir: &Path,
+    devices: &[DeviceWithStatus],
+    found: Vec<DeviceFile>,
+) -> DeviceResult<Option<Reservation>> {
+    let lock_names = lock_names(devices, &found);
+    take_locks(runtime_dir, found, &lock_names)
+}
+
+/// Returns the names of lock files to take for the found device files.
+fn lock_names(devices: &[DeviceWithStatus], found: &[DeviceFile]) -> Vec<String> {
+    let mut lock_names = vec![];
+    for file in found {
+        let device = devices
+            .iter()
+            .find(|device| device.device_index() == file.device_index())
+            .expect("found in the devices");
+
+        lock_names.extend(
+            device
+                .cores()
+                .iter()
+                .filter(|core| file.core_range().contains(core))
+                .map(|core| format!("npu{}.core{}.lock", device.device_index(), core))
+                .chain([format!("{}.lock", file.filename())]),
+        );
+    }
+    lock_names
+}
+
+fn take_locks(
+    runtime_dir: &Path,
+    found: Vec<DeviceFile>,
+    lock_names: &[String],
+) -> DeviceResult<Option<Reservation>> {
+    fs::create_dir_all(runtime_dir)
+        .map_err(|e| DeviceError::runtime_dir_unavailable(runtime_dir.display(), e))?;
+
+    let mut locks = vec![];
+    for lock_name in lock_names {
+        match try_lock(runtime_dir, lock_name)? {
+            Some(lock) => locks.push(lock),
+            // Locks taken so far are released on return.
+            None => return Ok(None),
+        }
+    }
+
+    Ok(Some(Reservation {
+        dev_files: found,
+        _locks: locks,
+    }))
+}
+
+fn try_lock(runtime_dir: &Path, lock_name: &str) -> DeviceResult<Option<File>> {
+    let lock = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(runtime_dir.join(lock_name))
+        .map_err(|e| DeviceError::runtime_dir_unavailable(runtime_dir.display(), e))?;
+    if lock::try_lock_exclusive(&lock)? {
+        Ok(Some(lock))
+    } else {
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::fake::test_lister;
+    use crate::find::find_devices_in;
+    use crate::{CoreStatus, DeviceConfig, DeviceError, OccupancyProbe};
+
+    fn named(config: &str) -> DeviceConfig {
+        config.parse().unwrap()
+    }
+
+    fn filenames(reservation: &Reservation) -> Vec<&str> {
+        reservation.iter().map(|file| file.filename()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_reserve_devices() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = test_lister().runtime_dir(runtime_dir.path());
+        let config = DeviceConfig::warboy().single().count(2);
+
+        let first = lister.reserve_devices(&config).await?;
+        assert_eq!(filenames(&first), vec!["npu0pe0", "npu0pe1"]);
+        let second = lister.reserve_devices(&config).await?;
+        assert_eq!(filenames(&second), vec!["npu1pe0", "npu1pe1"]);
+        assert!(matches!(
+            lister.reserve_devices(&config).await,
+            Err(DeviceError::DeviceNotFound { .. })
+        ));
+
+        // Reserved device files are visible to the lock file probe
+        let devices = lister
+            .clone()
+            .occupancy_probe(OccupancyProbe::LockFile(runtime_dir.path().to_path_buf()))
+            .list_devices()
+            .await?;
+        assert_eq!(
+            devices[1].get_status_core(1).await?,
+            CoreStatus::Occupied(String::from("npu1pe1"))
+        );
+
+        first.release();
+        let third = lister.reserve_devices(&config).await?;
+        assert_eq!(filenames(&third), vec!["npu0pe0", "npu0pe1"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_reserve_overlapping_devices() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = test_lister().runtime_dir(runtime_dir.path());
+
+        let single = lister.reserve_devices(&named("0:1")).await?;
+        assert_eq!(filenames(&single), vec!["npu0pe1"]);
+
+        // npu0pe0-1 and npu0 share core 1 with npu0pe1
+        let fused = DeviceConfig::warboy().fused().count(2);
+        assert!(lister.reserve_devices(&fused).await.is_err());
+        assert!(lister.reserve_devices(&named("0")).await.is_err());
+        let fused = lister
+            .reserve_devices(&DeviceConfig::warboy().fused().count(1))
+            .await?;
+        assert_eq!(filenames(&fused), vec!["npu1pe0-1"]);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_unusable_runtime_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let file = tempfile::NamedTempFile::new()?;
+        let lister = test_lister().runtime_dir(file.path().join("furiosa"));
+        let err = lister
+            .reserve_devices(&DeviceConfig::warboy().count(1))
+            .await
+            .unwrap_err();
+        assert!(
+            err.to_string().contains("DeviceLister::runtime_dir"),
+            "{}",
+            err
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_claim_race() -> Result<(), Box<dyn std::error::Error>> {
+        let runtime_dir = tempfile::tempdir()?;
+        let lister = test_lister().runtime_dir(runtime_dir.path());
+        let config = DeviceConfig::warboy().fused().count(2);
+
+        // Another process claims one of the device files after the statuses are examined.
+        let devices = expand_status(lister.list_devices().await?, runtime_dir.path()).await?;
+        let found = find_devices_in(&config, &devices, None)?;
+        assert_eq!(found[1].filename(), "npu1pe0-1");
+        let other = lister.reserve_devices(&named("1:0-1")).await?;
+        assert!(claim(runtime_dir.path(), &devices, found.clone())
+            .await?
+            .is_none());
+
+        // The locks of npu0pe0-1 taken before the failure have been released.
+        let fused = lister.reserve_devices(&named("0:0-1")).await?;
+        assert_eq!(filenames(&fused), vec!["npu0pe0-1"]);
+        drop(fused);
+        drop(other);
+        assert!(claim(runtime_dir.path(), &devices, found).await?.is_some());
+
+        Ok(())
+    }
+}
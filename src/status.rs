@@ -1,35 +1,401 @@
-use std::path::Path;
-use tokio::fs::OpenOptions;
+use std::collections::HashMap;
+use std::fs::File;
+use std::io;
+use std::path::{Path, PathBuf};
 
+use crate::device::{CoreIdx, CoreRange, CoreStatus, Device, DeviceFile};
 use crate::error::DeviceResult;
+use crate::lock;
+use crate::procfs::{self, ProcessInfo};
+use crate::sysfs::npu_mgmt::{self, DeviceState};
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Eq, PartialEq, Clone, Copy)]
 pub enum DeviceStatus {
     Available,
     Occupied,
 }
 
+/// A strategy to examine whether device files are occupied, used by
+/// [`Device::get_status_core`] and [`Device::get_status_all`].
+///
+/// Whatever the strategy is, processes holding device files are identified through procfs
+/// by [`Device::get_core_processes`].
+#[derive(Debug, Clone, Default, Eq, PartialEq, Hash)]
+pub enum OccupancyProbe {
+    /// Opens each device file for reading and writing, which the driver rejects with `EBUSY`
+    /// if the file is in use. It is exact, but the device is taken briefly if available,
+    /// which may make a concurrent user fail to open it.
+    #[default]
+    Open,
+    /// Looks for processes holding each device file under procfs (see
+    /// [`DeviceLister::procfs`][crate::DeviceLister::procfs]). Processes which are not
+    /// visible, e.g., in another PID namespace or owned by another user without privileges,
+    /// are missed.
+    Procfs,
+    /// Reads the cores in use from `cur_pe_ids`, or `device_state` if the driver does not
+    /// expose it. The driver does not tell which device file is in use, so a core is reported
+    /// to be occupied by the first device file whose cores are all in use.
+    Sysfs,
+    /// Tests advisory locks (`flock(2)`) of `{directory}/{device file}.lock` (e.g.,
+    /// `/run/furiosa/npu0pe0.lock`), which cooperating users take while using device files.
+    /// A missing lock file means that the device file is available.
+    LockFile(PathBuf),
+}
+
+impl OccupancyProbe {
+    /// Replaces [`Open`][Self::Open], which takes available device files briefly, with
+    /// [`Procfs`][Self::Procfs] for callers examining cores over and over.
+    pub(crate) fn non_intrusive(&self) -> Self {
+        match self {
+            Self::Open => Self::Procfs,
+            probe => probe.clone(),
+        }
+    }
+}
+
+impl DeviceStatus {
+    /// Interprets the result of opening a device file for reading and writing.
+    /// The driver rejects opening a device file in use with `EBUSY`.
+    fn from_open_result<T>(res: io::Result<T>) -> DeviceResult<Self> {
+        match res {
+            Ok(_) => Ok(DeviceStatus::Available),
+            Err(err) => {
+                if err.raw_os_error().unwrap_or(0) == 16 {
+                    Ok(DeviceStatus::Occupied)
+                } else {
+                    Err(err.into())
+                }
+            }
+        }
+    }
+}
+
 pub async fn get_device_status<P>(path: P) -> DeviceResult<DeviceStatus>
 where
     P: AsRef<Path>,
 {
-    let res = OpenOptions::new().read(true).write(true).open(path).await;
+    let res = tokio::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path)
+        .await;
+    DeviceStatus::from_open_result(res)
+}
+
+#[cfg(feature = "blocking")]
+pub fn get_device_status_blocking<P>(path: P) -> DeviceResult<DeviceStatus>
+where
+    P: AsRef<Path>,
+{
+    let res = std::fs::OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(path);
+    DeviceStatus::from_open_result(res)
+}
+
+/// Examines the given device files of a device with its [`OccupancyProbe`].
+pub(crate) async fn probe_files<'a, I>(
+    device: &Device,
+    files: I,
+) -> DeviceResult<Vec<(&'a DeviceFile, DeviceStatus)>>
+where
+    I: IntoIterator<Item = &'a DeviceFile>,
+{
+    probe_files_with(device, device.occupancy_probe(), files).await
+}
+
+/// Examines the given device files of a device with the given [`OccupancyProbe`].
+pub(crate) async fn probe_files_with<'a, I>(
+    device: &Device,
+    probe: &OccupancyProbe,
+    files: I,
+) -> DeviceResult<Vec<(&'a DeviceFile, DeviceStatus)>>
+where
+    I: IntoIterator<Item = &'a DeviceFile>,
+{
+    let files: Vec<_> = files.into_iter().collect();
+    match probe {
+        OccupancyProbe::Open => {
+            let mut statuses = Vec::with_capacity(files.len());
+            for file in files {
+                statuses.push((file, get_device_status(&file.path).await?));
+            }
+            Ok(statuses)
+        }
+        OccupancyProbe::Procfs => {
+            let paths = files.iter().map(|file| file.path.clone()).collect();
+            let proc_root = device.device_info().proc_root();
+            let processes = procfs::find_processes(proc_root, paths).await;
+            Ok(statuses_by_processes(files, &processes))
+        }
+        OccupancyProbe::Sysfs => {
+            let sys_root = device.device_info().sys_root().to_path_buf();
+            let (device_index, cores) = (device.device_index(), device.cores().clone());
+            let pe_ids =
+                run_blocking(move || pe_ids_in_use(&sys_root, device_index, &cores)).await?;
+            Ok(statuses_by_pe_ids(device, files, &pe_ids))
+        }
+        OccupancyProbe::LockFile(lock_dir) => {
+            let statuses = lock_file_statuses(lock_dir, &files).await?;
+            Ok(files.into_iter().zip(statuses).collect())
+        }
+    }
+}
+
+/// Runs blocking I/O, such as reading sysfs or taking locks, on the blocking thread pool.
+pub(crate) async fn run_blocking<F, T>(f: F) -> DeviceResult<T>
+where
+    F: FnOnce() -> DeviceResult<T> + Send + 'static,
+    T: Send + 'static,
+{
+    match tokio::task::spawn_blocking(f).await {
+        Ok(res) => res,
+        Err(e) if e.is_panic() => std::panic::resume_unwind(e.into_panic()),
+        Err(e) => Err(io::Error::other(e).into()),
+    }
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn probe_files_blocking<'a, I>(
+    device: &Device,
+    files: I,
+) -> DeviceResult<Vec<(&'a DeviceFile, DeviceStatus)>>
+where
+    I: IntoIterator<Item = &'a DeviceFile>,
+{
+    let files: Vec<_> = files.into_iter().collect();
+    match device.occupancy_probe() {
+        OccupancyProbe::Open => files
+            .into_iter()
+            .map(|file| Ok((file, get_device_status_blocking(&file.path)?)))
+            .collect(),
+        OccupancyProbe::Procfs => {
+            let paths = files.iter().map(|file| file.path.clone()).collect();
+            let proc_root = device.device_info().proc_root();
+            let processes = procfs::find_processes_blocking(proc_root, paths);
+            Ok(statuses_by_processes(files, &processes))
+        }
+        OccupancyProbe::Sysfs => {
+            let device_info = device.device_info();
+            let pe_ids = pe_ids_in_use(
+                device_info.sys_root(),
+                device.device_index(),
+                device.cores(),
+            )?;
+            Ok(statuses_by_pe_ids(device, files, &pe_ids))
+        }
+        OccupancyProbe::LockFile(lock_dir) => files
+            .into_iter()
+            .map(|file| Ok((file, get_lock_file_status(lock_dir, file.filename())?)))
+            .collect(),
+    }
+}
 
-    match res {
-        Ok(_) => Ok(DeviceStatus::Available),
-        Err(err) => {
-            if err.raw_os_error().unwrap_or(0) == 16 {
-                Ok(DeviceStatus::Occupied)
+fn statuses_by_processes<'a>(
+    files: Vec<&'a DeviceFile>,
+    processes: &HashMap<PathBuf, Vec<ProcessInfo>>,
+) -> Vec<(&'a DeviceFile, DeviceStatus)> {
+    files
+        .into_iter()
+        .map(|file| {
+            let status = if processes.contains_key(&file.path) {
+                DeviceStatus::Occupied
             } else {
-                Err(err.into())
+                DeviceStatus::Available
+            };
+            (file, status)
+        })
+        .collect()
+}
+
+/// Reads the cores in use, bypassing the cache of mgmt attributes.
+fn pe_ids_in_use(
+    sys_root: &Path,
+    device_index: u8,
+    cores: &[CoreIdx],
+) -> DeviceResult<Vec<CoreIdx>> {
+    match npu_mgmt::read_mgmt_file(sys_root, npu_mgmt::CUR_PE_IDS, device_index) {
+        Ok(ids) => npu_mgmt::parse_pe_ids(ids),
+        Err(e) if e.kind() == io::ErrorKind::NotFound => {
+            let state: DeviceState =
+                npu_mgmt::read_mgmt_file(sys_root, npu_mgmt::DEVICE_STATE, device_index)?
+                    .parse()?;
+            Ok(match state {
+                DeviceState::Busy => cores.to_vec(),
+                DeviceState::Idle => vec![],
+            })
+        }
+        Err(e) => Err(e.into()),
+    }
+}
+
+fn statuses_by_pe_ids<'a>(
+    device: &Device,
+    files: Vec<&'a DeviceFile>,
+    pe_ids: &[CoreIdx],
+) -> Vec<(&'a DeviceFile, DeviceStatus)> {
+    files
+        .into_iter()
+        .map(|file| {
+            let in_use = match file.core_range() {
+                CoreRange::All => {
+                    !pe_ids.is_empty() && device.cores().iter().all(|core| pe_ids.contains(core))
+                }
+                CoreRange::Range((start, end)) => (start..=end).all(|core| pe_ids.contains(&core)),
+            };
+            let status = if in_use {
+                DeviceStatus::Occupied
+            } else {
+                DeviceStatus::Available
+            };
+            (file, status)
+        })
+        .collect()
+}
+
+/// Tests the lock files of device files in `lock_dir`, on the blocking thread pool.
+async fn lock_file_statuses(
+    lock_dir: &Path,
+    files: &[&DeviceFile],
+) -> DeviceResult<Vec<DeviceStatus>> {
+    let lock_dir = lock_dir.to_path_buf();
+    let filenames: Vec<String> = files
+        .iter()
+        .map(|file| file.filename().to_string())
+        .collect();
+    run_blocking(move || {
+        filenames
+            .iter()
+            .map(|filename| get_lock_file_status(&lock_dir, filename))
+            .collect()
+    })
+    .await
+}
+
+/// Marks device files occupied whose lock files in `lock_dir` are locked, in addition to the
+/// ones already occupied.
+pub(crate) async fn with_lock_files<'a>(
+    lock_dir: &Path,
+    statuses: Vec<(&'a DeviceFile, DeviceStatus)>,
+) -> DeviceResult<Vec<(&'a DeviceFile, DeviceStatus)>> {
+    let available: Vec<&DeviceFile> = statuses
+        .iter()
+        .filter(|(_, status)| *status == DeviceStatus::Available)
+        .map(|(file, _)| *file)
+        .collect();
+    let mut locked = lock_file_statuses(lock_dir, &available).await?.into_iter();
+    Ok(statuses
+        .into_iter()
+        .map(|(file, status)| match status {
+            DeviceStatus::Occupied => (file, status),
+            DeviceStatus::Available => (file, locked.next().expect("examined")),
+        })
+        .collect())
+}
+
+#[cfg(feature = "blocking")]
+pub(crate) fn with_lock_files_blocking<'a>(
+    lock_dir: &Path,
+    statuses: Vec<(&'a DeviceFile, DeviceStatus)>,
+) -> DeviceResult<Vec<(&'a DeviceFile, DeviceStatus)>> {
+    statuses
+        .into_iter()
+        .map(|(file, status)| match status {
+            DeviceStatus::Occupied => Ok((file, status)),
+            DeviceStatus::Available => Ok((file, get_lock_file_status(lock_dir, file.filename())?)),
+        })
+        .collect()
+}
+
+/// Tests the lock of the lock file of a device file, and releases it at once if acquired.
+fn get_lock_file_status(lock_dir: &Path, filename: &str) -> DeviceResult<DeviceStatus> {
+    let path = lock_dir.join(format!("{}.lock", filename));
+    let lock_file = match File::open(path) {
+        Ok(lock_file) => lock_file,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(DeviceStatus::Available),
+        Err(e) => return Err(e.into()),
+    };
+    if lock::try_lock_exclusive(&lock_file)? {
+        Ok(DeviceStatus::Available)
+    } else {
+        Ok(DeviceStatus::Occupied)
+    }
+}
+
+/// Returns device files containing the core, in the order to be examined.
+pub(crate) fn files_of_core(device: &Device, core: CoreIdx) -> impl Iterator<Item = &DeviceFile> {
+    device
+        .dev_files()
+        .iter()
+        .filter(move |file| file.core_range().contains(&core))
+}
+
+/// Builds the status of each core from the statuses of device files.
+///
+/// A core is occupied by the first occupied device file containing it, in the order of
+/// [`Device::dev_files`], which is the same as the one examined by `get_status_core`.
+pub(crate) fn resolve_statuses<'a, I>(
+    device: &Device,
+    file_statuses: I,
+) -> HashMap<CoreIdx, CoreStatus>
+where
+    I: IntoIterator<Item = (&'a DeviceFile, DeviceStatus)>,
+{
+    let mut status_map = device.new_status_map();
+
+    for (file, status) in file_statuses {
+        if status != DeviceStatus::Occupied {
+            continue;
+        }
+        for core in device.cores() {
+            if !file.core_range().contains(core) {
+                continue;
+            }
+            if let Some(status @ CoreStatus::Available) = status_map.get_mut(core) {
+                *status = CoreStatus::Occupied(file.to_string());
             }
         }
     }
+    status_map
 }
 
 #[cfg(test)]
 mod tests {
+    use std::fs;
+
+    use tempfile::TempDir;
+
     use super::*;
+    use crate::fake::{fake_process, lock_device_file, test_lister};
+    use crate::DeviceLister;
+
+    async fn occupied_by(lister: &DeviceLister) -> DeviceResult<Vec<Option<String>>> {
+        let devices = lister.list_devices().await?;
+        let statuses = devices[0].get_status_all().await?;
+        for core in devices[0].cores() {
+            assert_eq!(devices[0].get_status_core(*core).await?, statuses[core]);
+        }
+        Ok((0..2)
+            .map(|core| match &statuses[&core] {
+                CoreStatus::Occupied(device_file) => Some(device_file.clone()),
+                _ => None,
+            })
+            .collect())
+    }
+
+    /// Copies the mgmt files of npu0 to a new sysfs, so that volatile ones can be changed.
+    fn fake_sysfs() -> io::Result<TempDir> {
+        let sysfs = tempfile::tempdir()?;
+        let mgmt = sysfs.path().join("class/npu_mgmt/npu0_mgmt");
+        fs::create_dir_all(&mgmt)?;
+        for entry in fs::read_dir("test_data/test-0/sys/class/npu_mgmt/npu0_mgmt")? {
+            let entry = entry?;
+            fs::copy(entry.path(), mgmt.join(entry.file_name()))?;
+        }
+        fs::create_dir_all(sysfs.path().join("bus/pci/devices/0000:6d:00.0/hwmon"))?;
+        Ok(sysfs)
+    }
 
     #[tokio::test]
     async fn test() -> DeviceResult<()> {
@@ -37,4 +403,149 @@ mod tests {
         assert_eq!(res, DeviceStatus::Available);
         Ok(())
     }
+
+    #[test]
+    fn test_from_open_result() -> DeviceResult<()> {
+        assert_eq!(
+            DeviceStatus::from_open_result(Ok(()))?,
+            DeviceStatus::Available
+        );
+        assert_eq!(
+            DeviceStatus::from_open_result::<()>(Err(io::Error::from_raw_os_error(16)))?,
+            DeviceStatus::Occupied
+        );
+        assert!(
+            DeviceStatus::from_open_result::<()>(Err(io::Error::from_raw_os_error(2))).is_err()
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_resolve_statuses() -> DeviceResult<()> {
+        let devices = test_lister().list_devices().await?;
+        let device = &devices[0];
+        let files = device.dev_files();
+        assert_eq!(
+            files_of_core(device, 1)
+                .map(|f| f.filename())
+                .collect::<Vec<_>>(),
+            vec!["npu0", "npu0pe1", "npu0pe0-1"]
+        );
+
+        // npu0pe1 and npu0pe0-1 are occupied; the first one in order wins.
+        let statuses: Vec<_> = files
+            .iter()
+            .map(|file| {
+                let status = match file.filename() {
+                    "npu0pe1" | "npu0pe0-1" => DeviceStatus::Occupied,
+                    _ => DeviceStatus::Available,
+                };
+                (file, status)
+            })
+            .collect();
+        let statuses = resolve_statuses(device, statuses);
+        assert_eq!(
+            statuses[&0],
+            CoreStatus::Occupied(String::from("npu0pe0-1"))
+        );
+        assert_eq!(statuses[&1], CoreStatus::Occupied(String::from("npu0pe1")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_procfs() -> Result<(), Box<dyn std::error::Error>> {
+        let proc_root = tempfile::tempdir()?;
+        let lister = test_lister()
+            .occupancy_probe(OccupancyProbe::Procfs)
+            .procfs(proc_root.path());
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+
+        fake_process(proc_root.path(), 1234, &["test_data/test-0/dev/npu0pe1"])?;
+        assert_eq!(
+            occupied_by(&lister).await?,
+            vec![None, Some(String::from("npu0pe1"))]
+        );
+        let devices = lister.list_devices().await?;
+        assert!(devices[0].get_core_processes(0).await?.is_empty());
+        let processes = devices[0].get_core_processes(1).await?;
+        assert_eq!(processes.len(), 1);
+        assert_eq!(processes[0].pid, 1234);
+
+        // A process holding several device files containing the core is listed once.
+        fake_process(
+            proc_root.path(),
+            5678,
+            &[
+                "test_data/test-0/dev/npu0pe0",
+                "test_data/test-0/dev/npu0pe0-1",
+            ],
+        )?;
+        let pids: Vec<u32> = devices[0]
+            .get_core_processes(0)
+            .await?
+            .iter()
+            .map(|process| process.pid)
+            .collect();
+        assert_eq!(pids, vec![5678]);
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_sysfs() -> Result<(), Box<dyn std::error::Error>> {
+        let sysfs = fake_sysfs()?;
+        let cur_pe_ids = sysfs.path().join("class/npu_mgmt/npu0_mgmt/cur_pe_ids");
+        let lister = test_lister()
+            .occupancy_probe(OccupancyProbe::Sysfs)
+            .sysfs(sysfs.path());
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+
+        fs::write(&cur_pe_ids, "0\n")?;
+        assert_eq!(
+            occupied_by(&lister).await?,
+            vec![Some(String::from("npu0pe0")), None]
+        );
+        fs::write(&cur_pe_ids, "0 1\n")?;
+        assert_eq!(
+            occupied_by(&lister).await?,
+            vec![Some(String::from("npu0")), Some(String::from("npu0"))]
+        );
+
+        // Falls back to device_state
+        fs::remove_file(&cur_pe_ids)?;
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+        fs::write(
+            sysfs.path().join("class/npu_mgmt/npu0_mgmt/device_state"),
+            "1 (BUSY)\n",
+        )?;
+        assert_eq!(
+            occupied_by(&lister).await?,
+            vec![Some(String::from("npu0")), Some(String::from("npu0"))]
+        );
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_probe_lock_file() -> Result<(), Box<dyn std::error::Error>> {
+        let lock_dir = tempfile::tempdir()?;
+        let lister =
+            test_lister().occupancy_probe(OccupancyProbe::LockFile(lock_dir.path().to_path_buf()));
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+
+        // A lock file nobody locks
+        File::create(lock_dir.path().join("npu0pe0-1.lock"))?;
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+
+        let lock_file = lock_device_file(lock_dir.path(), "npu0pe0-1")?;
+        assert_eq!(
+            occupied_by(&lister).await?,
+            vec![
+                Some(String::from("npu0pe0-1")),
+                Some(String::from("npu0pe0-1"))
+            ]
+        );
+        drop(lock_file);
+        assert_eq!(occupied_by(&lister).await?, vec![None, None]);
+        Ok(())
+    }
 }
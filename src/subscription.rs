@@ -0,0 +1,507 @@
+//! Transitions of core statuses, found by examining the cores of a device periodically.
+//!
+//! Subscriptions to a device with the same interval share a poller, a task which examines the
+//! cores and hands the statuses to each subscription. It stops once all of them are dropped.
+
+use std::collections::{HashMap, VecDeque};
+use std::path::PathBuf;
+use std::pin::Pin;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::task::{Context, Poll};
+use std::time::Duration;
+
+use futures::channel::mpsc::{self, Receiver, Sender};
+use futures::future;
+use futures::ready;
+use futures::stream::{self, BoxStream, Stream, StreamExt};
+use lazy_static::lazy_static;
+use tokio::time::{self, Instant, MissedTickBehavior};
+
+use crate::device::{CoreIdx, CoreStatus, Device};
+use crate::error::{DeviceError, DeviceResult};
+use crate::status::{self, OccupancyProbe};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+const DEFAULT_DEBOUNCE: Duration = Duration::from_secs(1);
+
+lazy_static! {
+    /// Subscriptions of each running poller
+    static ref POLLERS: Mutex<HashMap<PollerKey, Vec<Sender<Probe>>>> = Mutex::new(HashMap::new());
+}
+
+/// A transition of the status of a core, reported by a [`StatusSubscription`].
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct CoreStatusEvent {
+    pub device_index: u8,
+    pub core: CoreIdx,
+    pub previous: CoreStatus,
+    pub current: CoreStatus,
+}
+
+/// A stream of [`CoreStatusEvent`]s of a device, created by [`Device::subscribe_status`].
+///
+/// The cores are examined every [`interval`][Self::interval] with the
+/// [`OccupancyProbe`] of the device, except that [`OccupancyProbe::Open`], which takes
+/// available devices briefly, is replaced with [`OccupancyProbe::Procfs`]. Subscriptions to the
+/// same device with the same interval share the examination, which runs as a tokio task.
+///
+/// A new status is reported once it has lasted for the [`debounce`][Self::debounce]
+/// period, so that a core taken and released in the meantime is not reported at all.
+/// A core stays occupied while any device file sharing it is in use; a core handed over from
+/// one device file to another (e.g., from `npu0pe0-1` to `npu0pe0`) is not reported either.
+///
+/// Transitions are relative to the statuses examined first, which are not reported.
+pub struct StatusSubscription {
+    device: Arc<Device>,
+    interval: Duration,
+    debounce: Duration,
+    updates: Option<BoxStream<'static, DeviceResult<Update>>>,
+    /// Statuses examined first, followed by reported transitions
+    reported: Option<HashMap<CoreIdx, CoreStatus>>,
+}
+
+impl StatusSubscription {
+    pub(crate) fn new(device: Arc<Device>) -> Self {
+        Self {
+            device,
+            interval: DEFAULT_INTERVAL,
+            debounce: DEFAULT_DEBOUNCE,
+            updates: None,
+            reported: None,
+        }
+    }
+
+    /// Sets how often to examine the cores, 500 milliseconds by default.
+    pub fn interval(self, interval: Duration) -> Self {
+        Self { interval, ..self }
+    }
+
+    /// Sets how long a new status has to last before it is reported, 1 second by default.
+    pub fn debounce(self, debounce: Duration) -> Self {
+        Self { debounce, ..self }
+    }
+
+    /// Waits until the core is reported available, returning at once if it is already.
+    pub async fn wait_available(&mut self, core: CoreIdx) -> DeviceResult<()> {
+        loop {
+            if let Some(reported) = &self.reported {
+                if matches!(reported.get(&core), None | Some(CoreStatus::Available)) {
+                    return Ok(());
+                }
+            }
+            match future::poll_fn(|cx| self.poll_update(cx)).await {
+                Some(update) => update?,
+                None => return Ok(()),
+            };
+        }
+    }
+
+    fn poll_update(&mut self, cx: &mut Context<'_>) -> Poll<Option<DeviceResult<Update>>> {
+        let (device, interval, debounce) = (&self.device, self.interval, self.debounce);
+        let update = ready!(self
+            .updates
+            .get_or_insert_with(|| updates(device, interval, debounce))
+            .poll_next_unpin(cx));
+        match &update {
+            Some(Ok(Update::Baseline(statuses))) => self.reported = Some(statuses.clone()),
+            Some(Ok(Update::Event(event))) => {
+                if let Some(reported) = &mut self.reported {
+                    reported.insert(event.core, event.current.clone());
+                }
+            }
+            _ => {}
+        }
+        Poll::Ready(update)
+    }
+}
+
+impl Stream for StatusSubscription {
+    type Item = DeviceResult<CoreStatusEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            match ready!(self.poll_update(cx)) {
+                Some(Ok(Update::Baseline(_))) => continue,
+                Some(Ok(Update::Event(event))) => return Poll::Ready(Some(Ok(event))),
+                Some(Err(e)) => return Poll::Ready(Some(Err(e))),
+                None => return Poll::Ready(None),
+            }
+        }
+    }
+}
+
+enum Update {
+    /// Statuses examined first
+    Baseline(HashMap<CoreIdx, CoreStatus>),
+    Event(CoreStatusEvent),
+}
+
+fn updates(
+    device: &Arc<Device>,
+    interval: Duration,
+    debounce: Duration,
+) -> BoxStream<'static, DeviceResult<Update>> {
+    let tracker = Tracker {
+        device_index: device.device_index(),
+        debounce,
+        reported: None,
+        pending: HashMap::new(),
+        events: VecDeque::new(),
+    };
+    let probes = subscribe(device, interval);
+    stream::unfold((tracker, probes), |(mut tracker, mut probes)| async move {
+        loop {
+            if let Some(event) = tracker.events.pop_front() {
+                return Some((Ok(Update::Event(event)), (tracker, probes)));
+            }
+            let probe = probes.next().await?;
+            let statuses = match probe.statuses {
+                Ok(statuses) => statuses,
+                Err(e) => return Some((Err(e.duplicate()), (tracker, probes))),
+            };
+            if tracker.reported.is_none() {
+                let baseline = Update::Baseline(statuses.clone());
+                tracker.update(statuses, probe.at);
+                return Some((Ok(baseline), (tracker, probes)));
+            }
+            tracker.update(statuses, probe.at);
+        }
+    })
+    .boxed()
+}
+
+/// Identifies the examinations a poller makes.
+#[derive(Debug, Clone, Eq, PartialEq, Hash)]
+struct PollerKey {
+    dev_root: PathBuf,
+    sys_root: PathBuf,
+    proc_root: PathBuf,
+    device_index: u8,
+    probe: OccupancyProbe,
+    interval: Duration,
+}
+
+impl PollerKey {
+    fn new(device: &Device, interval: Duration) -> Self {
+        let device_info = device.device_info();
+        Self {
+            dev_root: device_info.dev_root().to_path_buf(),
+            sys_root: device_info.sys_root().to_path_buf(),
+            proc_root: device_info.proc_root().to_path_buf(),
+            device_index: device.device_index(),
+            probe: device.occupancy_probe().non_intrusive(),
+            interval,
+        }
+    }
+}
+
+/// Statuses examined by a poller, and when
+#[derive(Clone)]
+struct Probe {
+    at: Instant,
+    statuses: Result<HashMap<CoreIdx, CoreStatus>, Arc<DeviceError>>,
+}
+
+/// Joins the poller of the device, starting one if none is running.
+fn subscribe(device: &Arc<Device>, interval: Duration) -> Receiver<Probe> {
+    let key = PollerKey::new(device, interval);
+    // A subscription which falls behind misses statuses, rather than piling them up.
+    let (tx, rx) = mpsc::channel(1);
+    let mut pollers = POLLERS.lock().unwrap_or_else(PoisonError::into_inner);
+    match pollers.get_mut(&key) {
+        Some(subscriptions) => subscriptions.push(tx),
+        None => {
+            pollers.insert(key.clone(), vec![tx]);
+            tokio::spawn(poll(device.clone(), key));
+        }
+    }
+    rx
+}
+
+async fn poll(device: Arc<Device>, key: PollerKey) {
+    let mut ticker = time::interval(key.interval);
+    ticker.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        ticker.tick().await;
+        let statuses = match status::probe_files_with(&device, &key.probe, device.dev_files()).await
+        {
+            Ok(statuses) => Ok(status::resolve_statuses(&device, statuses)),
+            Err(e) => Err(Arc::new(e)),
+        };
+        let probe = Probe {
+            at: Instant::now(),
+            statuses,
+        };
+
+        let mut pollers = POLLERS.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(subscriptions) = pollers.get_mut(&key) else {
+            return;
+        };
+        subscriptions.retain_mut(|tx| match tx.try_send(probe.clone()) {
+            Ok(()) => true,
+            Err(e) => e.is_full(),
+        });
+        if subscriptions.is_empty() {
+            pollers.remove(&key);
+            return;
+        }
+    }
+}
+
+/// Both are available, both are occupied by whichever device files, or both are unavailable.
+fn same_state(a: &CoreStatus, b: &CoreStatus) -> bool {
+    std::mem::discriminant(a) == std::mem::discriminant(b)
+}
+
+/// A sans-IO core of the subscription, which turns examined statuses into events.
+struct Tracker {
+    device_index: u8,
+    debounce: Duration,
+    /// Statuses reported last, or examined first
+    reported: Option<HashMap<CoreIdx, CoreStatus>>,
+    /// New statuses not reported yet, since when they have been examined
+    pending: HashMap<CoreIdx, (CoreStatus, Instant)>,
+    events: VecDeque<CoreStatusEvent>,
+}
+
+impl Tracker {
+    fn update(&mut self, statuses: HashMap<CoreIdx, CoreStatus>, now: Instant) {
+        let Some(reported) = &mut self.reported else {
+            self.reported = Some(statuses);
+            return;
+        };
+
+        let mut cores: Vec<CoreIdx> = statuses.keys().copied().collect();
+        cores.sort_unstable();
+        for core in cores {
+            let current = statuses[&core].clone();
+            let previous = reported
+                .get(&core)
+                .cloned()
+                .unwrap_or(CoreStatus::Available);
+            if same_state(&previous, &current) {
+                self.pending.remove(&core);
+                continue;
+            }
+
+            let since = match self.pending.get(&core) {
+                Some((pending, since)) if same_state(pending, &current) => *since,
+                _ => now,
+            };
+            if now.duration_since(since) >= self.debounce {
+                self.pending.remove(&core);
+                reported.insert(core, current.clone());
+                self.events.push_back(CoreStatusEvent {
+                    device_index: self.device_index,
+                    core,
+                    previous,
+                    current,
+                });
+            } else {
+                self.pending.insert(core, (current, since));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::time::timeout;
+
+    use super::*;
+    use crate::fake::{lock_device_file, test_lister};
+    use crate::OccupancyProbe;
+
+    fn occupied(device_file: &str) -> CoreStatus {
+        CoreStatus::Occupied(String::from(device_file))
+    }
+
+    #[test]
+    fn test_tracker() {
+        let start = Instant::now();
+        let at = |millis| start + Duration::from_millis(millis);
+        let mut tracker = Tracker {
+            device_index: 0,
+            debounce: Duration::from_millis(100),
+            reported: None,
+            pending: HashMap::new(),
+            events: VecDeque::new(),
+        };
+        let statuses =
+            |core0: CoreStatus, core1: CoreStatus| HashMap::from([(0, core0), (1, core1)]);
+
+        tracker.update(
+            statuses(CoreStatus::Available, CoreStatus::Available),
+            at(0),
+        );
+        assert!(tracker.events.is_empty());
+
+        // Taken and released within the debounce period
+        tracker.update(statuses(occupied("npu0pe0"), CoreStatus::Available), at(10));
+        tracker.update(
+            statuses(CoreStatus::Available, CoreStatus::Available),
+            at(50),
+        );
+        tracker.update(
+            statuses(CoreStatus::Available, CoreStatus::Available),
+            at(200),
+        );
+        assert!(tracker.events.is_empty());
+
+        // Handed over from npu0pe0-1 to npu0pe0 while pending
+        tracker.update(
+            statuses(occupied("npu0pe0-1"), occupied("npu0pe0-1")),
+            at(300),
+        );
+        tracker.update(
+            statuses(occupied("npu0pe0"), CoreStatus::Available),
+            at(350),
+        );
+        assert!(tracker.events.is_empty());
+        tracker.update(
+            statuses(occupied("npu0pe0"), CoreStatus::Available),
+            at(400),
+        );
+        assert_eq!(
+            tracker.events.drain(..).collect::<Vec<_>>(),
+            vec![CoreStatusEvent {
+                device_index: 0,
+                core: 0,
+                previous: CoreStatus::Available,
+                current: occupied("npu0pe0"),
+            }]
+        );
+
+        tracker.update(
+            statuses(CoreStatus::Available, CoreStatus::Unavailable),
+            at(500),
+        );
+        tracker.update(
+            statuses(CoreStatus::Available, CoreStatus::Unavailable),
+            at(600),
+        );
+        assert_eq!(
+            tracker
+                .events
+                .iter()
+                .map(|event| (event.core, event.current.clone()))
+                .collect::<Vec<_>>(),
+            vec![(0, CoreStatus::Available), (1, CoreStatus::Unavailable)]
+        );
+    }
+
+    async fn next_event(subscription: &mut StatusSubscription) -> CoreStatusEvent {
+        timeout(Duration::from_secs(5), subscription.next())
+            .await
+            .expect("no event")
+            .unwrap()
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_subscribe_status() -> Result<(), Box<dyn std::error::Error>> {
+        let lock_dir = tempfile::tempdir()?;
+        let device = Arc::new(
+            test_lister()
+                .occupancy_probe(OccupancyProbe::LockFile(lock_dir.path().to_path_buf()))
+                .list_devices()
+                .await?
+                .remove(0),
+        );
+        let mut subscription = device
+            .subscribe_status()
+            .interval(Duration::from_millis(10))
+            .debounce(Duration::from_millis(30));
+
+        // The initial statuses are not reported.
+        assert!(timeout(Duration::from_millis(50), subscription.next())
+            .await
+            .is_err());
+        subscription.wait_available(0).await?;
+
+        let fused = lock_device_file(lock_dir.path(), "npu0pe0-1")?;
+        let event = next_event(&mut subscription).await;
+        assert_eq!(
+            event,
+            CoreStatusEvent {
+                device_index: 0,
+                core: 0,
+                previous: CoreStatus::Available,
+                current: occupied("npu0pe0-1"),
+            }
+        );
+        let event = next_event(&mut subscription).await;
+        assert_eq!((event.core, event.current), (1, occupied("npu0pe0-1")));
+
+        // Core 0 stays occupied, by npu0pe0 instead.
+        let single = lock_device_file(lock_dir.path(), "npu0pe0")?;
+        drop(fused);
+        let event = next_event(&mut subscription).await;
+        assert_eq!((event.core, event.current), (1, CoreStatus::Available));
+        subscription.wait_available(1).await?;
+
+        let release = tokio::spawn(async move {
+            time::sleep(Duration::from_millis(50)).await;
+            drop(single);
+        });
+        timeout(Duration::from_secs(5), subscription.wait_available(0)).await??;
+        release.await?;
+        assert_eq!(device.get_status_core(0).await?, CoreStatus::Available);
+
+        // A subscription can be moved to another task.
+        let fused = lock_device_file(lock_dir.path(), "npu0pe0-1")?;
+        next_event(&mut subscription).await;
+        let event = next_event(&mut subscription).await;
+        assert_eq!((event.core, event.current), (1, occupied("npu0pe0-1")));
+        let waiter = tokio::spawn(async move { subscription.wait_available(1).await });
+        time::sleep(Duration::from_millis(50)).await;
+        assert!(!waiter.is_finished());
+        drop(fused);
+        timeout(Duration::from_secs(5), waiter).await???;
+
+        Ok(())
+    }
+
+    fn subscriptions(key: &PollerKey) -> Option<usize> {
+        let pollers = POLLERS.lock().unwrap();
+        pollers.get(key).map(Vec::len)
+    }
+
+    #[tokio::test]
+    async fn test_shared_poller() -> Result<(), Box<dyn std::error::Error>> {
+        let proc_root = tempfile::tempdir()?;
+        let device = Arc::new(
+            test_lister()
+                .procfs(proc_root.path())
+                .list_devices()
+                .await?
+                .remove(0),
+        );
+        let interval = Duration::from_millis(10);
+        let key = PollerKey::new(&device, interval);
+        // Opening device files would take them from their users.
+        assert_eq!(device.occupancy_probe(), &OccupancyProbe::Open);
+        assert_eq!(key.probe, OccupancyProbe::Procfs);
+
+        let mut first = device.subscribe_status().interval(interval);
+        let mut second = device.subscribe_status().interval(interval);
+        first.wait_available(0).await?;
+        second.wait_available(0).await?;
+        assert_eq!(subscriptions(&key), Some(2));
+
+        // A subscription with another interval has its own poller.
+        let mut other = device.subscribe_status();
+        other.wait_available(0).await?;
+        assert_eq!(subscriptions(&key), Some(2));
+
+        drop(first);
+        drop(second);
+        timeout(Duration::from_secs(5), async {
+            while subscriptions(&key).is_some() {
+                time::sleep(interval).await;
+            }
+        })
+        .await?;
+        Ok(())
+    }
+}
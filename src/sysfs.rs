@@ -1,21 +1,71 @@
+//! Constants and value types for sysfs attributes exposed by the kernel driver.
+
+/// Attributes under `/sys/class/npu_mgmt/npu{N}_mgmt`.
+///
+/// The constants can be passed to [`DeviceInfo::get`][crate::DeviceInfo::get] and
+/// [`DeviceInfo::ctrl`][crate::DeviceInfo::ctrl].
 pub mod npu_mgmt {
     use std::collections::HashMap;
+    use std::fmt::{self, Display, Formatter};
     use std::io;
     use std::path::{Path, PathBuf};
+    use std::str::FromStr;
+
+    use crate::{DeviceError, DeviceResult};
 
-    #[derive(Copy, Clone, Debug)]
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum Toggle {
         Enable = 1,
         Disable = 0,
     }
 
-    #[derive(Copy, Clone, Debug)]
+    impl Toggle {
+        fn from_code(code: u8) -> Option<Self> {
+            match code {
+                1 => Some(Self::Enable),
+                0 => Some(Self::Disable),
+                _ => None,
+            }
+        }
+    }
+
+    impl FromStr for Toggle {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_code(s)
+                .and_then(Self::from_code)
+                .ok_or_else(|| DeviceError::unexpected_value(format!("Bad toggle value: {}", s)))
+        }
+    }
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum DtmPolicy {
         OnDemand = 1,
         Conservative = 0,
     }
 
-    #[derive(Copy, Clone, Debug)]
+    impl DtmPolicy {
+        fn from_code(code: u8) -> Option<Self> {
+            match code {
+                1 => Some(Self::OnDemand),
+                0 => Some(Self::Conservative),
+                _ => None,
+            }
+        }
+    }
+
+    impl FromStr for DtmPolicy {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_code(s)
+                .and_then(Self::from_code)
+                .ok_or_else(|| DeviceError::unexpected_value(format!("Bad DTM policy: {}", s)))
+        }
+    }
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum PerfMode {
         Full2 = 5,
         Full1 = 4,
@@ -25,7 +75,32 @@ pub mod npu_mgmt {
         Low = 0,
     }
 
-    #[derive(Copy, Clone, Debug)]
+    impl PerfMode {
+        fn from_code(code: u8) -> Option<Self> {
+            match code {
+                5 => Some(Self::Full2),
+                4 => Some(Self::Full1),
+                3 => Some(Self::Normal2),
+                2 => Some(Self::Normal1),
+                1 => Some(Self::Half),
+                0 => Some(Self::Low),
+                _ => None,
+            }
+        }
+    }
+
+    impl FromStr for PerfMode {
+        type Err = DeviceError;
+
+        /// Parses a value such as `4 (FULL 1)` by its leading code.
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_code(s).and_then(Self::from_code).ok_or_else(|| {
+                DeviceError::unexpected_value(format!("Bad performance mode: {}", s))
+            })
+        }
+    }
+
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
     pub enum PerfLevel {
         Level0 = 0,
         Level1 = 1,
@@ -45,27 +120,236 @@ pub mod npu_mgmt {
         Level15 = 15,
     }
 
-    pub(crate) static ALIVE: &str = "alive";
-    pub(crate) static ATR_ERROR: &str = "atr_error";
-    pub(crate) static BUSNAME: &str = "busname";
-    pub(crate) static CUR_PE_IDS: &str = "cur_pe_ids";
-    pub(crate) static DEV: &str = "dev";
-    pub(crate) static DEVICE_LED: &str = "device_led";
-    pub(crate) static DEVICE_STATE: &str = "device_state";
-    pub(crate) static DEVICE_TYPE: &str = "device_type";
-    pub(crate) static DEVICE_UUID: &str = "device_uuid";
-    pub(crate) static EVB_REV: &str = "evb_rev";
-    pub(crate) static FW_VERSION: &str = "fw_version";
-    pub(crate) static HEARTBEAT: &str = "heartbeat";
-    pub(crate) static NE_CLK_FREQ_INFO: &str = "ne_clk_freq_info";
-    pub(crate) static NE_CLOCK: &str = "ne_clock";
-    pub(crate) static NE_DTM_POLICY: &str = "ne_dtm_policy";
-    pub(crate) static PERFORMANCE_LEVEL: &str = "performance_level";
-    pub(crate) static PERFORMANCE_MODE: &str = "performance_mode";
-    pub(crate) static PLATFORM_TYPE: &str = "platform_type";
-    pub(crate) static SOC_REV: &str = "soc_rev";
-    pub(crate) static SOC_UID: &str = "soc_uid";
-    pub(crate) static VERSION: &str = "version";
+    impl PerfLevel {
+        fn from_code(code: u8) -> Option<Self> {
+            use PerfLevel::*;
+
+            const LEVELS: [PerfLevel; 16] = [
+                Level0, Level1, Level2, Level3, Level4, Level5, Level6, Level7, Level8, Level9,
+                Level10, Level11, Level12, Level13, Level14, Level15,
+            ];
+            LEVELS.get(usize::from(code)).copied()
+        }
+    }
+
+    impl FromStr for PerfLevel {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_code(s).and_then(Self::from_code).ok_or_else(|| {
+                DeviceError::unexpected_value(format!("Bad performance level: {}", s))
+            })
+        }
+    }
+
+    impl TryFrom<u8> for PerfLevel {
+        type Error = DeviceError;
+
+        fn try_from(level: u8) -> Result<Self, Self::Error> {
+            Self::from_code(level).ok_or_else(|| {
+                DeviceError::unexpected_value(format!("Bad performance level: {}", level))
+            })
+        }
+    }
+
+    /// Enum for the device state reported by `device_state`.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum DeviceState {
+        Idle = 0,
+        Busy = 1,
+    }
+
+    impl DeviceState {
+        fn from_code(code: u8) -> Option<Self> {
+            match code {
+                0 => Some(Self::Idle),
+                1 => Some(Self::Busy),
+                _ => None,
+            }
+        }
+    }
+
+    impl FromStr for DeviceState {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            parse_code(s)
+                .and_then(Self::from_code)
+                .ok_or_else(|| DeviceError::unexpected_value(format!("Bad device state: {}", s)))
+        }
+    }
+
+    impl Display for DeviceState {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                DeviceState::Idle => write!(f, "idle"),
+                DeviceState::Busy => write!(f, "busy"),
+            }
+        }
+    }
+
+    /// Enum for the platform reported by `platform_type`.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub enum PlatformType {
+        FuriosaAI,
+        Vitis,
+    }
+
+    impl FromStr for PlatformType {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            match s.trim() {
+                "FuriosaAI" => Ok(PlatformType::FuriosaAI),
+                "VITIS" => Ok(PlatformType::Vitis),
+                _ => Err(DeviceError::unexpected_value(format!(
+                    "Bad platform type: {}",
+                    s
+                ))),
+            }
+        }
+    }
+
+    impl Display for PlatformType {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                PlatformType::FuriosaAI => write!(f, "FuriosaAI"),
+                PlatformType::Vitis => write!(f, "VITIS"),
+            }
+        }
+    }
+
+    /// Version of the kernel driver reported by `version` (e.g., `1.2.0, 0e7b5e6`).
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct DriverVersion {
+        major: u32,
+        minor: u32,
+        patch: u32,
+        metadata: String,
+    }
+
+    impl DriverVersion {
+        pub fn major(&self) -> u32 {
+            self.major
+        }
+
+        pub fn minor(&self) -> u32 {
+            self.minor
+        }
+
+        pub fn patch(&self) -> u32 {
+            self.patch
+        }
+
+        /// Returns the build metadata following the version (e.g., a git hash).
+        pub fn metadata(&self) -> &str {
+            &self.metadata
+        }
+    }
+
+    impl FromStr for DriverVersion {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let bad_version = || DeviceError::unexpected_value(format!("Bad version: {}", s));
+
+            let (version, metadata) = s.trim().split_once(',').unwrap_or((s.trim(), ""));
+            let mut numbers = version.trim().split('.').map(|n| n.parse::<u32>());
+            match (
+                numbers.next(),
+                numbers.next(),
+                numbers.next(),
+                numbers.next(),
+            ) {
+                (Some(Ok(major)), Some(Ok(minor)), Some(Ok(patch)), None) => Ok(Self {
+                    major,
+                    minor,
+                    patch,
+                    metadata: metadata.trim().to_string(),
+                }),
+                _ => Err(bad_version()),
+            }
+        }
+    }
+
+    impl Display for DriverVersion {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{}.{}.{}", self.major, self.minor, self.patch)?;
+            if !self.metadata.is_empty() {
+                write!(f, ", {}", self.metadata)?;
+            }
+            Ok(())
+        }
+    }
+
+    /// A clock frequency of a component, reported by `ne_clk_freq_info`.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct ClockFrequency {
+        name: String,
+        unit: String,
+        value: u32,
+    }
+
+    impl ClockFrequency {
+        /// Returns the name of the clock (e.g., `ne tensor`).
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Returns the unit of the value (e.g., `MHz`).
+        pub fn unit(&self) -> &str {
+            &self.unit
+        }
+
+        pub fn value(&self) -> u32 {
+            self.value
+        }
+    }
+
+    impl FromStr for ClockFrequency {
+        type Err = DeviceError;
+
+        fn from_str(line: &str) -> Result<Self, Self::Err> {
+            let bad_line =
+                || DeviceError::unexpected_value(format!("Bad clock frequency: {}", line));
+
+            let (name, value) = line.trim().split_once(':').ok_or_else(bad_line)?;
+            let (value, unit) = value.trim().split_once(' ').ok_or_else(bad_line)?;
+            Ok(Self {
+                name: name.trim().to_string(),
+                unit: unit.trim().to_string(),
+                value: value.parse().map_err(|_| bad_line())?,
+            })
+        }
+    }
+
+    impl Display for ClockFrequency {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{}: {} {}", self.name, self.value, self.unit)
+        }
+    }
+
+    pub static ALIVE: &str = "alive";
+    pub static ATR_ERROR: &str = "atr_error";
+    pub static BUSNAME: &str = "busname";
+    pub static CUR_PE_IDS: &str = "cur_pe_ids";
+    pub static DEV: &str = "dev";
+    pub static DEVICE_LED: &str = "device_led";
+    pub static DEVICE_STATE: &str = "device_state";
+    pub static DEVICE_TYPE: &str = "device_type";
+    pub static DEVICE_UUID: &str = "device_uuid";
+    pub static EVB_REV: &str = "evb_rev";
+    pub static FW_VERSION: &str = "fw_version";
+    pub static HEARTBEAT: &str = "heartbeat";
+    pub static NE_CLK_FREQ_INFO: &str = "ne_clk_freq_info";
+    pub static NE_CLOCK: &str = "ne_clock";
+    pub static NE_DTM_POLICY: &str = "ne_dtm_policy";
+    pub static PERFORMANCE_LEVEL: &str = "performance_level";
+    pub static PERFORMANCE_MODE: &str = "performance_mode";
+    pub static PLATFORM_TYPE: &str = "platform_type";
+    pub static SOC_REV: &str = "soc_rev";
+    pub static SOC_UID: &str = "soc_uid";
+    pub static VERSION: &str = "version";
 
     pub(crate) static MGMT_FILES: &[(&str, bool)] = &[
         (ALIVE, false),
@@ -80,6 +364,7 @@ pub mod npu_mgmt {
         (FW_VERSION, false),
         (HEARTBEAT, false),
         (NE_CLK_FREQ_INFO, false),
+        (NE_CLOCK, false),
         (NE_DTM_POLICY, false),
         (PERFORMANCE_LEVEL, false),
         (PERFORMANCE_MODE, false),
@@ -89,6 +374,19 @@ pub mod npu_mgmt {
         (VERSION, false),
     ];
 
+    /// Attributes which never change while the device exists. Their values are always cached
+    /// regardless of [`CachePolicy`][crate::CachePolicy].
+    pub(crate) static STATIC_FILES: &[&str] = &[
+        BUSNAME,
+        DEV,
+        DEVICE_TYPE,
+        DEVICE_UUID,
+        EVB_REV,
+        PLATFORM_TYPE,
+        SOC_REV,
+        SOC_UID,
+    ];
+
     pub(crate) static CTRL_FILES: &[&str] = &[
         DEVICE_LED,
         NE_CLOCK,
@@ -97,6 +395,10 @@ pub mod npu_mgmt {
         PERFORMANCE_MODE,
     ];
 
+    pub(crate) fn is_static_file(mgmt_file: &str) -> bool {
+        STATIC_FILES.contains(&mgmt_file)
+    }
+
     pub(crate) fn path<P: AsRef<Path>>(base_dir: P, file: &str, idx: u8) -> PathBuf {
         base_dir
             .as_ref()
@@ -105,8 +407,7 @@ pub mod npu_mgmt {
 
     /// It can be used to check `platform_type`.
     pub(crate) fn is_furiosa_platform(contents: &str) -> bool {
-        let contents = contents.trim();
-        contents == "FuriosaAI" || contents == "VITIS"
+        contents.parse::<PlatformType>().is_ok()
     }
 
     pub(crate) fn read_mgmt_file<P: AsRef<Path>>(
@@ -173,9 +474,416 @@ pub mod npu_mgmt {
             _ => None,
         }
     }
+
+    /// Parses the leading numeric code of a value such as `4 (FULL 1)`.
+    pub(crate) fn parse_code<S: AsRef<str>>(contents: S) -> Option<u8> {
+        let contents = contents.as_ref().trim();
+        let code = contents
+            .split_once(' ')
+            .map(|(code, _)| code)
+            .unwrap_or(contents);
+        code.parse().ok()
+    }
+
+    /// Parses `cur_pe_ids` which lists the PE ids separated by whitespaces or commas.
+    pub(crate) fn parse_pe_ids<S: AsRef<str>>(contents: S) -> DeviceResult<Vec<u8>> {
+        let contents = contents.as_ref();
+        contents
+            .split(|c: char| c.is_whitespace() || c == ',')
+            .filter(|id| !id.is_empty())
+            .map(|id| {
+                id.parse::<u8>()
+                    .map_err(|_| DeviceError::unexpected_value(format!("Bad PE ids: {}", contents)))
+            })
+            .collect()
+    }
+
+    pub(crate) fn parse_clock_frequencies<S: AsRef<str>>(
+        contents: S,
+    ) -> DeviceResult<Vec<ClockFrequency>> {
+        contents
+            .as_ref()
+            .lines()
+            .filter(|line| !line.trim().is_empty())
+            .map(ClockFrequency::from_str)
+            .collect()
+    }
 }
 
-pub(crate) mod pci {
+/// Attributes of PCI devices under `/sys/bus/pci/devices/{BDF}`.
+pub mod pci {
+    use std::collections::BTreeMap;
+    use std::fmt::{self, Display, Formatter};
+    use std::io;
+    use std::path::Path;
+    use std::str::FromStr;
+
+    use crate::{DeviceError, DeviceResult};
+
+    /// Speed of a PCIe link, reported by `current_link_speed` and `max_link_speed`
+    /// (e.g., `16.0 GT/s PCIe`).
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+    pub struct LinkSpeed {
+        mega_transfers: u32,
+    }
+
+    impl LinkSpeed {
+        /// Returns the speed in megatransfers per second (e.g., 16000 for 16 GT/s).
+        pub fn mega_transfers(&self) -> u32 {
+            self.mega_transfers
+        }
+
+        /// Returns the PCIe generation running at the speed (e.g., 4 for 16 GT/s).
+        pub fn generation(&self) -> Option<u8> {
+            match self.mega_transfers {
+                2500 => Some(1),
+                5000 => Some(2),
+                8000 => Some(3),
+                16000 => Some(4),
+                32000 => Some(5),
+                64000 => Some(6),
+                _ => None,
+            }
+        }
+    }
+
+    impl FromStr for LinkSpeed {
+        type Err = DeviceError;
+
+        fn from_str(s: &str) -> Result<Self, Self::Err> {
+            let bad_speed = || DeviceError::unexpected_value(format!("Bad link speed: {}", s));
+
+            let gt = s.trim().split_once(" GT/s").ok_or_else(bad_speed)?.0.trim();
+            let (integer, fraction) = gt.split_once('.').unwrap_or((gt, "0"));
+            if fraction.len() > 3 {
+                return Err(bad_speed());
+            }
+            let integer = integer.parse::<u32>().map_err(|_| bad_speed())?;
+            let fraction = format!("{:0<3}", fraction)
+                .parse::<u32>()
+                .map_err(|_| bad_speed())?;
+            Ok(Self {
+                mega_transfers: integer * 1000 + fraction,
+            })
+        }
+    }
+
+    impl Display for LinkSpeed {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            let (integer, fraction) = (self.mega_transfers / 1000, self.mega_transfers % 1000);
+            let fraction = format!("{:03}", fraction);
+            let fraction = fraction.trim_end_matches('0');
+            write!(
+                f,
+                "{}.{} GT/s",
+                integer,
+                if fraction.is_empty() { "0" } else { fraction }
+            )
+        }
+    }
+
+    /// Speed and width of a PCIe link.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq)]
+    pub struct PciLink {
+        speed: LinkSpeed,
+        width: u8,
+    }
+
+    impl PciLink {
+        pub fn speed(&self) -> LinkSpeed {
+            self.speed
+        }
+
+        /// Returns the number of lanes.
+        pub fn width(&self) -> u8 {
+            self.width
+        }
+    }
+
+    impl Display for PciLink {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            write!(f, "{} x{}", self.speed, self.width)
+        }
+    }
+
+    /// Identity, link and state of the PCI device of an NPU.
+    #[derive(Clone, Debug, Eq, PartialEq)]
+    pub struct PciInfo {
+        vendor_id: u16,
+        device_id: u16,
+        subsystem_vendor_id: u16,
+        subsystem_device_id: u16,
+        class: u32,
+        revision: u8,
+        current_link: Option<PciLink>,
+        max_link: Option<PciLink>,
+        iommu_group: Option<u32>,
+        driver: Option<String>,
+        enabled: bool,
+    }
+
+    impl PciInfo {
+        pub fn vendor_id(&self) -> u16 {
+            self.vendor_id
+        }
+
+        pub fn device_id(&self) -> u16 {
+            self.device_id
+        }
+
+        pub fn subsystem_vendor_id(&self) -> u16 {
+            self.subsystem_vendor_id
+        }
+
+        pub fn subsystem_device_id(&self) -> u16 {
+            self.subsystem_device_id
+        }
+
+        /// Returns the class code, of the base class, subclass and programming interface
+        /// (e.g., `0x120000` for processing accelerators).
+        pub fn class(&self) -> u32 {
+            self.class
+        }
+
+        pub fn revision(&self) -> u8 {
+            self.revision
+        }
+
+        /// Returns the negotiated link, or `None` if it is unknown (e.g., the link is down).
+        pub fn current_link(&self) -> Option<PciLink> {
+            self.current_link
+        }
+
+        /// Returns the link which the device is capable of.
+        pub fn max_link(&self) -> Option<PciLink> {
+            self.max_link
+        }
+
+        /// Returns whether the link has been trained to a lower speed or fewer lanes than
+        /// the device is capable of, which usually indicates a bad slot, riser or cable.
+        ///
+        /// The capability of the slot is not taken into account; a device in a slot of
+        /// fewer lanes is degraded as well.
+        pub fn is_link_degraded(&self) -> bool {
+            match (self.current_link, self.max_link) {
+                (Some(current), Some(max)) => {
+                    current.speed < max.speed || current.width < max.width
+                }
+                (None, Some(_)) => true,
+                _ => false,
+            }
+        }
+
+        /// Returns the IOMMU group, or `None` if the IOMMU is disabled.
+        pub fn iommu_group(&self) -> Option<u32> {
+            self.iommu_group
+        }
+
+        /// Returns the name of the bound driver, or `None` if no driver is bound.
+        pub fn driver(&self) -> Option<&str> {
+            self.driver.as_deref()
+        }
+
+        pub fn is_enabled(&self) -> bool {
+            self.enabled
+        }
+    }
+
+    /// Severity of PCIe Advanced Error Reporting (AER) errors.
+    #[derive(Copy, Clone, Debug, Eq, PartialEq, Ord, PartialOrd, Hash)]
+    pub enum AerSeverity {
+        Correctable,
+        NonFatal,
+        Fatal,
+    }
+
+    impl AerSeverity {
+        pub const ALL: [AerSeverity; 3] = [Self::Correctable, Self::NonFatal, Self::Fatal];
+
+        fn file_name(&self) -> &'static str {
+            match self {
+                Self::Correctable => "aer_dev_correctable",
+                Self::NonFatal => "aer_dev_nonfatal",
+                Self::Fatal => "aer_dev_fatal",
+            }
+        }
+    }
+
+    impl Display for AerSeverity {
+        fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+            match self {
+                Self::Correctable => write!(f, "correctable"),
+                Self::NonFatal => write!(f, "nonfatal"),
+                Self::Fatal => write!(f, "fatal"),
+            }
+        }
+    }
+
+    /// Counters of PCIe AER errors by severity and type, reported by `aer_dev_correctable`,
+    /// `aer_dev_nonfatal` and `aer_dev_fatal` (e.g., `RxErr 2` or `CmpltTO 0`).
+    ///
+    /// Take [`diff`][Self::diff] of two snapshots to find whether errors are rising.
+    #[derive(Clone, Debug, Default, Eq, PartialEq)]
+    pub struct AerCounters {
+        counts: BTreeMap<AerSeverity, BTreeMap<String, u64>>,
+    }
+
+    impl AerCounters {
+        /// Returns the count of each error type of the severity (e.g., `RxErr`).
+        pub fn counts(&self, severity: AerSeverity) -> impl Iterator<Item = (&str, u64)> {
+            self.counts
+                .get(&severity)
+                .into_iter()
+                .flatten()
+                .map(|(name, count)| (name.as_str(), *count))
+        }
+
+        /// Returns the count of an error type, or `None` if the kernel does not report it.
+        pub fn get(&self, severity: AerSeverity, name: &str) -> Option<u64> {
+            self.counts.get(&severity)?.get(name).copied()
+        }
+
+        /// Returns the sum of the counts of the severity.
+        pub fn total(&self, severity: AerSeverity) -> u64 {
+            self.counts(severity).map(|(_, count)| count).sum()
+        }
+
+        pub fn is_zero(&self) -> bool {
+            AerSeverity::ALL
+                .iter()
+                .all(|severity| self.total(*severity) == 0)
+        }
+
+        /// Returns how much each count has increased since the earlier snapshot.
+        ///
+        /// A count smaller than before means that the counters have been reset (e.g., by a
+        /// reset of the device), so all of it counts as the increase.
+        pub fn diff(&self, earlier: &AerCounters) -> AerCounters {
+            let counts = self
+                .counts
+                .iter()
+                .map(|(severity, counts)| {
+                    let counts = counts
+                        .iter()
+                        .map(|(name, count)| {
+                            let before = earlier.get(*severity, name).unwrap_or(0);
+                            let increase = if *count >= before {
+                                count - before
+                            } else {
+                                *count
+                            };
+                            (name.clone(), increase)
+                        })
+                        .collect();
+                    (*severity, counts)
+                })
+                .collect();
+            AerCounters { counts }
+        }
+    }
+
+    pub(crate) fn read_aer_counters<P: AsRef<Path>>(
+        sysfs: P,
+        bdf: &str,
+    ) -> DeviceResult<AerCounters> {
+        let dir = sysfs
+            .as_ref()
+            .join(format!("bus/pci/devices/{}", bdf.trim()));
+        let mut counts = BTreeMap::new();
+        for severity in AerSeverity::ALL {
+            let path = dir.join(severity.file_name());
+            let contents = std::fs::read_to_string(&path).map_err(|e| match e.kind() {
+                io::ErrorKind::NotFound => DeviceError::file_not_found(path.display()),
+                _ => e.into(),
+            })?;
+            counts.insert(severity, parse_aer_counts(&contents)?);
+        }
+        Ok(AerCounters { counts })
+    }
+
+    /// Parses lines like `RxErr 2`, except for the totals (e.g., `TOTAL_ERR_COR 2`).
+    pub(crate) fn parse_aer_counts(contents: &str) -> DeviceResult<BTreeMap<String, u64>> {
+        let mut counts = BTreeMap::new();
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let bad_line = || DeviceError::unexpected_value(format!("Bad AER counter: {}", line));
+            let (name, count) = line.trim().split_once(' ').ok_or_else(bad_line)?;
+            if name.starts_with("TOTAL_ERR_") {
+                continue;
+            }
+            counts.insert(
+                name.to_string(),
+                count.trim().parse().map_err(|_| bad_line())?,
+            );
+        }
+        Ok(counts)
+    }
+
+    pub(crate) fn read_pci_info<P: AsRef<Path>>(sysfs: P, bdf: &str) -> DeviceResult<PciInfo> {
+        let dir = sysfs
+            .as_ref()
+            .join(format!("bus/pci/devices/{}", bdf.trim()));
+        let read = |name: &str| -> DeviceResult<String> {
+            std::fs::read_to_string(dir.join(name))
+                .map(|s| s.trim().to_string())
+                .map_err(|e| match e.kind() {
+                    io::ErrorKind::NotFound => {
+                        DeviceError::file_not_found(dir.join(name).display())
+                    }
+                    _ => e.into(),
+                })
+        };
+        let read_optional = |name: &str| -> DeviceResult<Option<String>> {
+            match read(name) {
+                Err(DeviceError::IoError { cause }) if cause.kind() == io::ErrorKind::NotFound => {
+                    Ok(None)
+                }
+                result => result.map(Some),
+            }
+        };
+        let link_target = |name: &str| -> Option<String> {
+            let target = std::fs::read_link(dir.join(name)).ok()?;
+            Some(target.file_name()?.to_string_lossy().into_owned())
+        };
+        let read_link = |speed: &str, width: &str| -> DeviceResult<Option<PciLink>> {
+            let (speed, width) = match (read_optional(speed)?, read_optional(width)?) {
+                (Some(speed), Some(width)) => (speed, width),
+                _ => return Ok(None),
+            };
+            // e.g., `Unknown` or `Unknown speed` if the link is down
+            if speed.starts_with("Unknown") {
+                return Ok(None);
+            }
+            Ok(Some(PciLink {
+                speed: speed.parse()?,
+                width: width.parse().map_err(|_| {
+                    DeviceError::unexpected_value(format!("Bad link width: {}", width))
+                })?,
+            }))
+        };
+
+        Ok(PciInfo {
+            vendor_id: parse_hex(&read("vendor")?)?,
+            device_id: parse_hex(&read("device")?)?,
+            subsystem_vendor_id: parse_hex(&read("subsystem_vendor")?)?,
+            subsystem_device_id: parse_hex(&read("subsystem_device")?)?,
+            class: parse_hex(&read("class")?)?,
+            revision: parse_hex(&read("revision")?)?,
+            current_link: read_link("current_link_speed", "current_link_width")?,
+            max_link: read_link("max_link_speed", "max_link_width")?,
+            iommu_group: link_target("iommu_group").and_then(|group| group.parse().ok()),
+            driver: link_target("driver"),
+            enabled: read("enable")? != "0",
+        })
+    }
+
+    /// Parses a value like `0x1ed2`.
+    pub(crate) fn parse_hex<T: TryFrom<u64>>(s: &str) -> DeviceResult<T> {
+        let bad_value = || DeviceError::unexpected_value(format!("Bad hex value: {}", s));
+        let value =
+            u64::from_str_radix(s.trim().trim_start_matches("0x"), 16).map_err(|_| bad_value())?;
+        T::try_from(value).map_err(|_| bad_value())
+    }
+
     pub(crate) mod numa {
         use std::io;
         use std::path::{Path, PathBuf};
@@ -192,18 +900,126 @@ pub(crate) mod pci {
         }
     }
 
+    pub(crate) mod local_cpus {
+        use std::io;
+        use std::path::Path;
+
+        use crate::sysfs::node::parse_cpulist;
+
+        /// Reads the CPUs close to the PCI device from `local_cpulist`, or from the
+        /// `local_cpus` mask on kernels without it.
+        pub(crate) fn read_local_cpus<P: AsRef<Path>>(
+            sysfs: P,
+            bdf: &str,
+        ) -> io::Result<Vec<usize>> {
+            let dir = sysfs
+                .as_ref()
+                .join(format!("bus/pci/devices/{}", bdf.trim()));
+            let (contents, cpus) = match std::fs::read_to_string(dir.join("local_cpulist")) {
+                Ok(cpulist) => {
+                    let cpus = parse_cpulist(&cpulist);
+                    (cpulist, cpus)
+                }
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {
+                    let mask = std::fs::read_to_string(dir.join("local_cpus"))?;
+                    let cpus = parse_cpumask(&mask);
+                    (mask, cpus)
+                }
+                Err(e) => return Err(e),
+            };
+            cpus.ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad local CPUs: {}", contents.trim()),
+                )
+            })
+        }
+
+        /// Parses a mask like `ff,00000000` of comma-separated 32-bit words in hex, the most
+        /// significant first.
+        pub(crate) fn parse_cpumask(mask: &str) -> Option<Vec<usize>> {
+            let mut cpus = vec![];
+            for (idx, word) in mask.trim().rsplit(',').enumerate() {
+                let word = u32::from_str_radix(word, 16).ok()?;
+                cpus.extend(
+                    (0..32)
+                        .filter(|bit| word & (1 << bit) != 0)
+                        .map(|bit| idx * 32 + bit),
+                );
+            }
+            cpus.sort_unstable();
+            Some(cpus)
+        }
+    }
+
     pub(crate) mod hwmon {
-        use std::path::PathBuf;
+        use std::path::{Path, PathBuf};
 
-        pub fn path(base_dir: &str, bdf: &str) -> PathBuf {
-            PathBuf::from(format!("{}/bus/pci/devices/{}/hwmon", base_dir, bdf.trim()))
+        pub fn path<P: AsRef<Path>>(base_dir: P, bdf: &str) -> PathBuf {
+            base_dir
+                .as_ref()
+                .join(format!("bus/pci/devices/{}/hwmon", bdf.trim()))
         }
     }
 }
 
+/// NUMA nodes under `/sys/devices/system/node`.
+pub(crate) mod node {
+    use std::io;
+    use std::path::Path;
+
+    /// Reads the CPUs of each NUMA node, sorted by node.
+    pub(crate) fn read_cpulists<P: AsRef<Path>>(sysfs: P) -> io::Result<Vec<(usize, Vec<usize>)>> {
+        let mut nodes = vec![];
+        for entry in std::fs::read_dir(sysfs.as_ref().join("devices/system/node"))? {
+            let entry = entry?;
+            let node = match entry
+                .file_name()
+                .to_str()
+                .and_then(|name| name.strip_prefix("node"))
+                .and_then(|id| id.parse().ok())
+            {
+                Some(node) => node,
+                None => continue,
+            };
+            let cpulist = std::fs::read_to_string(entry.path().join("cpulist"))?;
+            let cpus = parse_cpulist(&cpulist).ok_or_else(|| {
+                io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    format!("Bad cpulist: {}", cpulist.trim()),
+                )
+            })?;
+            nodes.push((node, cpus));
+        }
+        nodes.sort_unstable();
+        Ok(nodes)
+    }
+
+    /// Returns the NUMA node of a CPU.
+    pub(crate) fn node_of_cpu<P: AsRef<Path>>(sysfs: P, cpu: usize) -> io::Result<Option<usize>> {
+        Ok(read_cpulists(sysfs)?
+            .into_iter()
+            .find(|(_, cpus)| cpus.contains(&cpu))
+            .map(|(node, _)| node))
+    }
+
+    /// Parses a list like `0-3,8-11` or `5`, which is empty for a node without CPUs.
+    pub(crate) fn parse_cpulist(cpulist: &str) -> Option<Vec<usize>> {
+        let mut cpus = vec![];
+        for range in cpulist.trim().split(',').filter(|range| !range.is_empty()) {
+            match range.split_once('-') {
+                Some((start, end)) => cpus.extend(start.parse::<usize>().ok()?..=end.parse().ok()?),
+                None => cpus.push(range.parse().ok()?),
+            }
+        }
+        Some(cpus)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::DeviceResult;
 
     #[test]
     fn test_build_atr_error_map() {
@@ -241,4 +1057,163 @@ Device Error: 0";
         let res3 = npu_mgmt::parse_zero_or_one_to_bool(case3);
         assert!(res3.is_none());
     }
+
+    #[test]
+    fn test_parse_cpulist() {
+        assert_eq!(node::parse_cpulist("0-3,8\n"), Some(vec![0, 1, 2, 3, 8]));
+        assert_eq!(node::parse_cpulist("\n"), Some(vec![]));
+        assert_eq!(node::parse_cpulist("0-"), None);
+        assert_eq!(
+            pci::local_cpus::parse_cpumask("00000001,00000000,0000000f\n"),
+            Some(vec![0, 1, 2, 3, 64])
+        );
+        assert_eq!(pci::local_cpus::parse_cpumask("0"), Some(vec![]));
+        assert_eq!(pci::local_cpus::parse_cpumask("ff,"), None);
+    }
+
+    #[test]
+    fn test_aer_counters_diff() -> DeviceResult<()> {
+        use pci::{AerCounters, AerSeverity};
+
+        let read = |correctable: &str, fatal: &str| -> DeviceResult<AerCounters> {
+            let sysfs = tempfile::tempdir().unwrap();
+            let dir = sysfs.path().join("bus/pci/devices/0000:01:00.0");
+            std::fs::create_dir_all(&dir).unwrap();
+            std::fs::write(dir.join("aer_dev_correctable"), correctable).unwrap();
+            std::fs::write(
+                dir.join("aer_dev_nonfatal"),
+                "TLP 0\nTOTAL_ERR_NONFATAL 0\n",
+            )
+            .unwrap();
+            std::fs::write(dir.join("aer_dev_fatal"), fatal).unwrap();
+            pci::read_aer_counters(sysfs.path(), "0000:01:00.0")
+        };
+
+        let before = read("RxErr 2\nBadTLP 0\nTOTAL_ERR_COR 2\n", "DLP 1\n")?;
+        assert_eq!(before.get(AerSeverity::Correctable, "RxErr"), Some(2));
+        assert_eq!(before.get(AerSeverity::Correctable, "TOTAL_ERR_COR"), None);
+        assert_eq!(before.total(AerSeverity::Fatal), 1);
+        assert!(!before.is_zero());
+
+        let after = read("RxErr 5\nBadTLP 1\nTOTAL_ERR_COR 6\n", "DLP 1\n")?;
+        let diff = after.diff(&before);
+        assert_eq!(
+            diff.counts(AerSeverity::Correctable).collect::<Vec<_>>(),
+            vec![("BadTLP", 1), ("RxErr", 3)]
+        );
+        assert_eq!(diff.total(AerSeverity::Fatal), 0);
+        assert!(!diff.is_zero());
+        assert!(after.diff(&after).is_zero());
+
+        // Counters reset in the meantime
+        let reset = read("RxErr 1\nBadTLP 0\n", "DLP 0\n")?;
+        assert_eq!(reset.diff(&after).total(AerSeverity::Correctable), 1);
+
+        assert!(read("RxErr two\n", "DLP 0\n").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_link_speed() -> DeviceResult<()> {
+        let speed = "16.0 GT/s PCIe\n".parse::<pci::LinkSpeed>()?;
+        assert_eq!(speed.mega_transfers(), 16000);
+        assert_eq!(speed.generation(), Some(4));
+        assert_eq!(speed.to_string(), "16.0 GT/s");
+        let speed = "2.5 GT/s".parse::<pci::LinkSpeed>()?;
+        assert_eq!(speed.generation(), Some(1));
+        assert_eq!(speed.to_string(), "2.5 GT/s");
+        assert!("8 GT/s".parse::<pci::LinkSpeed>()? > speed);
+        assert!("Unknown".parse::<pci::LinkSpeed>().is_err());
+        assert!("2.5001 GT/s".parse::<pci::LinkSpeed>().is_err());
+
+        assert_eq!(pci::parse_hex::<u16>("0x1ed2\n")?, 0x1ed2);
+        assert!(pci::parse_hex::<u8>("0x100").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_code() {
+        assert_eq!(npu_mgmt::parse_code("4 (FULL 1)"), Some(4));
+        assert_eq!(npu_mgmt::parse_code("1"), Some(1));
+        assert_eq!(npu_mgmt::parse_code(" 0 (IDLE)\n"), Some(0));
+        assert_eq!(npu_mgmt::parse_code("FULL"), None);
+        assert_eq!(npu_mgmt::parse_code(""), None);
+    }
+
+    #[test]
+    fn test_parse_ctrl_values() -> DeviceResult<()> {
+        use npu_mgmt::*;
+
+        assert_eq!("4 (FULL 1)".parse::<PerfMode>()?, PerfMode::Full1);
+        assert_eq!("0 (LOW)".parse::<PerfMode>()?, PerfMode::Low);
+        assert!("6".parse::<PerfMode>().is_err());
+        assert!("FULL 1".parse::<PerfMode>().is_err());
+
+        assert_eq!("0".parse::<PerfLevel>()?, PerfLevel::Level0);
+        assert_eq!("15".parse::<PerfLevel>()?, PerfLevel::Level15);
+        assert!("16".parse::<PerfLevel>().is_err());
+        assert_eq!(PerfLevel::try_from(7)?, PerfLevel::Level7);
+        assert!(PerfLevel::try_from(16).is_err());
+
+        assert_eq!("1 (ON DEMAND)".parse::<DtmPolicy>()?, DtmPolicy::OnDemand);
+        assert_eq!("0".parse::<DtmPolicy>()?, DtmPolicy::Conservative);
+        assert!("2".parse::<DtmPolicy>().is_err());
+
+        assert_eq!("1".parse::<Toggle>()?, Toggle::Enable);
+        assert_eq!("0".parse::<Toggle>()?, Toggle::Disable);
+        assert!("".parse::<Toggle>().is_err());
+
+        // Values written by ctrl can be parsed back
+        for mode in [PerfMode::Full2, PerfMode::Half] {
+            assert_eq!((mode as u8).to_string().parse::<PerfMode>()?, mode);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_pe_ids() -> DeviceResult<()> {
+        assert_eq!(npu_mgmt::parse_pe_ids("0 1")?, vec![0, 1]);
+        assert_eq!(npu_mgmt::parse_pe_ids("0,1\n")?, vec![0, 1]);
+        assert_eq!(npu_mgmt::parse_pe_ids("")?, Vec::<u8>::new());
+        assert!(npu_mgmt::parse_pe_ids("0 a").is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_typed_values() -> DeviceResult<()> {
+        use npu_mgmt::*;
+
+        assert_eq!("0 (IDLE)".parse::<DeviceState>()?, DeviceState::Idle);
+        assert_eq!("1".parse::<DeviceState>()?, DeviceState::Busy);
+        assert!("9".parse::<DeviceState>().is_err());
+
+        assert_eq!(
+            "FuriosaAI".parse::<PlatformType>()?,
+            PlatformType::FuriosaAI
+        );
+        assert_eq!("VITIS".parse::<PlatformType>()?, PlatformType::Vitis);
+        assert!("Other".parse::<PlatformType>().is_err());
+
+        let version = "1.2.0, 0e7b5e6".parse::<DriverVersion>()?;
+        assert_eq!(
+            (version.major(), version.minor(), version.patch()),
+            (1, 2, 0)
+        );
+        assert_eq!(version.metadata(), "0e7b5e6");
+        assert_eq!(version.to_string(), "1.2.0, 0e7b5e6");
+        assert_eq!("1.2.3".parse::<DriverVersion>()?.to_string(), "1.2.3");
+        assert!("1.2".parse::<DriverVersion>().is_err());
+        assert!("1.2.3.4".parse::<DriverVersion>().is_err());
+
+        let freqs = parse_clock_frequencies("ne tensor: 2000 MHz\nne cluster 0: 1000 MHz\n")?;
+        assert_eq!(freqs.len(), 2);
+        assert_eq!(freqs[0].name(), "ne tensor");
+        assert_eq!(freqs[0].value(), 2000);
+        assert_eq!(freqs[0].unit(), "MHz");
+        assert_eq!(freqs[1].to_string(), "ne cluster 0: 1000 MHz");
+        assert!(parse_clock_frequencies("ne tensor 2000 MHz").is_err());
+
+        Ok(())
+    }
 }
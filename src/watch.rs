@@ -0,0 +1,473 @@
+//! Watches devfs for NPU devices being added, removed or changed (e.g., removed after a PCIe
+//! error, or recreated by a reload of the driver).
+//!
+//! Kernel uevents are received from a netlink socket when the devfs root is `/dev`, and the
+//! devfs root is watched with inotify otherwise (e.g., a bind-mounted devfs or a fake devfs
+//! in tests). Either way, they only trigger a rescan of devfs, whose result is compared with
+//! the previous one.
+
+use std::collections::{BTreeMap, BTreeSet, HashMap};
+use std::ffi::CString;
+use std::io;
+use std::mem;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+use std::os::unix::ffi::OsStrExt;
+use std::os::unix::fs::MetadataExt;
+use std::path::{Path, PathBuf};
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use futures::channel::mpsc::{self, UnboundedReceiver, UnboundedSender};
+use futures::Stream;
+
+use crate::devfs;
+use crate::list::list_devfs_blocking;
+use crate::{DeviceError, DeviceLister, DeviceResult};
+
+/// How long to wait for events before checking whether the watcher has been dropped.
+const POLL_INTERVAL: Duration = Duration::from_millis(200);
+/// How long events have to stop before a rescan, so that a burst of them (e.g., all device
+/// files recreated by a reload of the driver) leads to a single rescan.
+const DEBOUNCE: Duration = Duration::from_millis(50);
+/// A rescan happens after this many debounce periods even if events keep coming.
+const MAX_DEBOUNCES: usize = 20;
+
+/// A change of an NPU device in devfs, reported by a [`DeviceWatcher`].
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+pub enum DeviceEvent {
+    /// Device files of a device have appeared.
+    Added { device_index: u8 },
+    /// All device files of a device have disappeared.
+    Removed { device_index: u8 },
+    /// Device files of a device have been added, removed or recreated, or the kernel has
+    /// reported a change of the device.
+    Changed { device_index: u8 },
+}
+
+impl DeviceEvent {
+    pub fn device_index(&self) -> u8 {
+        match self {
+            Self::Added { device_index }
+            | Self::Removed { device_index }
+            | Self::Changed { device_index } => *device_index,
+        }
+    }
+}
+
+/// A stream of [`DeviceEvent`]s, created by [`DeviceLister::watch`].
+///
+/// Events are reported relative to the devices present when the watcher was created. The
+/// stream ends with an error if devfs can no longer be watched (e.g., its root is removed).
+/// Dropping the watcher stops watching within a fraction of a second.
+#[derive(Debug)]
+pub struct DeviceWatcher {
+    events: UnboundedReceiver<DeviceResult<DeviceEvent>>,
+}
+
+impl Stream for DeviceWatcher {
+    type Item = DeviceResult<DeviceEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        Pin::new(&mut self.events).poll_next(cx)
+    }
+}
+
+pub(crate) fn watch(lister: &DeviceLister) -> DeviceResult<DeviceWatcher> {
+    spawn(lister).map(|(watcher, _)| watcher)
+}
+
+fn spawn(lister: &DeviceLister) -> DeviceResult<(DeviceWatcher, JoinHandle<()>)> {
+    let source = Source::open(lister.devfs_root())?;
+    let snapshot = scan(lister)?;
+    let (sender, events) = mpsc::unbounded();
+    let lister = lister.clone();
+    let handle = thread::Builder::new()
+        .name(String::from("npu-watcher"))
+        .spawn(move || run(lister, source, snapshot, sender))?;
+    Ok((DeviceWatcher { events }, handle))
+}
+
+/// Identity of each device file of each device, which differs once a file is recreated.
+type Snapshot = HashMap<u8, BTreeMap<PathBuf, (u64, u64, u64)>>;
+
+fn scan(lister: &DeviceLister) -> DeviceResult<Snapshot> {
+    let dev_files = lister.npu_dev_files(list_devfs_blocking(lister.devfs_root())?)?;
+    Ok(dev_files
+        .into_iter()
+        .map(|(idx, paths)| {
+            let files = paths
+                .into_iter()
+                // A file may be removed in the meantime, which the next rescan will find.
+                .filter_map(|path| {
+                    let meta = std::fs::metadata(&path).ok()?;
+                    Some((path, (meta.dev(), meta.ino(), meta.rdev())))
+                })
+                .collect();
+            (idx, files)
+        })
+        .collect())
+}
+
+/// Events from the previous snapshot to the new one, in the order of device indices.
+fn diff(previous: &Snapshot, new: &Snapshot, changed: &BTreeSet<u8>) -> Vec<DeviceEvent> {
+    let indices: BTreeSet<u8> = previous.keys().chain(new.keys()).copied().collect();
+    indices
+        .into_iter()
+        .filter_map(
+            |device_index| match (previous.get(&device_index), new.get(&device_index)) {
+                (None, Some(_)) => Some(DeviceEvent::Added { device_index }),
+                (Some(_), None) => Some(DeviceEvent::Removed { device_index }),
+                (Some(before), Some(after))
+                    if before != after || changed.contains(&device_index) =>
+                {
+                    Some(DeviceEvent::Changed { device_index })
+                }
+                _ => None,
+            },
+        )
+        .collect()
+}
+
+fn run(
+    lister: DeviceLister,
+    source: Source,
+    mut snapshot: Snapshot,
+    sender: UnboundedSender<DeviceResult<DeviceEvent>>,
+) {
+    while !sender.is_closed() {
+        let mut triggers = match source.wait(POLL_INTERVAL) {
+            Ok(Some(triggers)) if !triggers.is_empty() => triggers,
+            Ok(_) => continue,
+            Err(e) => {
+                let _ = sender.unbounded_send(Err(e.into()));
+                return;
+            }
+        };
+        for _ in 0..MAX_DEBOUNCES {
+            match source.wait(DEBOUNCE) {
+                Ok(Some(more)) => triggers.extend(more),
+                Ok(None) | Err(_) => break,
+            }
+        }
+
+        let changed: BTreeSet<u8> = triggers
+            .iter()
+            .filter_map(|trigger| match trigger {
+                Trigger::Changed(idx) => Some(*idx),
+                _ => None,
+            })
+            .collect();
+        let gone = triggers.contains(&Trigger::Gone);
+        match scan(&lister) {
+            Ok(new) => {
+                for event in diff(&snapshot, &new, &changed) {
+                    let _ = sender.unbounded_send(Ok(event));
+                }
+                snapshot = new;
+            }
+            // The error below is enough.
+            Err(_) if gone => {}
+            Err(e) => {
+                let _ = sender.unbounded_send(Err(e));
+            }
+        }
+
+        if gone {
+            let _ = sender.unbounded_send(Err(DeviceError::file_not_found(
+                lister.devfs_root().display(),
+            )));
+            return;
+        }
+    }
+}
+
+#[derive(Copy, Clone, Debug, Eq, PartialEq)]
+enum Trigger {
+    /// Device files may have been added or removed.
+    Rescan,
+    /// The kernel reported a change of the device.
+    Changed(u8),
+    /// The devfs root is no longer watched.
+    Gone,
+}
+
+enum Source {
+    Uevent(OwnedFd),
+    Inotify(OwnedFd),
+}
+
+impl Source {
+    fn open(devfs: &Path) -> io::Result<Self> {
+        if devfs.canonicalize()? == Path::new(crate::list::DEFAULT_DEVFS) {
+            match open_uevent_socket() {
+                Ok(fd) => return Ok(Self::Uevent(fd)),
+                Err(e) => tracing::debug!("Falling back to inotify without uevents: {}", e),
+            }
+        }
+        open_inotify(devfs).map(Self::Inotify)
+    }
+
+    /// Waits for events up to the timeout, and returns triggers of those about device files,
+    /// or `None` if no events have arrived.
+    fn wait(&self, timeout: Duration) -> io::Result<Option<Vec<Trigger>>> {
+        let fd = match self {
+            Self::Uevent(fd) | Self::Inotify(fd) => fd,
+        };
+        let mut pollfd = libc::pollfd {
+            fd: fd.as_raw_fd(),
+            events: libc::POLLIN,
+            revents: 0,
+        };
+        // SAFETY: `pollfd` is a valid array of one element.
+        let ret = unsafe { libc::poll(&mut pollfd, 1, timeout.as_millis() as libc::c_int) };
+        if ret < 0 {
+            let e = io::Error::last_os_error();
+            return match e.kind() {
+                io::ErrorKind::Interrupted => Ok(None),
+                _ => Err(e),
+            };
+        }
+        if ret == 0 {
+            return Ok(None);
+        }
+
+        let mut triggers = vec![];
+        let mut buf = vec![0u8; 16 * 1024];
+        loop {
+            // SAFETY: `buf` is valid for writes of its length.
+            let len = unsafe { libc::read(fd.as_raw_fd(), buf.as_mut_ptr().cast(), buf.len()) };
+            if len < 0 {
+                let e = io::Error::last_os_error();
+                match e.kind() {
+                    io::ErrorKind::WouldBlock => break,
+                    io::ErrorKind::Interrupted => continue,
+                    // Uevents have been dropped, so something may have changed.
+                    _ if e.raw_os_error() == Some(libc::ENOBUFS) => {
+                        triggers.push(Trigger::Rescan);
+                        continue;
+                    }
+                    _ => return Err(e),
+                }
+            }
+            let buf = &buf[..len as usize];
+            match self {
+                Self::Uevent(_) => triggers.extend(parse_uevent(buf)),
+                Self::Inotify(_) => triggers.extend(parse_inotify_events(buf)),
+            }
+        }
+        Ok(Some(triggers))
+    }
+}
+
+fn open_uevent_socket() -> io::Result<OwnedFd> {
+    // SAFETY: plain system calls with valid arguments.
+    unsafe {
+        let fd = libc::socket(
+            libc::AF_NETLINK,
+            libc::SOCK_DGRAM | libc::SOCK_CLOEXEC | libc::SOCK_NONBLOCK,
+            libc::NETLINK_KOBJECT_UEVENT,
+        );
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mut addr: libc::sockaddr_nl = mem::zeroed();
+        addr.nl_family = libc::AF_NETLINK as libc::sa_family_t;
+        // The multicast group of uevents from the kernel, not from udev
+        addr.nl_groups = 1;
+        let ret = libc::bind(
+            fd.as_raw_fd(),
+            &addr as *const libc::sockaddr_nl as *const libc::sockaddr,
+            mem::size_of::<libc::sockaddr_nl>() as libc::socklen_t,
+        );
+        if ret < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
+
+/// Parses a uevent like `add@/devices/...\0ACTION=add\0DEVNAME=npu0pe0\0...`, which is about
+/// a device file if `DEVNAME` is one.
+fn parse_uevent(message: &[u8]) -> Option<Trigger> {
+    let fields: HashMap<&[u8], &[u8]> = message
+        .split(|b| *b == 0)
+        .filter_map(|field| {
+            let eq = field.iter().position(|b| *b == b'=')?;
+            Some((&field[..eq], &field[eq + 1..]))
+        })
+        .collect();
+    let devname = std::str::from_utf8(fields.get(&b"DEVNAME"[..])?).ok()?;
+    let filename = devname.rsplit('/').next()?;
+    let (device_index, _) = devfs::parse_indices(filename).ok()?;
+    match *fields.get(&b"ACTION"[..])? {
+        b"change" => Some(Trigger::Changed(device_index)),
+        _ => Some(Trigger::Rescan),
+    }
+}
+
+fn open_inotify(devfs: &Path) -> io::Result<OwnedFd> {
+    let path = CString::new(devfs.as_os_str().as_bytes())
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+    // SAFETY: plain system calls with valid arguments.
+    unsafe {
+        let fd = libc::inotify_init1(libc::IN_CLOEXEC | libc::IN_NONBLOCK);
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = OwnedFd::from_raw_fd(fd);
+
+        let mask = libc::IN_CREATE
+            | libc::IN_DELETE
+            | libc::IN_MOVED_FROM
+            | libc::IN_MOVED_TO
+            | libc::IN_ATTRIB
+            | libc::IN_DELETE_SELF
+            | libc::IN_MOVE_SELF
+            | libc::IN_ONLYDIR;
+        if libc::inotify_add_watch(fd.as_raw_fd(), path.as_ptr(), mask) < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(fd)
+    }
+}
+
+/// Parses `inotify_event`s, which are about device files if named like them.
+fn parse_inotify_events(mut buf: &[u8]) -> Vec<Trigger> {
+    const HEADER: usize = mem::size_of::<libc::inotify_event>();
+
+    let mut triggers = vec![];
+    while buf.len() >= HEADER {
+        // SAFETY: `buf` holds a header, which may not be aligned.
+        let event: libc::inotify_event = unsafe { std::ptr::read_unaligned(buf.as_ptr().cast()) };
+        let end = (HEADER + event.len as usize).min(buf.len());
+        let name = &buf[HEADER..end];
+        let name = &name[..name.iter().position(|b| *b == 0).unwrap_or(name.len())];
+        buf = &buf[end..];
+
+        let is_device_file = std::str::from_utf8(name)
+            .ok()
+            .filter(|name| devfs::parse_indices(name).is_ok())
+            .is_some();
+        if event.mask & (libc::IN_IGNORED | libc::IN_DELETE_SELF | libc::IN_MOVE_SELF) != 0 {
+            triggers.push(Trigger::Gone);
+        } else if is_device_file || event.mask & libc::IN_Q_OVERFLOW != 0 {
+            // Events may have been dropped on an overflow, so something may have changed.
+            triggers.push(Trigger::Rescan);
+        }
+    }
+    triggers
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+    use std::time::Instant;
+
+    use futures::StreamExt;
+    use tokio::time::timeout;
+
+    use super::*;
+
+    async fn next(watcher: &mut DeviceWatcher) -> DeviceResult<DeviceEvent> {
+        timeout(Duration::from_secs(5), watcher.next())
+            .await
+            .expect("no event")
+            .expect("the stream ended")
+    }
+
+    fn create(devfs: &Path, idx: u8) -> io::Result<()> {
+        for name in ["", "pe0", "pe1", "pe0-1"] {
+            fs::write(devfs.join(format!("npu{}{}", idx, name)), "")?;
+        }
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn test_watch() -> Result<(), Box<dyn std::error::Error>> {
+        let devfs = tempfile::tempdir()?;
+        create(devfs.path(), 0)?;
+        let lister = DeviceLister::new()
+            .devfs(devfs.path())
+            .allow_regular_files(true);
+        let mut watcher = lister.watch()?;
+
+        create(devfs.path(), 1)?;
+        assert_eq!(
+            next(&mut watcher).await?,
+            DeviceEvent::Added { device_index: 1 }
+        );
+
+        // Device files replaced with new ones, without a moment when they are missing
+        for name in ["npu0", "npu0pe0", "npu0pe1", "npu0pe0-1"] {
+            // Unrelated files are ignored
+            let staged = devfs.path().join(format!(".{}", name));
+            fs::write(&staged, "")?;
+            fs::rename(staged, devfs.path().join(name))?;
+        }
+        assert_eq!(
+            next(&mut watcher).await?,
+            DeviceEvent::Changed { device_index: 0 }
+        );
+
+        fs::remove_file(devfs.path().join("npu1pe0-1"))?;
+        assert_eq!(
+            next(&mut watcher).await?,
+            DeviceEvent::Changed { device_index: 1 }
+        );
+        for name in ["npu1", "npu1pe0", "npu1pe1"] {
+            fs::remove_file(devfs.path().join(name))?;
+        }
+        assert_eq!(
+            next(&mut watcher).await?,
+            DeviceEvent::Removed { device_index: 1 }
+        );
+
+        // The stream ends with an error once the root is removed, possibly after npu0 is.
+        let path = devfs.path().to_path_buf();
+        devfs.close()?;
+        while let Ok(event) = next(&mut watcher).await {
+            assert_eq!(event, DeviceEvent::Removed { device_index: 0 });
+        }
+        assert!(timeout(Duration::from_secs(5), watcher.next())
+            .await?
+            .is_none());
+
+        assert!(DeviceLister::new().devfs(path).watch().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_drop_watcher() -> Result<(), Box<dyn std::error::Error>> {
+        let devfs = tempfile::tempdir()?;
+        let (watcher, handle) = spawn(&DeviceLister::new().devfs(devfs.path()))?;
+        drop(watcher);
+
+        let started = Instant::now();
+        while !handle.is_finished() {
+            assert!(started.elapsed() < Duration::from_secs(5));
+            thread::sleep(DEBOUNCE);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn test_parse_uevent() {
+        let uevent = b"add@/devices/pci0000:00/0000:00:01.0/npu_mgmt/npu0pe0\0ACTION=add\0\
+            DEVPATH=/devices/pci0000:00/0000:00:01.0/npu_mgmt/npu0pe0\0SUBSYSTEM=npu\0\
+            MAJOR=508\0MINOR=1\0DEVNAME=npu0pe0\0SEQNUM=4242\0";
+        assert_eq!(parse_uevent(uevent), Some(Trigger::Rescan));
+        assert_eq!(
+            parse_uevent(b"change@/devices/x\0ACTION=change\0DEVNAME=npu3\0"),
+            Some(Trigger::Changed(3))
+        );
+        assert_eq!(
+            parse_uevent(b"add@/devices/x\0ACTION=add\0DEVNAME=nvme0n1\0"),
+            None
+        );
+        assert_eq!(parse_uevent(b"add@/devices/x\0ACTION=add\0"), None);
+    }
+}
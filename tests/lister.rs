@@ -0,0 +1,52 @@
+use furiosa_device::{DeviceConfig, DeviceLister, DeviceResult};
+
+fn lister() -> DeviceLister {
+    DeviceLister::new()
+        .devfs("test_data/test-0/dev")
+        .sysfs("test_data/test-0/sys")
+        .allow_regular_files(true)
+}
+
+#[tokio::test]
+async fn test_list_devices() -> DeviceResult<()> {
+    let devices = lister().list_devices().await?;
+    let names: Vec<String> = devices.iter().map(|device| device.name()).collect();
+    assert_eq!(names, vec!["npu0", "npu1"]);
+    for device in &devices {
+        assert_eq!(device.cores(), &vec![0, 1]);
+        assert_eq!(device.dev_files().len(), 4);
+    }
+
+    // The fake devfs consists of regular files, which are not device files by default.
+    let strict = lister().allow_regular_files(false);
+    assert!(strict.list_devices().await?.is_empty());
+    Ok(())
+}
+
+#[tokio::test]
+async fn test_find_and_get_devices() -> DeviceResult<()> {
+    let lister = lister();
+    let found = lister
+        .find_devices(&DeviceConfig::warboy().single().count(2))
+        .await?;
+    let found: Vec<String> = found
+        .iter()
+        .map(|file| file.filename().to_string())
+        .collect();
+    assert_eq!(found, vec!["npu0pe0", "npu0pe1"]);
+
+    let file = lister.get_device("npu1pe0-1").await?;
+    assert_eq!(file.path(), &lister.devfs_root().join("npu1pe0-1"));
+    assert!(lister.get_device("npu2").await.is_err());
+    Ok(())
+}
+
+#[cfg(feature = "blocking")]
+#[test]
+fn test_blocking_lister() -> DeviceResult<()> {
+    let lister: furiosa_device::blocking::DeviceLister = lister().into();
+    assert_eq!(lister.list_devices()?.len(), 2);
+    let found = lister.find_devices(&DeviceConfig::warboy().fused().count(2))?;
+    assert_eq!(found.len(), 2);
+    Ok(())
+}